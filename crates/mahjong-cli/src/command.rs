@@ -0,0 +1,404 @@
+use mahjong_client::{
+    Call,
+    protocol::{
+        chat::{Chat, Reaction},
+        enums::TileKind,
+        lobby::{Ruleset, Tier},
+        stats::{Board, LeaderboardQuery},
+        tournament::TournamentRequest,
+    },
+};
+
+pub const HELP: &str = "\
+commands:
+  rooms          list the lobby's open rooms
+  create <rules> [password]
+                 open a room (hanchan, tonpuusen or sanma) and sit down
+  join <code> [password]
+                 sit down in the room with that code
+  play           sit down in any open room
+  queue [rules] [tier]
+                 wait for the ranked queue to seat you (hanchan and general when left out;
+                 tiers: general, advanced, expert, phoenix)
+  leave          leave the matchmaking queue
+  profile        show your rating and rank
+  stats [id]     show a player's statistics (yours when left out)
+  top [board] [rules] [season] [page]
+                 show a leaderboard page (boards: rating, games, placement; rules or all;
+                 season number or now; page from 1)
+  tour           list the tournaments
+  tour show|join|leave|play <id>
+                 show standings and seating, register, withdraw or take your seat
+  tour create <rules> <rounds> <name>, tour start <id>, tour swap <id> <player> <player>,
+  tour forfeit <id> <player>
+                 run a tournament (admins only)
+  d <tile>       discard a tile in MPSZ notation (d 5m, d 7z)
+  t, draw        draw a tile
+  pon, chi, kan  call the last discard (not supported by the server yet)
+  ron, tsumo     declare a win (not supported by the server yet)
+  riichi         declare riichi (not supported by the server yet)
+  init           ask for the starting hand again
+  sync           ask for a snapshot of the whole table
+  replay         ask again for the match events missed since the last one seen
+  say <text>     chat with the table
+  react <name>   send a reaction: gl, nice, thanks, oops, hurry, gg
+  mute <id>      stop showing a player's chat (unmute <id> to undo)
+  ready          mark yourself as ready
+  show           render the table
+  ping           measure the round trip (shown by `show`)
+  help           show this list
+  q, quit        leave";
+
+pub enum Command {
+    Rooms,
+    Create(Ruleset, Option<String>),
+    Join(String, Option<String>),
+    QuickPlay,
+    Queue(Ruleset, Tier),
+    LeaveQueue,
+    Profile,
+    Stats(Option<i32>),
+    Leaderboard(LeaderboardQuery),
+    Tournament(TournamentRequest),
+    Discard(TileKind),
+    Draw,
+    Call(Call),
+    Riichi,
+    Initialize,
+    Resync,
+    Replay,
+    Chat(Chat),
+    Ready,
+    Show,
+    Ping,
+    Help,
+    Quit,
+}
+
+impl Command {
+    pub fn parse(line: &str) -> Result<Self, String> {
+        let mut words = line.split_whitespace();
+        let Some(name) = words.next() else {
+            return Err("empty command".to_string());
+        };
+
+        let command = match name.to_ascii_lowercase().as_str() {
+            "rooms" => Command::Rooms,
+            "create" => {
+                let Some(ruleset) = words.next().and_then(ruleset) else {
+                    return Err("create takes hanchan, tonpuusen or sanma".to_string());
+                };
+                Command::Create(ruleset, words.next().map(str::to_string))
+            }
+            "join" => {
+                let Some(code) = words.next() else {
+                    return Err("missing room code, e.g. `join K7QZ2M`".to_string());
+                };
+                Command::Join(code.to_string(), words.next().map(str::to_string))
+            }
+            "play" => Command::QuickPlay,
+            "queue" => {
+                let ruleset = match words.next() {
+                    None => Ruleset::default(),
+                    Some(word) => ruleset(word)
+                        .ok_or("queue takes hanchan, tonpuusen or sanma".to_string())?,
+                };
+                let tier = match words.next() {
+                    None => Tier::default(),
+                    Some(word) => tier(word)
+                        .ok_or("the tier is general, advanced, expert or phoenix".to_string())?,
+                };
+                Command::Queue(ruleset, tier)
+            }
+            "leave" => Command::LeaveQueue,
+            "profile" => Command::Profile,
+            "stats" => match words.next() {
+                None => Command::Stats(None),
+                Some(id) => match id.parse::<i32>() {
+                    Ok(id) => Command::Stats(Some(id)),
+                    Err(_) => return Err("stats takes a player id".to_string()),
+                },
+            },
+            "top" => {
+                let board = match words.next() {
+                    None => Board::Rating,
+                    Some(word) => {
+                        board(word).ok_or("the board is rating, games or placement".to_string())?
+                    }
+                };
+                let ruleset = match words.next() {
+                    None | Some("all") => None,
+                    Some(word) => Some(
+                        ruleset(word)
+                            .ok_or("top takes hanchan, tonpuusen, sanma or all".to_string())?,
+                    ),
+                };
+                let season = match words.next() {
+                    None | Some("now") => None,
+                    Some(word) => match word.parse::<u32>() {
+                        Ok(season) if season > 0 => Some(season),
+                        _ => return Err("the season is a number from 1 or now".to_string()),
+                    },
+                };
+                let page = match words.next() {
+                    None => 0,
+                    Some(word) => match word.parse::<u32>() {
+                        Ok(page) if page > 0 => page - 1,
+                        _ => return Err("the page is a number from 1".to_string()),
+                    },
+                };
+                Command::Leaderboard(LeaderboardQuery {
+                    board,
+                    ruleset,
+                    season,
+                    page,
+                })
+            }
+            "tour" => {
+                let Some(action) = words.next() else {
+                    return Ok(Command::Tournament(TournamentRequest::List));
+                };
+                let mut number = |what: &str| {
+                    let word = words.next().ok_or(format!("tour {action} takes {what}"))?;
+                    return word
+                        .parse::<i32>()
+                        .map_err(|_| format!("`{word}` is not {what}"));
+                };
+                let request = match action.to_ascii_lowercase().as_str() {
+                    "show" => TournamentRequest::Show(number("a tournament id")? as u32),
+                    "join" => TournamentRequest::Register(number("a tournament id")? as u32),
+                    "leave" => TournamentRequest::Withdraw(number("a tournament id")? as u32),
+                    "play" => TournamentRequest::Play(number("a tournament id")? as u32),
+                    "start" => TournamentRequest::Start(number("a tournament id")? as u32),
+                    "swap" => TournamentRequest::Swap {
+                        tournament: number("a tournament id")? as u32,
+                        first: number("a player id")?,
+                        second: number("a player id")?,
+                    },
+                    "forfeit" => TournamentRequest::Forfeit {
+                        tournament: number("a tournament id")? as u32,
+                        player: number("a player id")?,
+                    },
+                    // The name takes the rest of the line.
+                    "create" => {
+                        let usage = "usage: tour create <rules> <rounds> <name>".to_string();
+                        let ruleset = words.next().and_then(ruleset).ok_or(usage.clone())?;
+                        let rounds = words.next().and_then(|w| w.parse::<u8>().ok());
+                        let name = words.collect::<Vec<_>>().join(" ");
+                        return match rounds {
+                            Some(rounds) if rounds > 0 && !name.is_empty() => {
+                                Ok(Command::Tournament(TournamentRequest::Create {
+                                    ruleset,
+                                    rounds,
+                                    name,
+                                }))
+                            }
+                            _ => Err(usage),
+                        };
+                    }
+                    other => return Err(format!("unknown tour action `{other}`, type `help`")),
+                };
+                Command::Tournament(request)
+            }
+            "d" | "discard" => {
+                let Some(tile) = words.next() else {
+                    return Err("missing tile, e.g. `d 5m`".to_string());
+                };
+                let kind = tile
+                    .parse::<TileKind>()
+                    .map_err(|_| format!("`{tile}` is not a tile in MPSZ notation"))?;
+                Command::Discard(kind)
+            }
+            "t" | "draw" => Command::Draw,
+            "pon" => Command::Call(Call::Pon),
+            "chi" => Command::Call(Call::Chi),
+            "kan" => Command::Call(Call::Kan),
+            "ron" => Command::Call(Call::Ron),
+            "tsumo" => Command::Call(Call::Tsumo),
+            "riichi" => Command::Riichi,
+            "init" => Command::Initialize,
+            "sync" => Command::Resync,
+            "replay" => Command::Replay,
+            // Free text takes the rest of the line.
+            "say" => {
+                let text = line.trim_start()[name.len()..].trim();
+                if text.is_empty() {
+                    return Err("missing message, e.g. `say good luck`".to_string());
+                }
+                return Ok(Command::Chat(Chat::Message(text.to_string())));
+            }
+            "react" => {
+                let reaction = match words.next() {
+                    Some("gl") => Reaction::GoodLuck,
+                    Some("nice") => Reaction::NiceHand,
+                    Some("thanks") => Reaction::Thanks,
+                    Some("oops") => Reaction::Oops,
+                    Some("hurry") => Reaction::Hurry,
+                    Some("gg") => Reaction::GoodGame,
+                    _ => return Err("react takes gl, nice, thanks, oops, hurry or gg".to_string()),
+                };
+                Command::Chat(Chat::Reaction(reaction))
+            }
+            "mute" | "unmute" => {
+                let Some(id) = words.next().and_then(|id| id.parse::<i32>().ok()) else {
+                    return Err(format!("{name} takes a player id"));
+                };
+                match name.eq_ignore_ascii_case("mute") {
+                    true => Command::Chat(Chat::Mute(id)),
+                    false => Command::Chat(Chat::Unmute(id)),
+                }
+            }
+            "ready" => Command::Ready,
+            "show" => Command::Show,
+            "ping" => Command::Ping,
+            "help" | "?" => Command::Help,
+            "q" | "quit" | "exit" => Command::Quit,
+            other => return Err(format!("unknown command `{other}`, type `help`")),
+        };
+
+        if words.next().is_some() {
+            return Err(format!("too many arguments for `{name}`"));
+        }
+
+        return Ok(command);
+    }
+}
+
+fn tier(word: &str) -> Option<Tier> {
+    match word.to_ascii_lowercase().as_str() {
+        "general" => Some(Tier::General),
+        "advanced" => Some(Tier::Advanced),
+        "expert" => Some(Tier::Expert),
+        "phoenix" => Some(Tier::Phoenix),
+        _ => None,
+    }
+}
+
+fn board(word: &str) -> Option<Board> {
+    match word.to_ascii_lowercase().as_str() {
+        "rating" => Some(Board::Rating),
+        "games" => Some(Board::Games),
+        "placement" => Some(Board::Placement),
+        _ => None,
+    }
+}
+
+fn ruleset(word: &str) -> Option<Ruleset> {
+    match word.to_ascii_lowercase().as_str() {
+        "hanchan" => Some(Ruleset::Hanchan),
+        "tonpuusen" => Some(Ruleset::Tonpuusen),
+        "sanma" => Some(Ruleset::Sanma),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_commands() {
+        assert!(matches!(
+            Command::parse("d 5m"),
+            Ok(Command::Discard(TileKind::Manzu5))
+        ));
+        assert!(matches!(
+            Command::parse("  PON "),
+            Ok(Command::Call(Call::Pon))
+        ));
+        assert!(matches!(Command::parse("riichi"), Ok(Command::Riichi)));
+        assert!(Command::parse("d").is_err());
+        assert!(Command::parse("d 0z").is_err());
+        assert!(Command::parse("pon 5m").is_err());
+        assert!(Command::parse("shout").is_err());
+        assert!(matches!(
+            Command::parse("say  good luck all"),
+            Ok(Command::Chat(Chat::Message(text))) if text == "good luck all"
+        ));
+        assert!(matches!(
+            Command::parse("react gg"),
+            Ok(Command::Chat(Chat::Reaction(Reaction::GoodGame)))
+        ));
+        assert!(matches!(
+            Command::parse("mute 3"),
+            Ok(Command::Chat(Chat::Mute(3)))
+        ));
+        assert!(Command::parse("say").is_err());
+        assert!(Command::parse("react wave").is_err());
+        assert!(matches!(
+            Command::parse("create sanma hunter2"),
+            Ok(Command::Create(Ruleset::Sanma, Some(password))) if password == "hunter2"
+        ));
+        assert!(matches!(
+            Command::parse("join k7qz2m"),
+            Ok(Command::Join(code, None)) if code == "k7qz2m"
+        ));
+        assert!(Command::parse("create riichi").is_err());
+        assert!(matches!(
+            Command::parse("queue"),
+            Ok(Command::Queue(Ruleset::Hanchan, Tier::General))
+        ));
+        assert!(matches!(
+            Command::parse("queue Sanma"),
+            Ok(Command::Queue(Ruleset::Sanma, Tier::General))
+        ));
+        assert!(matches!(
+            Command::parse("queue tonpuusen expert"),
+            Ok(Command::Queue(Ruleset::Tonpuusen, Tier::Expert))
+        ));
+        assert!(Command::parse("queue sanma now").is_err());
+        assert!(Command::parse("queue sanma expert now").is_err());
+        assert!(matches!(Command::parse("stats"), Ok(Command::Stats(None))));
+        assert!(matches!(
+            Command::parse("stats 12"),
+            Ok(Command::Stats(Some(12)))
+        ));
+        assert!(Command::parse("stats bunny").is_err());
+        assert!(matches!(
+            Command::parse("top"),
+            Ok(Command::Leaderboard(LeaderboardQuery {
+                board: Board::Rating,
+                ruleset: None,
+                season: None,
+                page: 0
+            }))
+        ));
+        assert!(matches!(
+            Command::parse("top placement sanma 3 2"),
+            Ok(Command::Leaderboard(LeaderboardQuery {
+                board: Board::Placement,
+                ruleset: Some(Ruleset::Sanma),
+                season: Some(3),
+                page: 1
+            }))
+        ));
+        assert!(Command::parse("top wins").is_err());
+        assert!(Command::parse("top games all now 0").is_err());
+        assert!(matches!(
+            Command::parse("tour"),
+            Ok(Command::Tournament(TournamentRequest::List))
+        ));
+        assert!(matches!(
+            Command::parse("tour swap 2 7 12"),
+            Ok(Command::Tournament(TournamentRequest::Swap {
+                tournament: 2,
+                first: 7,
+                second: 12
+            }))
+        ));
+        assert!(matches!(
+            Command::parse("tour forfeit 2 7"),
+            Ok(Command::Tournament(TournamentRequest::Forfeit {
+                tournament: 2,
+                player: 7
+            }))
+        ));
+        assert!(matches!(
+            Command::parse("tour create hanchan 4 October cup"),
+            Ok(Command::Tournament(TournamentRequest::Create { rounds: 4, name, .. }))
+                if name == "October cup"
+        ));
+        assert!(Command::parse("tour play").is_err());
+        assert!(Command::parse("tour create hanchan 0 Cup").is_err());
+    }
+}
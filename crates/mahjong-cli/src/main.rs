@@ -0,0 +1,289 @@
+//! Terminal client for playing and debugging matches.
+//!
+//! `mahjong-cli ((--register | --login) <username> --password <password> | [--id 7] (--alias Bunny | --token <hex>) | --spectate <public|full|player id> [--table 2 [--password <room password>]]) [--addr 127.0.0.1:3000] [--json]`
+use mahjong_client::{
+    ClientError, Connection, ServerEvent,
+    protocol::{
+        account::Credentials,
+        models::{JoinRequest, SpectatorMode},
+        session::SessionToken,
+        version::Capabilities,
+    },
+};
+use tokio::{
+    io::{AsyncBufReadExt, BufReader},
+    net::TcpStream,
+};
+use tokio_stream::StreamExt;
+
+use crate::{
+    command::{Command, HELP},
+    table::Table,
+};
+
+mod command;
+mod table;
+
+const USAGE: &str = "usage: mahjong-cli ((--register | --login) <username> --password <password> | [--id <id>] (--alias <alias> | --token <session token>) | --spectate <public|full|player id> [--table <table id> [--password <room password>]]) [--addr <host:port>] [--json]";
+
+// An account, a guest, the session token printed by an earlier run, or no seat at all.
+enum Seating {
+    Register(Credentials),
+    Login(Credentials),
+    Join(JoinRequest),
+    Rejoin(SessionToken),
+    Spectate(SpectatorMode, Option<i32>, Option<String>),
+}
+
+struct Options {
+    id: i32,
+    addr: String,
+    seating: Seating,
+    capabilities: Capabilities,
+}
+
+impl Options {
+    fn parse(mut args: impl Iterator<Item = String>) -> Result<Self, String> {
+        let mut addr = "127.0.0.1:3000".to_string();
+        let mut id = None;
+        let mut alias = None;
+        let mut capabilities = Capabilities::default();
+        let mut token = None;
+        let mut spectate = None;
+        let mut table = None;
+        let mut account = None;
+        let mut password = None;
+
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--addr" => addr = args.next().ok_or("--addr needs a value")?,
+                "--alias" => alias = Some(args.next().ok_or("--alias needs a value")?),
+                "--id" => {
+                    let value = args.next().ok_or("--id needs a value")?;
+                    id = Some(value.parse::<i32>().map_err(|_| "--id must be an i32")?);
+                }
+                "--json" => capabilities = Capabilities::JSON_PAYLOADS,
+                "--token" => {
+                    let value = args.next().ok_or("--token needs a value")?;
+                    let parsed = value.parse::<SessionToken>();
+                    token = Some(parsed.map_err(|_| "--token must be 64 hex characters")?);
+                }
+                "--spectate" => {
+                    let value = args.next().ok_or("--spectate needs a value")?;
+                    spectate = Some(match value.as_str() {
+                        "public" => SpectatorMode::Public,
+                        "full" => SpectatorMode::Full,
+                        id => SpectatorMode::Player(
+                            id.parse::<i32>()
+                                .map_err(|_| "--spectate takes public, full or a player id")?,
+                        ),
+                    });
+                }
+                "--register" | "--login" => {
+                    let username = args.next().ok_or(format!("{arg} needs a username"))?;
+                    account = Some((arg == "--register", username));
+                }
+                "--password" => password = Some(args.next().ok_or("--password needs a value")?),
+                "--table" => {
+                    let value = args.next().ok_or("--table needs a value")?;
+                    table = Some(value.parse::<i32>().map_err(|_| "--table must be an i32")?);
+                }
+                other => return Err(format!("unknown argument `{other}`")),
+            }
+        }
+
+        // Spectators have no player id of their own.
+        if let Some(mode) = spectate {
+            if alias.is_some() || token.is_some() {
+                return Err("--spectate does not take a seat, drop --alias/--token".to_string());
+            }
+            // The password opens a locked room, so it needs the room's table.
+            if password.is_some() && table.is_none() {
+                return Err("--password with --spectate needs --table".to_string());
+            }
+            return Ok(Self {
+                id: id.unwrap_or(0),
+                addr,
+                seating: Seating::Spectate(mode, table, password),
+                capabilities,
+            });
+        }
+
+        if table.is_some() {
+            return Err("--table only picks the table to spectate".to_string());
+        }
+
+        // Accounts get their id from the server.
+        if let Some((register, username)) = account {
+            if alias.is_some() || token.is_some() {
+                return Err("--register/--login take no --alias or --token".to_string());
+            }
+            let Some(password) = password else {
+                return Err("--password is required with --register/--login".to_string());
+            };
+            let credentials = Credentials { username, password };
+            return Ok(Self {
+                id: 0,
+                addr,
+                seating: match register {
+                    true => Seating::Register(credentials),
+                    false => Seating::Login(credentials),
+                },
+                capabilities,
+            });
+        }
+        if password.is_some() {
+            return Err("--password goes with --register or --login".to_string());
+        }
+
+        // Servers from version 4 assign guest ids and ignore --id.
+        let id = id.unwrap_or(0);
+        let seating = match (alias, token) {
+            (Some(alias), None) => Seating::Join(JoinRequest { id, alias }),
+            (None, Some(token)) => Seating::Rejoin(token),
+            _ => return Err("pass either --alias or --token".to_string()),
+        };
+
+        return Ok(Self {
+            id,
+            addr,
+            seating,
+            capabilities,
+        });
+    }
+}
+
+#[tokio::main]
+async fn main() -> Result<(), ClientError> {
+    let options = match Options::parse(std::env::args().skip(1)) {
+        Ok(options) => options,
+        Err(message) => {
+            eprintln!("{message}\n{USAGE}");
+            std::process::exit(2);
+        }
+    };
+
+    let stream = TcpStream::connect(&options.addr).await?;
+    let mut connection = match &options.seating {
+        Seating::Register(credentials) => {
+            Connection::register(stream, credentials, options.capabilities).await?
+        }
+        Seating::Login(credentials) => {
+            Connection::login(stream, credentials, options.capabilities).await?
+        }
+        Seating::Join(request) => Connection::join(stream, request, options.capabilities).await?,
+        Seating::Rejoin(token) => Connection::rejoin(stream, token, options.capabilities).await?,
+        Seating::Spectate(mode, table, password) => {
+            let password = password.as_deref();
+            Connection::spectate(stream, *mode, *table, password, options.capabilities).await?
+        }
+    };
+
+    let handshake = connection.handshake();
+    println!(
+        "connected to {} (protocol v{}, {:?} payloads)",
+        options.addr,
+        handshake.version,
+        connection.codec()
+    );
+    let mut events = connection.take_events().ok_or(ClientError::Closed)?;
+    let pid = match connection.identity() {
+        None => options.id,
+        Some(identity) => {
+            let kind = if identity.guest { "guest" } else { "account" };
+            println!("playing as {kind} {}", identity.player_id);
+            identity.player_id
+        }
+    };
+    let mut table = Table::new(pid);
+    let mut lines = BufReader::new(tokio::io::stdin()).lines();
+    match (&options.seating, connection.session()) {
+        (Seating::Spectate(..), _) => {}
+        (_, Some(token)) => {
+            println!("session token (reconnect with --token): {token}");
+            connection.initialize().await?;
+        }
+        // Servers speaking version 3 and up seat players from the lobby.
+        (_, None) => println!("in the lobby: `rooms`, `create`, `join`, `play` or `queue`"),
+    }
+
+    loop {
+        tokio::select! {
+            event = events.next() => {
+                let Some(event) = event else {
+                    println!("connection closed by the server");
+                    return Ok(());
+                };
+                let seated = matches!(event, ServerEvent::Seated(_));
+                println!("< {}", table.apply(event));
+                if let (true, Some(token)) = (seated, connection.session()) {
+                    println!("session token (reconnect with --token): {token}");
+                    connection.initialize().await?;
+                }
+            }
+            line = lines.next_line() => {
+                let Some(line) = line? else {
+                    return Ok(());
+                };
+                if line.trim().is_empty() {
+                    continue;
+                }
+
+                match Command::parse(&line) {
+                    Err(message) => println!("{message}"),
+                    Ok(Command::Quit) => return Ok(()),
+                    Ok(command) => run(&connection, &mut table, command).await?,
+                }
+            }
+        }
+    }
+}
+
+async fn run(
+    connection: &Connection,
+    table: &mut Table,
+    command: Command,
+) -> Result<(), ClientError> {
+    match command {
+        Command::Discard(kind) => match table.take(kind) {
+            None => println!("no {kind} in hand"),
+            Some(tile) => connection.discard(tile).await?,
+        },
+        Command::Draw => connection.draw().await?,
+        // The server cannot act on calls yet, so they are not sent either.
+        Command::Call(call) => {
+            let name = format!("{call:?}").to_lowercase();
+            println!("{name} is not supported by the server yet");
+        }
+        // The protocol has no riichi action yet, so there is nothing to send.
+        Command::Riichi => println!("riichi is not supported by the server yet"),
+        Command::Initialize => connection.initialize().await?,
+        Command::Resync => connection.resync().await?,
+        Command::Replay => connection.replay().await?,
+        Command::Rooms => connection.rooms().await?,
+        Command::Create(ruleset, password) => {
+            connection.create_room(ruleset, password.as_deref()).await?
+        }
+        Command::Join(code, password) => connection.join_room(&code, password.as_deref()).await?,
+        Command::QuickPlay => connection.quick_play().await?,
+        Command::Queue(ruleset, tier) => connection.queue(ruleset, tier).await?,
+        Command::Profile => connection.profile().await?,
+        Command::LeaveQueue => connection.leave_queue().await?,
+        Command::Stats(player_id) => connection.stats(player_id).await?,
+        Command::Leaderboard(query) => connection.leaderboard(query).await?,
+        Command::Tournament(request) => connection.tournament(&request).await?,
+        Command::Chat(chat) => connection.chat(&chat).await?,
+        Command::Ready => connection.ready().await?,
+        Command::Show => {
+            println!("{}", table.render());
+            if let Some(rtt) = connection.rtt() {
+                println!("rtt    {} ms", rtt.as_millis());
+            }
+        }
+        Command::Ping => connection.ping().await?,
+        Command::Help => println!("{HELP}"),
+        Command::Quit => {}
+    }
+
+    return Ok(());
+}
@@ -0,0 +1,426 @@
+use std::collections::BTreeMap;
+
+use mahjong_client::{
+    ServerEvent,
+    protocol::{
+        chat::Chat,
+        enums::{Seat, Tile, TileKind},
+        models::MeldFlags,
+    },
+};
+
+/// What this client knows about the match, rebuilt from server events.
+pub struct Table {
+    pub pid: i32,
+    pub seat: Option<Seat>,
+    pub hand: Vec<Tile>,
+    pub ponds: BTreeMap<i32, Vec<TileKind>>,
+    pub melds: BTreeMap<i32, Vec<Vec<TileKind>>>,
+    pub scores: BTreeMap<i32, i32>,
+    pub dora: Vec<TileKind>,
+    pub calls: Option<MeldFlags>,
+}
+
+impl Table {
+    pub fn new(pid: i32) -> Self {
+        Self {
+            pid,
+            seat: None,
+            hand: Vec::new(),
+            ponds: BTreeMap::new(),
+            melds: BTreeMap::new(),
+            scores: BTreeMap::new(),
+            dora: Vec::new(),
+            calls: None,
+        }
+    }
+
+    // Updates the table and returns a line describing the event.
+    pub fn apply(&mut self, event: ServerEvent) -> String {
+        match event {
+            ServerEvent::Connected(handshake, _)
+            | ServerEvent::Reconnected(handshake, _)
+            | ServerEvent::Identified(handshake, _) => {
+                format!("joined (protocol v{})", handshake.version)
+            }
+            ServerEvent::Rooms(rooms) if rooms.is_empty() => {
+                "no open rooms, `create` one or `play`".to_string()
+            }
+            ServerEvent::Rooms(rooms) => {
+                let lines: Vec<String> = rooms
+                    .iter()
+                    .map(|room| {
+                        let lock = if room.locked { ", locked" } else { "" };
+                        format!(
+                            "  {} {:?} {}/{} (table {}{lock})",
+                            room.code, room.ruleset, room.seated, room.seats, room.table_id
+                        )
+                    })
+                    .collect();
+                format!("open rooms:\n{}", lines.join("\n"))
+            }
+            ServerEvent::Seated(seated) => {
+                format!("seated at table {} (room {})", seated.table_id, seated.code)
+            }
+            ServerEvent::Queue(queue) => format!(
+                "queued for {:?} ({:?}): {} waiting, rating {} ± {}, {}s in, bots in {}s",
+                queue.ruleset,
+                queue.tier,
+                queue.waiting,
+                queue.rating,
+                queue.band,
+                queue.waited_ms / 1000,
+                queue.bots_in_ms / 1000
+            ),
+            ServerEvent::LeftQueue => "left the queue".to_string(),
+            ServerEvent::Profile(profile) => {
+                let progress = match profile.promotion {
+                    Some(promotion) => format!("{}/{promotion} points", profile.points),
+                    None => format!("{} points", profile.points),
+                };
+                let kind = if profile.guest { "guest" } else { "account" };
+                format!(
+                    "{kind} {}: rating {}, {} ({progress}), {} ranked games, tiers {:?}",
+                    profile.player_id,
+                    profile.rating,
+                    profile.rank,
+                    profile.ranked_games,
+                    profile.tiers
+                )
+            }
+            ServerEvent::Spectating(handshake, id) => {
+                format!("spectating as {id} (protocol v{})", handshake.version)
+            }
+            ServerEvent::InitialView(view) => {
+                self.seat = Some(view.seat);
+                self.hand = view.hand.iter().map(|tile| **tile).collect();
+                let first = if view.is_first { ", you start" } else { "" };
+                format!("seated {}{first}", view.seat.to_string())
+            }
+            ServerEvent::ReadyAck => "ready".to_string(),
+            ServerEvent::Snapshot(snapshot) => {
+                self.seat = Some(snapshot.seat);
+                self.hand = snapshot.hand;
+                self.calls = snapshot.pending_calls;
+                self.dora = snapshot
+                    .dora_indicators
+                    .iter()
+                    .map(|tile| tile.kind)
+                    .collect();
+                self.ponds.clear();
+                self.melds.clear();
+                self.scores.clear();
+                for seat in snapshot.seats {
+                    let pond = seat.pond.iter().map(|tile| tile.kind).collect();
+                    let melds = seat
+                        .melds
+                        .iter()
+                        .map(|meld| meld.tiles.iter().map(|tile| tile.kind).collect())
+                        .collect();
+                    let melds: Vec<Vec<TileKind>> = melds;
+                    self.ponds.insert(seat.player_id, pond);
+                    if !melds.is_empty() {
+                        self.melds.insert(seat.player_id, melds);
+                    }
+                    self.scores.insert(seat.player_id, seat.score);
+                }
+                format!(
+                    "resynced: turn {}, {} to play",
+                    snapshot.turn,
+                    snapshot.current_seat.to_string()
+                )
+            }
+            ServerEvent::DrawnTile(tile) => {
+                self.calls = None;
+                self.hand.push(tile);
+                format!("drew {}", tile.kind)
+            }
+            ServerEvent::CallsAvailable(flags) => {
+                let line = format!("calls available: {}", available_calls(&flags));
+                self.calls = Some(flags);
+                line
+            }
+            ServerEvent::Dealt(deal) => match deal.hand {
+                Some(hand) => {
+                    let kinds: Vec<TileKind> = hand.iter().map(|tile| tile.kind).collect();
+                    let seat = deal.seat.to_string();
+                    format!(
+                        "player {} dealt {seat}: {}",
+                        deal.player_id,
+                        notation(&kinds)
+                    )
+                }
+                None => format!("player {} sat {}", deal.player_id, deal.seat.to_string()),
+            },
+            // Our own tile already came with DrawnTile; spectators may be shown others'.
+            ServerEvent::Drew(draw) => match draw.tile {
+                Some(tile) if draw.player_id != self.pid => {
+                    format!("player {} drew {}", draw.player_id, tile.kind)
+                }
+                _ => format!("player {} drew", draw.player_id),
+            },
+            ServerEvent::Discarded(discard) => {
+                let pond = self.ponds.entry(discard.player_id).or_default();
+                pond.push(discard.tile_kind);
+                format!(
+                    "player {} discarded {}",
+                    discard.player_id, discard.tile_kind
+                )
+            }
+            ServerEvent::HandEnded(result) => {
+                let wins: Vec<String> = result
+                    .wins
+                    .iter()
+                    .map(|win| {
+                        let how = if win.tsumo { "tsumo" } else { "ron" };
+                        format!("player {} wins {} ({how})", win.player_id, win.value)
+                    })
+                    .collect();
+                match result.dealt_in {
+                    _ if wins.is_empty() => "hand drawn".to_string(),
+                    Some(id) => format!("{}, player {id} dealt in", wins.join(", ")),
+                    None => wins.join(", "),
+                }
+            }
+            ServerEvent::Stats(stats) => format!(
+                "player {}: {} matches, {} hands, win {}, deal-in {}, riichi {}, call {}, \
+                 avg placement {:.2}, avg win {}",
+                stats.player_id,
+                stats.matches,
+                stats.hands,
+                percent(stats.win_rate),
+                percent(stats.deal_in_rate),
+                percent(stats.riichi_rate),
+                percent(stats.call_rate),
+                stats.average_placement,
+                stats
+                    .average_win_value
+                    .map_or("n/a".to_string(), |value| format!("{value:.0}"))
+            ),
+            ServerEvent::Leaderboard(board) => {
+                let rules = board
+                    .ruleset
+                    .map_or("all rules".to_string(), |r| format!("{r:?}"));
+                let lines: Vec<String> = board
+                    .entries
+                    .iter()
+                    .map(|entry| {
+                        format!(
+                            "  {:>3}. {} (player {}) rating {}, {}, {} games, avg placement {:.2}",
+                            entry.position,
+                            entry.username,
+                            entry.player_id,
+                            entry.rating,
+                            entry.rank,
+                            entry.games,
+                            entry.average_placement
+                        )
+                    })
+                    .collect();
+                format!(
+                    "{:?} leaderboard, season {}, {rules}, page {}/{}:\n{}",
+                    board.board,
+                    board.season,
+                    board.page + 1,
+                    board.pages.max(1),
+                    match lines.is_empty() {
+                        true => "  nobody yet".to_string(),
+                        false => lines.join("\n"),
+                    }
+                )
+            }
+            ServerEvent::Tournaments(list) if list.is_empty() => "no tournaments".to_string(),
+            ServerEvent::Tournaments(list) => {
+                let lines: Vec<String> = list
+                    .iter()
+                    .map(|t| {
+                        format!(
+                            "  {} {} ({:?}, {:?}): round {}/{}, {} players",
+                            t.id, t.name, t.ruleset, t.stage, t.round, t.rounds, t.participants
+                        )
+                    })
+                    .collect();
+                format!("tournaments:\n{}", lines.join("\n"))
+            }
+            ServerEvent::Tournament(view) => {
+                let info = &view.info;
+                let mut lines = vec![format!(
+                    "tournament {} {} ({:?}, {:?}), round {}/{}:",
+                    info.id, info.name, info.ruleset, info.stage, info.round, info.rounds
+                )];
+                for standing in &view.standings {
+                    lines.push(format!(
+                        "  {:>3}. {} (player {}) {:+.1} {:?}",
+                        standing.position,
+                        standing.username,
+                        standing.player_id,
+                        standing.points,
+                        standing.placements
+                    ));
+                }
+                for table in &view.tables {
+                    let state = match (table.finished, table.table_id) {
+                        (true, _) => "finished".to_string(),
+                        (false, Some(id)) => format!("table {id}, seated {:?}", table.seated),
+                        (false, None) => "not open".to_string(),
+                    };
+                    lines.push(format!(
+                        "  table {}: players {:?} ({state})",
+                        table.number, table.players
+                    ));
+                }
+                lines.join("\n")
+            }
+            ServerEvent::ReplayDone(seq) => format!("replayed up to event {seq}"),
+            ServerEvent::Chat { player_id, chat } => match chat {
+                Chat::Message(text) => format!("player {player_id}: {text}"),
+                Chat::Reaction(reaction) => format!("player {player_id} reacts: {reaction:?}"),
+                Chat::Mute(_) | Chat::Unmute(_) => format!("player {player_id}: {chat:?}"),
+            },
+            ServerEvent::Error { id, message } => format!("error on packet {id}: {message}"),
+            ServerEvent::Unknown(packet) => {
+                let body: Vec<String> = packet.body.iter().map(|b| format!("{b:02x}")).collect();
+                format!(
+                    "unhandled {:?} packet {}: {}",
+                    packet.kind,
+                    packet.id,
+                    body.join(" ")
+                )
+            }
+        }
+    }
+
+    // Removes a copy of `kind` from the hand, returning the exact tile to send.
+    pub fn take(&mut self, kind: TileKind) -> Option<Tile> {
+        let index = self.hand.iter().position(|tile| tile.kind == kind)?;
+        return Some(self.hand.remove(index));
+    }
+
+    pub fn render(&self) -> String {
+        let seat = self.seat.map_or("-".to_string(), |seat| seat.to_string());
+        let mut lines = vec![format!("player {} ({seat})", self.pid)];
+        let hand: Vec<TileKind> = self.hand.iter().map(|tile| tile.kind).collect();
+        lines.push(format!("hand   {}", notation(&hand)));
+
+        if !self.dora.is_empty() {
+            lines.push(format!("dora   {}", joined(&self.dora)));
+        }
+
+        for (pid, pond) in &self.ponds {
+            lines.push(format!("pond {pid:>2} {}", joined(pond)));
+        }
+
+        for (pid, melds) in &self.melds {
+            let melds: Vec<String> = melds.iter().map(|meld| notation(meld)).collect();
+            lines.push(format!("melds {pid:>2} {}", melds.join(" | ")));
+        }
+
+        if !self.scores.is_empty() {
+            let scores: Vec<String> = self
+                .scores
+                .iter()
+                .map(|(pid, score)| format!("{pid}: {score}"))
+                .collect();
+            lines.push(format!("scores {}", scores.join(", ")));
+        }
+
+        if let Some(flags) = &self.calls {
+            lines.push(format!("calls  {}", available_calls(flags)));
+        }
+
+        return lines.join("\n");
+    }
+}
+
+// Rates the server does not have the hands to build yet are left out.
+fn percent(rate: Option<f64>) -> String {
+    return rate.map_or("n/a".to_string(), |rate| format!("{:.1}%", rate * 100.0));
+}
+
+fn available_calls(flags: &MeldFlags) -> String {
+    let calls: Vec<&str> = [
+        (flags.ron, "ron"),
+        (flags.pon, "pon"),
+        (flags.chi, "chi"),
+        (flags.kan, "kan"),
+    ]
+    .into_iter()
+    .filter_map(|(available, name)| available.then_some(name))
+    .collect();
+
+    if calls.is_empty() {
+        return "none".to_string();
+    }
+    return calls.join(", ");
+}
+
+// Discards in order, one tile at a time.
+fn joined(tiles: &[TileKind]) -> String {
+    let tiles: Vec<String> = tiles.iter().map(TileKind::to_string).collect();
+    return tiles.join(" ");
+}
+
+// Sorted compact MPSZ, numbers grouped by suit: 123m 406p 77z.
+fn notation(tiles: &[TileKind]) -> String {
+    let mut sorted: Vec<String> = tiles.iter().map(TileKind::to_string).collect();
+    sorted.sort_by_key(|tile| {
+        let suit = match tile.as_bytes()[1] {
+            b'm' => 0,
+            b'p' => 1,
+            b's' => 2,
+            _ => 3,
+        };
+        (suit, tile.as_bytes()[0])
+    });
+
+    let mut groups: Vec<String> = Vec::new();
+    let mut current = String::new();
+    for (index, tile) in sorted.iter().enumerate() {
+        let (number, suit) = tile.split_at(1);
+        current.push_str(number);
+        let next_suit = sorted.get(index + 1).map(|next| &next[1..]);
+        if next_suit != Some(suit) {
+            current.push_str(suit);
+            groups.push(std::mem::take(&mut current));
+        }
+    }
+
+    return groups.join(" ");
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn compact_hand() {
+        let hand = [
+            TileKind::Red,
+            TileKind::Manzu3,
+            TileKind::Souzu9,
+            TileKind::Manzu1,
+            TileKind::East,
+            TileKind::Red,
+        ];
+        assert_eq!(notation(&hand), "13m 9s 177z");
+        assert_eq!(notation(&[]), "");
+    }
+
+    #[test]
+    fn take_from_hand() {
+        let mut table = Table::new(1);
+        table.hand = vec![
+            Tile {
+                copy: 0,
+                kind: TileKind::Pinzu5,
+            },
+            Tile {
+                copy: 3,
+                kind: TileKind::Pinzu5,
+            },
+        ];
+        assert_eq!(table.take(TileKind::Pinzu5).map(|tile| tile.copy), Some(0));
+        assert!(table.take(TileKind::Pinzu6).is_none());
+        assert_eq!(table.hand.len(), 1);
+    }
+}
@@ -0,0 +1,709 @@
+use std::{
+    sync::{
+        Arc,
+        atomic::{AtomicI32, Ordering},
+    },
+    time::Duration,
+};
+
+use mahjong_protocol::{
+    account::{Credentials, Identity},
+    chat::Chat,
+    codec::Codec,
+    decoder::PacketDecoder,
+    enums::{Action, Tile},
+    errors::Error,
+    heartbeat::Heartbeat,
+    lobby::{Lobby, Ruleset, Tier},
+    models::{JoinRequest, SpectatorMode},
+    packet::{Packet, PacketKind, WriteBytesExt},
+    session::SessionToken,
+    setup::Setup,
+    stats::{LeaderboardQuery, StatsQuery},
+    tournament::TournamentRequest,
+    version::{Capabilities, Handshake, PROTOCOL_VERSION},
+};
+use tokio::{
+    io::{AsyncRead, AsyncWrite, ReadHalf, WriteHalf},
+    net::{TcpStream, ToSocketAddrs},
+    sync::{Mutex, mpsc},
+    time::Instant,
+};
+use tokio_stream::wrappers::ReceiverStream;
+
+use crate::{error::ClientError, event::ServerEvent};
+
+/// Calls a player can make on another seat's discard, or tsumo on their own draw.
+/// Only sent with the `calls` feature, the server refuses them for now.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Call {
+    Kan,
+    Pon,
+    Chi,
+    Ron,
+    Tsumo,
+}
+
+#[cfg(feature = "calls")]
+impl Call {
+    fn action(&self) -> Action {
+        match self {
+            Call::Kan => Action::KAN,
+            Call::Pon => Action::PON,
+            Call::Chi => Action::CHI,
+            Call::Ron => Action::RON,
+            Call::Tsumo => Action::TSUMO,
+        }
+    }
+}
+
+trait Stream: AsyncRead + AsyncWrite + Send + Unpin {}
+impl<S: AsyncRead + AsyncWrite + Send + Unpin> Stream for S {}
+
+// State shared between the connection and its reader task.
+struct Shared {
+    epoch: Instant, // Reference point for ping timestamps.
+    writer: Mutex<WriteHalf<Box<dyn Stream>>>,
+    rtt: std::sync::Mutex<Option<Duration>>,
+    last_seq: AtomicI32, // Sequence number of the last match event received.
+    session: std::sync::Mutex<Option<SessionToken>>, // Set on connection, or once the lobby seats the player.
+}
+
+/// A joined session with the server.
+/// Server packets are decoded on a background task and handed out through `take_events`.
+/// Server pings are answered on the same task, and replayed match events already seen are dropped there.
+pub struct Connection {
+    handshake: Handshake,
+    identity: Option<Identity>, // Player the server took the connection for, from version 4.
+    next_id: AtomicI32,
+    shared: Arc<Shared>,
+    events: Option<mpsc::Receiver<ServerEvent>>,
+}
+
+impl Connection {
+    /// Opens a TCP connection and takes a seat.
+    pub async fn connect(
+        addr: impl ToSocketAddrs,
+        request: &JoinRequest,
+        capabilities: Capabilities,
+    ) -> Result<Self, ClientError> {
+        let stream = TcpStream::connect(addr).await?;
+        return Self::join(stream, request, capabilities).await;
+    }
+
+    /// Takes a seat over an already open stream, e.g. a TLS stream.
+    pub async fn join<S>(
+        stream: S,
+        request: &JoinRequest,
+        capabilities: Capabilities,
+    ) -> Result<Self, ClientError>
+    where
+        S: AsyncRead + AsyncWrite + Send + Unpin + 'static,
+    {
+        let join = request.bytes();
+        return Self::open(Box::new(stream), Setup::Connection, &join, capabilities).await;
+    }
+
+    /// Creates an account and logs in to it. The server picks the player id, see `identity`.
+    pub async fn register<S>(
+        stream: S,
+        credentials: &Credentials,
+        capabilities: Capabilities,
+    ) -> Result<Self, ClientError>
+    where
+        S: AsyncRead + AsyncWrite + Send + Unpin + 'static,
+    {
+        let body = credentials.bytes();
+        return Self::open(Box::new(stream), Setup::Register, &body, capabilities).await;
+    }
+
+    /// Logs in to an existing account.
+    pub async fn login<S>(
+        stream: S,
+        credentials: &Credentials,
+        capabilities: Capabilities,
+    ) -> Result<Self, ClientError>
+    where
+        S: AsyncRead + AsyncWrite + Send + Unpin + 'static,
+    {
+        let body = credentials.bytes();
+        return Self::open(Box::new(stream), Setup::Login, &body, capabilities).await;
+    }
+
+    /// Takes back the seat of the session after a dropped connection.
+    /// The token is single use: keep the one returned by `session` on the new connection.
+    pub async fn rejoin<S>(
+        stream: S,
+        token: &SessionToken,
+        capabilities: Capabilities,
+    ) -> Result<Self, ClientError>
+    where
+        S: AsyncRead + AsyncWrite + Send + Unpin + 'static,
+    {
+        let token = token.bytes();
+        return Self::open(Box::new(stream), Setup::Reconnection, &token, capabilities).await;
+    }
+
+    /// Watches a match without a seat, the server's oldest table when `table` is None.
+    /// A locked room takes its password, which is only sent along with a table.
+    /// Every event so far is sent first; modes that reveal hands are kept behind the
+    /// table by the server's spectator delay.
+    pub async fn spectate<S>(
+        stream: S,
+        mode: SpectatorMode,
+        table: Option<i32>,
+        password: Option<&str>,
+        capabilities: Capabilities,
+    ) -> Result<Self, ClientError>
+    where
+        S: AsyncRead + AsyncWrite + Send + Unpin + 'static,
+    {
+        let mut target = mode.bytes();
+        if let Some(table) = table {
+            target.extend(table.to_le_bytes());
+            target.extend(password.unwrap_or_default().as_bytes());
+        }
+        return Self::open(Box::new(stream), Setup::Spectate, &target, capabilities).await;
+    }
+
+    // Sends the Connection/Reconnection/Spectate packet and waits for the negotiated handshake
+    // before the reader task is started, so the codec is known for every later packet.
+    async fn open(
+        stream: Box<dyn Stream>,
+        operation: Setup,
+        credentials: &[u8],
+        capabilities: Capabilities,
+    ) -> Result<Self, ClientError> {
+        let (mut reader, mut writer) = tokio::io::split(stream);
+        let offer = Handshake {
+            version: PROTOCOL_VERSION,
+            capabilities,
+        };
+
+        let mut body = operation.bytes().to_vec();
+        body.extend(offer.bytes());
+        body.extend(credentials);
+        writer
+            .send_packet(&Packet::create(1, PacketKind::Setup, &body))
+            .await?;
+
+        let mut decoder = PacketDecoder::new();
+        let (handshake, session, identity) = loop {
+            let Some(packet) = decoder.read_packet(&mut reader).await? else {
+                return Err(ClientError::Closed);
+            };
+
+            // The codec is irrelevant until the handshake is known: only Setup and Error packets are expected.
+            match ServerEvent::parse(packet, Codec::Cbor)? {
+                ServerEvent::Connected(handshake, session)
+                | ServerEvent::Reconnected(handshake, session) => {
+                    break (handshake, session, None);
+                }
+                ServerEvent::Identified(handshake, identity) => {
+                    break (handshake, None, Some(identity));
+                }
+                ServerEvent::Spectating(handshake, _) => break (handshake, None, None),
+                ServerEvent::Error { message, .. } => return Err(ClientError::Server(message)),
+                _ => continue,
+            }
+        };
+
+        let shared = Arc::new(Shared {
+            epoch: Instant::now(),
+            writer: Mutex::new(writer),
+            rtt: std::sync::Mutex::new(None),
+            last_seq: AtomicI32::new(0),
+            session: std::sync::Mutex::new(session),
+        });
+
+        let codec = Codec::from_capabilities(handshake.capabilities);
+        let (events_tx, events_rx) = mpsc::channel(64);
+        tokio::spawn(Self::read_events(
+            reader,
+            decoder,
+            codec,
+            events_tx,
+            Arc::clone(&shared),
+        ));
+
+        return Ok(Self {
+            handshake,
+            identity,
+            next_id: AtomicI32::new(2),
+            shared,
+            events: Some(events_rx),
+        });
+    }
+
+    // Forwards decoded packets until the stream closes, the framing is lost or the events are dropped.
+    // Heartbeats never reach the events: pings are answered and pongs update the round trip.
+    async fn read_events(
+        mut reader: ReadHalf<Box<dyn Stream>>,
+        mut decoder: PacketDecoder,
+        codec: Codec,
+        events: mpsc::Sender<ServerEvent>,
+        shared: Arc<Shared>,
+    ) {
+        loop {
+            let packet = match decoder.read_packet(&mut reader).await {
+                Ok(Some(packet)) => packet,
+                Ok(None) => return,
+                Err(Error::PacketSizeInvalid(_)) => return,
+                Err(_) => continue,
+            };
+
+            if packet.kind == PacketKind::Heartbeat {
+                match Heartbeat::parse(&packet.body) {
+                    Ok(Heartbeat::Pong(timestamp)) => {
+                        let sent = Duration::from_micros(timestamp);
+                        *shared.rtt.lock().unwrap() = shared.epoch.elapsed().checked_sub(sent);
+                    }
+                    Ok(Heartbeat::Ping(timestamp)) => {
+                        let pong = Heartbeat::Pong(timestamp).packet(packet.id);
+                        if shared.writer.lock().await.send_packet(&pong).await.is_err() {
+                            return;
+                        }
+                    }
+                    Err(_) => {}
+                }
+                continue;
+            }
+
+            // Broadcasts carry the match event sequence number as id.
+            if packet.kind == PacketKind::Broadcast {
+                let last_seq = shared.last_seq.load(Ordering::Relaxed);
+                if packet.id <= last_seq {
+                    continue;
+                }
+                shared.last_seq.store(packet.id, Ordering::Relaxed);
+            }
+
+            let event = match ServerEvent::parse(packet.clone(), codec) {
+                Ok(event) => event,
+                Err(_) => ServerEvent::Unknown(packet),
+            };
+
+            match &event {
+                // Events before the snapshot are already part of it.
+                ServerEvent::Snapshot(snapshot) => {
+                    shared
+                        .last_seq
+                        .fetch_max(snapshot.last_sequence, Ordering::Relaxed);
+                }
+                ServerEvent::Seated(seated) => {
+                    *shared.session.lock().unwrap() = Some(seated.token);
+                }
+                _ => {}
+            }
+
+            if events.send(event).await.is_err() {
+                return;
+            }
+        }
+    }
+
+    /// Stream of server packets. Can only be taken once.
+    pub fn take_events(&mut self) -> Option<ReceiverStream<ServerEvent>> {
+        return self.events.take().map(ReceiverStream::new);
+    }
+
+    pub fn handshake(&self) -> Handshake {
+        return self.handshake;
+    }
+
+    /// Player id the server assigned and whether it belongs to an account.
+    /// None before version 4, where the id sent in the join request was used.
+    pub fn identity(&self) -> Option<Identity> {
+        return self.identity;
+    }
+
+    pub fn codec(&self) -> Codec {
+        return Codec::from_capabilities(self.handshake.capabilities);
+    }
+
+    /// Token to pass to `rejoin` if this connection drops.
+    /// None while the player is in the lobby (protocol version 3 and up) and for spectators.
+    pub fn session(&self) -> Option<SessionToken> {
+        return *self.shared.session.lock().unwrap();
+    }
+
+    /// Round trip of the last answered `ping`.
+    pub fn rtt(&self) -> Option<Duration> {
+        return *self.shared.rtt.lock().unwrap();
+    }
+
+    /// Sequence number of the last match event received.
+    pub fn last_sequence(&self) -> i32 {
+        return self.shared.last_seq.load(Ordering::Relaxed);
+    }
+
+    /// Asks for every match event after the last one received, e.g. after a reconnection.
+    /// Ends with `ServerEvent::ReplayDone`.
+    pub async fn replay(&self) -> Result<(), ClientError> {
+        let mut body = Setup::Replay.bytes().to_vec();
+        body.extend(self.last_sequence().to_le_bytes());
+        return self.send(PacketKind::Setup, &body).await;
+    }
+
+    /// Measures the round trip to the server; read the result with `rtt` once the pong is back.
+    pub async fn ping(&self) -> Result<(), ClientError> {
+        let timestamp = self.shared.epoch.elapsed().as_micros() as u64;
+        return self
+            .send(PacketKind::Heartbeat, &Heartbeat::Ping(timestamp).bytes())
+            .await;
+    }
+
+    /// Lists the lobby's open rooms, answered with `ServerEvent::Rooms`.
+    pub async fn rooms(&self) -> Result<(), ClientError> {
+        return self.send(PacketKind::Lobby, &Lobby::List.bytes()).await;
+    }
+
+    /// Opens a room and takes a seat in it, answered with `ServerEvent::Seated`.
+    /// A password locks the room; share its code for others to join.
+    pub async fn create_room(
+        &self,
+        ruleset: Ruleset,
+        password: Option<&str>,
+    ) -> Result<(), ClientError> {
+        let password = password.map(str::to_string);
+        let create = Lobby::Create { ruleset, password };
+        return self.send(PacketKind::Lobby, &create.bytes()).await;
+    }
+
+    /// Takes a seat in the room with the given code, answered with `ServerEvent::Seated`.
+    pub async fn join_room(&self, code: &str, password: Option<&str>) -> Result<(), ClientError> {
+        let join = Lobby::Join {
+            code: code.to_ascii_uppercase(),
+            password: password.map(str::to_string),
+        };
+        return self.send(PacketKind::Lobby, &join.bytes()).await;
+    }
+
+    /// Takes a seat in any open room with the default rules, answered with `ServerEvent::Seated`.
+    pub async fn quick_play(&self) -> Result<(), ClientError> {
+        return self
+            .send(PacketKind::Lobby, &Lobby::QuickPlay.bytes())
+            .await;
+    }
+
+    /// Enters the ranked matchmaking queue of a tier the player's rank admits, answered with
+    /// `ServerEvent::Queue` until it seats the player with `ServerEvent::Seated`.
+    pub async fn queue(&self, ruleset: Ruleset, tier: Tier) -> Result<(), ClientError> {
+        return self
+            .send(PacketKind::Lobby, &Lobby::Queue(ruleset, tier).bytes())
+            .await;
+    }
+
+    /// Asks for this player's rating and rank, answered with `ServerEvent::Profile`.
+    pub async fn profile(&self) -> Result<(), ClientError> {
+        return self.send(PacketKind::Lobby, &Lobby::Profile.bytes()).await;
+    }
+
+    /// Sends a tournament request from the lobby. The list is answered with
+    /// `ServerEvent::Tournaments`, taking the seat of the current round with `ServerEvent::Seated`
+    /// and everything else with `ServerEvent::Tournament`.
+    pub async fn tournament(&self, request: &TournamentRequest) -> Result<(), ClientError> {
+        return self.send(PacketKind::Tournament, &request.bytes()).await;
+    }
+
+    /// Leaves the matchmaking queue, answered with `ServerEvent::LeftQueue`.
+    pub async fn leave_queue(&self) -> Result<(), ClientError> {
+        return self
+            .send(PacketKind::Lobby, &Lobby::LeaveQueue.bytes())
+            .await;
+    }
+
+    /// Asks for a player's statistics (this player's without an id), answered with
+    /// `ServerEvent::Stats`. Works in the lobby and at a table.
+    pub async fn stats(&self, player_id: Option<i32>) -> Result<(), ClientError> {
+        let query = StatsQuery { player_id };
+        return self.send(PacketKind::Stats, &query.bytes()).await;
+    }
+
+    /// Asks for a page of a leaderboard, answered with `ServerEvent::Leaderboard`. Works in the
+    /// lobby and at a table.
+    pub async fn leaderboard(&self, query: LeaderboardQuery) -> Result<(), ClientError> {
+        return self.send(PacketKind::Leaderboard, &query.bytes()).await;
+    }
+
+    /// Asks for the starting hand and seat.
+    pub async fn initialize(&self) -> Result<(), ClientError> {
+        return self
+            .send(PacketKind::Setup, &Setup::Initialization.bytes())
+            .await;
+    }
+
+    /// Sends a chat message, reaction or (un)mute to the table.
+    pub async fn chat(&self, chat: &Chat) -> Result<(), ClientError> {
+        return self.send(PacketKind::Chat, &chat.bytes()).await;
+    }
+
+    /// Asks for a snapshot of the table, answered with `ServerEvent::Snapshot`.
+    pub async fn resync(&self) -> Result<(), ClientError> {
+        return self.send(PacketKind::Setup, &Setup::Resync.bytes()).await;
+    }
+
+    pub async fn ready(&self) -> Result<(), ClientError> {
+        return self.send(PacketKind::Setup, &Setup::Ready.bytes()).await;
+    }
+
+    pub async fn draw(&self) -> Result<(), ClientError> {
+        return self
+            .send(PacketKind::Action, &[Action::DRAW.bytes()[0]])
+            .await;
+    }
+
+    pub async fn discard(&self, tile: Tile) -> Result<(), ClientError> {
+        let body = [Action::DISCARD.bytes()[0], tile.kind as u8, tile.copy];
+        return self.send(PacketKind::Action, &body).await;
+    }
+
+    #[cfg(feature = "calls")]
+    pub async fn call(&self, call: Call) -> Result<(), ClientError> {
+        return self
+            .send(PacketKind::Action, &[call.action().bytes()[0]])
+            .await;
+    }
+
+    // Client actions carry a one byte opcode, unlike the four byte opcodes sent by the server.
+    async fn send(&self, kind: PacketKind, body: &[u8]) -> Result<(), ClientError> {
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+        let packet = Packet::create(id, kind, body);
+        self.shared.writer.lock().await.send_packet(&packet).await?;
+        return Ok(());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use mahjong_protocol::{enums::TileKind, packet::ToBytes};
+    use tokio::io::AsyncWriteExt;
+    use tokio_stream::StreamExt;
+
+    // Plays the server side of a Connection handshake over an in-memory stream.
+    async fn fake_server(
+        mut server: tokio::io::DuplexStream,
+    ) -> (tokio::io::DuplexStream, Packet, PacketDecoder) {
+        let mut decoder = PacketDecoder::new();
+        let join = decoder.read_packet(&mut server).await.unwrap().unwrap();
+        let handshake = Handshake::parse(&join.body[4..])
+            .unwrap()
+            .negotiate()
+            .unwrap();
+
+        let mut body = Setup::Connection.bytes().to_vec();
+        body.extend(handshake.bytes());
+        body.extend([0xAB; SessionToken::SIZE]);
+        let response = Packet::create(join.id, PacketKind::Setup, &body);
+        server.write_all(&response.to_bytes()).await.unwrap();
+        return (server, join, decoder);
+    }
+
+    #[tokio::test]
+    async fn join_and_discard() {
+        let (client, server) = tokio::io::duplex(4096);
+        let server = tokio::spawn(fake_server(server));
+
+        let request = JoinRequest {
+            id: 42,
+            alias: "Bunny".to_string(),
+        };
+        let mut connection = Connection::join(client, &request, Capabilities::JSON_PAYLOADS)
+            .await
+            .unwrap();
+        assert_eq!(connection.codec(), Codec::Json);
+        assert!(connection.session() == Some(SessionToken([0xAB; SessionToken::SIZE])));
+
+        let (mut server, join, mut decoder) = server.await.unwrap();
+        assert_eq!(JoinRequest::parse(&join.body[10..]).unwrap().id, 42);
+
+        let tile = Tile {
+            copy: 1,
+            kind: TileKind::Manzu3,
+        };
+        connection.discard(tile).await.unwrap();
+        let discard = decoder.read_packet(&mut server).await.unwrap().unwrap();
+        assert_eq!(&discard.body[..], &[0x01, 33, 1]);
+
+        let mut body = Action::DRAW.bytes().to_vec();
+        body.extend(Codec::Json.encode(&tile).unwrap());
+        let drawn = Packet::create(7, PacketKind::Action, &body);
+        server.write_all(&drawn.to_bytes()).await.unwrap();
+
+        let mut events = connection.take_events().unwrap();
+        assert!(matches!(
+            events.next().await,
+            Some(ServerEvent::DrawnTile(Tile {
+                kind: TileKind::Manzu3,
+                copy: 1
+            }))
+        ));
+    }
+
+    #[tokio::test]
+    async fn answers_server_ping() {
+        let (client, server) = tokio::io::duplex(4096);
+        let server = tokio::spawn(fake_server(server));
+        let request = JoinRequest {
+            id: 3,
+            alias: "Quiet".to_string(),
+        };
+        let _connection = Connection::join(client, &request, Capabilities::default())
+            .await
+            .unwrap();
+
+        let (mut server, _, mut decoder) = server.await.unwrap();
+        let ping = Heartbeat::Ping(1234).packet(11);
+        server.write_all(&ping.to_bytes()).await.unwrap();
+        let pong = decoder.read_packet(&mut server).await.unwrap().unwrap();
+        assert_eq!(pong.id, 11);
+        assert_eq!(Heartbeat::parse(&pong.body).unwrap(), Heartbeat::Pong(1234));
+    }
+
+    #[tokio::test]
+    async fn drops_replayed_events() {
+        use mahjong_protocol::models::Discard;
+
+        let (client, server) = tokio::io::duplex(4096);
+        let server = tokio::spawn(fake_server(server));
+        let request = JoinRequest {
+            id: 5,
+            alias: "Late".to_string(),
+        };
+        let mut connection = Connection::join(client, &request, Capabilities::default())
+            .await
+            .unwrap();
+        let (mut server, _, _) = server.await.unwrap();
+
+        for seq in [1, 2, 1, 3] {
+            let discard = Discard {
+                player_id: 2,
+                tile_copy: 0,
+                tile_kind: TileKind::Souzu1,
+            };
+            let mut body = Action::DISCARD.bytes().to_vec();
+            body.extend(Codec::Cbor.encode(&discard).unwrap());
+            let packet = Packet::create(seq, PacketKind::Broadcast, &body);
+            server.write_all(&packet.to_bytes()).await.unwrap();
+        }
+        let error = Packet::error(9, Error::InternalError);
+        server.write_all(&error.to_bytes()).await.unwrap();
+
+        let mut events = connection.take_events().unwrap();
+        for _ in 0..3 {
+            assert!(matches!(
+                events.next().await,
+                Some(ServerEvent::Discarded(_))
+            ));
+        }
+        assert!(matches!(
+            events.next().await,
+            Some(ServerEvent::Error { id: 9, .. })
+        ));
+        assert_eq!(connection.last_sequence(), 3);
+    }
+
+    // Version 3 servers answer the Connection without a token; the lobby hands it out with the seat.
+    #[tokio::test]
+    async fn login_gives_identity() {
+        let (client, mut server) = tokio::io::duplex(4096);
+        let server = tokio::spawn(async move {
+            let mut decoder = PacketDecoder::new();
+            let login = decoder.read_packet(&mut server).await.unwrap().unwrap();
+            let identity = Identity {
+                player_id: 17,
+                guest: false,
+            };
+            let mut body = Setup::Login.bytes().to_vec();
+            body.extend(Handshake::parse(&login.body[4..]).unwrap().bytes());
+            body.extend(identity.bytes());
+            let response = Packet::create(login.id, PacketKind::Setup, &body);
+            server.write_all(&response.to_bytes()).await.unwrap();
+            return (server, login);
+        });
+
+        let credentials = Credentials {
+            username: "bunny".to_string(),
+            password: "hunter2hunter2".to_string(),
+        };
+        let connection = Connection::login(client, &credentials, Capabilities::default())
+            .await
+            .unwrap();
+        let (_server, login) = server.await.unwrap();
+        assert!(matches!(Setup::from(&login.body[..4]), Some(Setup::Login)));
+        assert!(Credentials::parse(&login.body[4 + Handshake::SIZE..]).unwrap() == credentials);
+        assert_eq!(
+            connection.identity().map(|identity| identity.player_id),
+            Some(17)
+        );
+        assert!(connection.session().is_none());
+    }
+
+    #[tokio::test]
+    async fn lobby_seat_gives_session() {
+        let (client, mut server) = tokio::io::duplex(4096);
+        let server = tokio::spawn(async move {
+            let mut decoder = PacketDecoder::new();
+            let join = decoder.read_packet(&mut server).await.unwrap().unwrap();
+            let mut body = Setup::Connection.bytes().to_vec();
+            body.extend(Handshake::parse(&join.body[4..]).unwrap().bytes());
+            let response = Packet::create(join.id, PacketKind::Setup, &body);
+            server.write_all(&response.to_bytes()).await.unwrap();
+            return (server, decoder);
+        });
+
+        let request = JoinRequest {
+            id: 3,
+            alias: "Lobbyist".to_string(),
+        };
+        let mut connection = Connection::join(client, &request, Capabilities::default())
+            .await
+            .unwrap();
+        assert!(connection.session().is_none());
+
+        let (mut server, mut decoder) = server.await.unwrap();
+        connection.join_room("k7qz2m", Some("pw")).await.unwrap();
+        let join = decoder.read_packet(&mut server).await.unwrap().unwrap();
+        assert_eq!(join.kind, PacketKind::Lobby);
+        assert!(matches!(
+            Lobby::parse(&join.body),
+            Ok(Lobby::Join { code, password: Some(_) }) if code == "K7QZ2M"
+        ));
+
+        let seated = mahjong_protocol::lobby::Seated {
+            table_id: 2,
+            code: "K7QZ2M".to_string(),
+            token: SessionToken([0x11; SessionToken::SIZE]),
+        };
+        server
+            .write_all(&seated.packet(join.id).to_bytes())
+            .await
+            .unwrap();
+
+        let mut events = connection.take_events().unwrap();
+        assert!(matches!(
+            events.next().await,
+            Some(ServerEvent::Seated(seated)) if seated.table_id == 2
+        ));
+        assert!(connection.session() == Some(SessionToken([0x11; SessionToken::SIZE])));
+    }
+
+    #[tokio::test]
+    async fn join_rejected() {
+        let (client, mut server) = tokio::io::duplex(4096);
+        tokio::spawn(async move {
+            let mut decoder = PacketDecoder::new();
+            let join = decoder.read_packet(&mut server).await.unwrap().unwrap();
+            let response = Packet::error(join.id, Error::MatchAlreadyFull);
+            server.write_all(&response.to_bytes()).await.unwrap();
+        });
+
+        let request = JoinRequest {
+            id: 1,
+            alias: "Late".to_string(),
+        };
+        let result = Connection::join(client, &request, Capabilities::default()).await;
+        assert!(matches!(result, Err(ClientError::Server(_))));
+    }
+}
@@ -0,0 +1,17 @@
+use mahjong_protocol::errors::Error;
+
+#[derive(Debug, thiserror::Error)]
+pub enum ClientError {
+    #[error("connection error: {0}")]
+    Io(#[from] std::io::Error),
+
+    #[error("protocol error: {0}")]
+    Protocol(#[from] Error),
+
+    /// Error packet sent by the server in answer to a request.
+    #[error("server error: {0}")]
+    Server(String),
+
+    #[error("connection closed")]
+    Closed,
+}
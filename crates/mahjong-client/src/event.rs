@@ -0,0 +1,292 @@
+use mahjong_protocol::{
+    account::Identity,
+    broadcast::Broadcast,
+    chat::Chat,
+    codec::Codec,
+    enums::{Action, Tile},
+    errors::Error,
+    lobby::{LEFT_QUEUE_OP, PROFILE_OP, Profile, QUEUE_OP, QueueState, ROOMS_OP, RoomInfo, Seated},
+    models::{Deal, Discard, Draw, HandResult, InitialPlayerView, MeldFlags, ResyncSnapshot},
+    packet::{Packet, PacketKind},
+    session::SessionToken,
+    setup::Setup,
+    stats::{LeaderboardPage, PlayerStats},
+    tournament::{TOURNAMENT_OP, TOURNAMENTS_OP, TournamentInfo, TournamentView},
+    version::Handshake,
+};
+
+/// Packets sent by the server, decoded with the codec agreed on in the handshake.
+pub enum ServerEvent {
+    /// Negotiated handshake and the token needed to reconnect.
+    /// Servers older than version 2 do not hand out tokens.
+    Connected(Handshake, Option<SessionToken>),
+    Reconnected(Handshake, Option<SessionToken>),
+    /// Negotiated handshake and the player the server took the connection for: a guest or the
+    /// account just registered or logged in to (version 4 and up).
+    Identified(Handshake, Identity),
+    /// Rooms of the lobby still waiting for players.
+    Rooms(Vec<RoomInfo>),
+    /// A lobby request gave this player a seat. Its token replaces any session of the connection.
+    Seated(Seated),
+    /// Where this player stands in the matchmaking queue, sent as it changes.
+    /// The queue ends with `Seated` once it formed a table.
+    Queue(QueueState),
+    LeftQueue,
+    /// Rating and rank of this player, sent on entering the lobby and on `profile`.
+    Profile(Profile),
+    /// Tournaments of the server.
+    Tournaments(Vec<TournamentInfo>),
+    /// Standings and seating of a tournament, the answer to every tournament request but the
+    /// list and taking a seat (answered with `Seated`).
+    Tournament(TournamentView),
+    /// Negotiated handshake and the spectator id given by the server.
+    Spectating(Handshake, i32),
+    InitialView(InitialPlayerView),
+    ReadyAck,
+    /// Whole table as this player may see it, sent after a reconnection or on `resync`.
+    Snapshot(ResyncSnapshot),
+    /// Every missed match event was sent again, up to this sequence number.
+    ReplayDone(i32),
+    /// Tile drawn by this client.
+    DrawnTile(Tile),
+    /// Calls this client may make on the last discard.
+    CallsAvailable(MeldFlags),
+    /// A seat was dealt its hand. The hand is only set for its owner and spectators allowed to see it.
+    Dealt(Deal),
+    /// A seat drew a tile. The tile is only set when this client drew it,
+    /// which it also learns from `DrawnTile`.
+    Drew(Draw),
+    /// Another seat discarded a tile.
+    Discarded(Discard),
+    /// A hand was won or drawn.
+    HandEnded(HandResult),
+    /// Statistics asked for with `stats`.
+    Stats(PlayerStats),
+    /// A page of a leaderboard asked for with `leaderboard`.
+    Leaderboard(LeaderboardPage),
+    /// Table chat: a message or a preset reaction from a seated player.
+    Chat {
+        player_id: i32,
+        chat: Chat,
+    },
+    Error {
+        id: i32,
+        message: String,
+    },
+    /// Packet the SDK does not know how to read yet. Kept so callers can handle it themselves.
+    Unknown(Packet),
+}
+
+impl ServerEvent {
+    pub fn parse(packet: Packet, codec: Codec) -> Result<Self, Error> {
+        match packet.kind {
+            PacketKind::Error => {
+                let message = String::from_utf8_lossy(&packet.body).to_string();
+                return Ok(Self::Error {
+                    id: packet.id,
+                    message,
+                });
+            }
+            PacketKind::Setup => {
+                // The Ready acknowledgement is a single byte, not an operation.
+                if packet.body.len() < 4 {
+                    return Ok(Self::ReadyAck);
+                }
+
+                let payload = &packet.body[4..];
+                let session = || {
+                    let token = payload.get(Handshake::SIZE..)?;
+                    SessionToken::parse(token).ok()
+                };
+                match Setup::from(&packet.body[..4]) {
+                    Some(Setup::Connection | Setup::Register | Setup::Login)
+                        if payload.len() == Handshake::SIZE + Identity::SIZE =>
+                    {
+                        let identity = Identity::parse(&payload[Handshake::SIZE..])?;
+                        Ok(Self::Identified(Handshake::parse(payload)?, identity))
+                    }
+                    Some(Setup::Connection) => {
+                        Ok(Self::Connected(Handshake::parse(payload)?, session()))
+                    }
+                    Some(Setup::Reconnection) => {
+                        Ok(Self::Reconnected(Handshake::parse(payload)?, session()))
+                    }
+                    Some(Setup::Spectate) => {
+                        let handshake = Handshake::parse(payload)?;
+                        match payload.get(Handshake::SIZE..Handshake::SIZE + 4) {
+                            Some(id) => Ok(Self::Spectating(
+                                handshake,
+                                i32::from_le_bytes([id[0], id[1], id[2], id[3]]),
+                            )),
+                            None => Ok(Self::Unknown(packet)),
+                        }
+                    }
+                    Some(Setup::Initialization) => Ok(Self::InitialView(codec.decode(payload)?)),
+                    Some(Setup::Resync) => Ok(Self::Snapshot(codec.decode(payload)?)),
+                    Some(Setup::Replay) => match payload.get(..4) {
+                        Some(seq) => Ok(Self::ReplayDone(i32::from_le_bytes([
+                            seq[0], seq[1], seq[2], seq[3],
+                        ]))),
+                        None => Ok(Self::Unknown(packet)),
+                    },
+                    _ => Ok(Self::Unknown(packet)),
+                }
+            }
+            PacketKind::Action => {
+                let Some(payload) = packet.body.get(4..) else {
+                    return Ok(Self::Unknown(packet));
+                };
+
+                match Action::get(packet.body[0]) {
+                    Some(Action::DRAW) => Ok(Self::DrawnTile(codec.decode(payload)?)),
+                    Some(Action::DISCARD) => Ok(Self::CallsAvailable(codec.decode(payload)?)),
+                    _ => Ok(Self::Unknown(packet)),
+                }
+            }
+            PacketKind::Broadcast => {
+                let Some(payload) = packet.body.get(4..) else {
+                    return Ok(Self::Unknown(packet));
+                };
+
+                match Broadcast::get(packet.body[0]) {
+                    Some(Broadcast::DEALT) => Ok(Self::Dealt(codec.decode(payload)?)),
+                    Some(Broadcast::DREW) => Ok(Self::Drew(codec.decode(payload)?)),
+                    Some(Broadcast::DISCARDED) => Ok(Self::Discarded(codec.decode(payload)?)),
+                    Some(Broadcast::WINNER) => Ok(Self::HandEnded(codec.decode(payload)?)),
+                    _ => Ok(Self::Unknown(packet)),
+                }
+            }
+            PacketKind::Lobby => {
+                let Some(payload) = packet.body.get(4..) else {
+                    return Ok(Self::Unknown(packet));
+                };
+
+                match [
+                    packet.body[0],
+                    packet.body[1],
+                    packet.body[2],
+                    packet.body[3],
+                ] {
+                    ROOMS_OP => Ok(Self::Rooms(codec.decode(payload)?)),
+                    Seated::OP => Ok(Self::Seated(Seated::parse(payload)?)),
+                    QUEUE_OP => Ok(Self::Queue(codec.decode(payload)?)),
+                    LEFT_QUEUE_OP => Ok(Self::LeftQueue),
+                    PROFILE_OP => Ok(Self::Profile(codec.decode(payload)?)),
+                    _ => Ok(Self::Unknown(packet)),
+                }
+            }
+            PacketKind::Tournament => {
+                let Some(payload) = packet.body.get(4..) else {
+                    return Ok(Self::Unknown(packet));
+                };
+
+                match [
+                    packet.body[0],
+                    packet.body[1],
+                    packet.body[2],
+                    packet.body[3],
+                ] {
+                    TOURNAMENTS_OP => Ok(Self::Tournaments(codec.decode(payload)?)),
+                    TOURNAMENT_OP => Ok(Self::Tournament(codec.decode(payload)?)),
+                    _ => Ok(Self::Unknown(packet)),
+                }
+            }
+            PacketKind::Chat => {
+                let (player_id, chat) = Chat::parse_relay(&packet.body)?;
+                return Ok(Self::Chat { player_id, chat });
+            }
+            PacketKind::Stats => Ok(Self::Stats(codec.decode(&packet.body)?)),
+            PacketKind::Leaderboard => Ok(Self::Leaderboard(codec.decode(&packet.body)?)),
+            // Answered by the connection itself and never handed out.
+            PacketKind::Heartbeat => Ok(Self::Unknown(packet)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use mahjong_protocol::enums::TileKind;
+
+    #[test]
+    fn parse_drawn_tile() {
+        let codec = Codec::Json;
+        let tile = Tile {
+            copy: 2,
+            kind: TileKind::Pinzu5,
+        };
+        let mut body = Action::DRAW.bytes().to_vec();
+        body.extend(codec.encode(&tile).unwrap());
+
+        let packet = Packet::create(9, PacketKind::Action, &body);
+        let Ok(ServerEvent::DrawnTile(drawn)) = ServerEvent::parse(packet, codec) else {
+            panic!("expected a drawn tile");
+        };
+        assert!(drawn.kind == TileKind::Pinzu5 && drawn.copy == 2);
+    }
+
+    #[test]
+    fn parse_snapshot() {
+        use mahjong_protocol::{enums::Seat, models::SeatView};
+
+        let codec = Codec::Cbor;
+        let snapshot = ResyncSnapshot {
+            seat: Seat::West,
+            hand: vec![Tile {
+                copy: 0,
+                kind: TileKind::Red,
+            }],
+            seats: vec![SeatView {
+                player_id: 4,
+                seat: Seat::West,
+                alias: "Bunny".to_string(),
+                connected: true,
+                score: 25_000,
+                latency_ms: Some(42),
+                hand_size: 1,
+                pond: Vec::new(),
+                melds: Vec::new(),
+            }],
+            dora_indicators: Vec::new(),
+            turn: 3,
+            current_seat: Seat::East,
+            pending_calls: None,
+            turn_elapsed_ms: Some(1500),
+            last_sequence: 12,
+        };
+        let mut body = Setup::Resync.bytes().to_vec();
+        body.extend(codec.encode(&snapshot).unwrap());
+
+        let packet = Packet::create(2, PacketKind::Setup, &body);
+        let Ok(ServerEvent::Snapshot(parsed)) = ServerEvent::parse(packet, codec) else {
+            panic!("expected a snapshot");
+        };
+        assert_eq!(parsed.turn, 3);
+        assert_eq!(parsed.seats[0].alias, "Bunny");
+        assert_eq!(parsed.seats[0].latency_ms, Some(42));
+        assert_eq!(parsed.turn_elapsed_ms, Some(1500));
+    }
+
+    #[test]
+    fn parse_stats() {
+        let codec = Codec::Cbor;
+        let stats = PlayerStats {
+            player_id: 3,
+            matches: 2,
+            win_rate: Some(0.25),
+            ..Default::default()
+        };
+        let packet = Packet::create(5, PacketKind::Stats, &codec.encode(&stats).unwrap());
+        let Ok(ServerEvent::Stats(parsed)) = ServerEvent::parse(packet, codec) else {
+            panic!("expected stats");
+        };
+        assert_eq!(parsed, stats);
+    }
+
+    #[test]
+    fn parse_error() {
+        let packet = Packet::error(3, Error::MatchAlreadyFull);
+        let event = ServerEvent::parse(packet, Codec::Cbor).unwrap();
+        assert!(matches!(event, ServerEvent::Error { id: 3, .. }));
+    }
+}
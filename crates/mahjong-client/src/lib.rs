@@ -0,0 +1,34 @@
+//! Async client for the mahjong server.
+//!
+//! ```no_run
+//! # async fn run() -> Result<(), mahjong_client::ClientError> {
+//! use mahjong_client::{Connection, ServerEvent};
+//! use mahjong_protocol::{models::JoinRequest, version::Capabilities};
+//! use tokio_stream::StreamExt;
+//!
+//! let request = JoinRequest { id: 7, alias: "Bunny".to_string() };
+//! let mut connection = Connection::connect("127.0.0.1:3000", &request, Capabilities::default()).await?;
+//! let mut events = connection.take_events().unwrap();
+//! // Players start in the lobby: take any open seat.
+//! connection.quick_play().await?;
+//! while let Some(event) = events.next().await {
+//!     match event {
+//!         ServerEvent::Seated(_) => connection.initialize().await?,
+//!         ServerEvent::InitialView(view) => {
+//!             println!("{} tiles in hand", view.hand.len());
+//!             connection.ready().await?;
+//!         }
+//!         _ => {}
+//!     }
+//! }
+//! # Ok(())
+//! # }
+//! ```
+pub mod connection;
+pub mod error;
+pub mod event;
+
+pub use connection::{Call, Connection};
+pub use error::ClientError;
+pub use event::ServerEvent;
+pub use mahjong_protocol as protocol;
@@ -0,0 +1,84 @@
+use crate::errors::Error;
+
+/// Username and password of a Register or Login request (protocol version 4 and up).
+///
+/// ```text
+/// | username length (u8) | username (utf-8) | password (utf-8) |
+/// ```
+#[derive(Clone, PartialEq, Eq)]
+pub struct Credentials {
+    pub username: String,
+    pub password: String,
+}
+
+impl Credentials {
+    pub fn parse(bytes: &[u8]) -> Result<Self, Error> {
+        let length = *bytes.first().ok_or(Error::ConnectionFailed(54))? as usize;
+        let username = bytes
+            .get(1..1 + length)
+            .ok_or(Error::ConnectionFailed(54))?;
+        let username =
+            String::from_utf8(username.to_vec()).map_err(|_| Error::ConnectionFailed(54))?;
+        let password = String::from_utf8(bytes[1 + length..].to_vec())
+            .map_err(|_| Error::ConnectionFailed(54))?;
+        return Ok(Self { username, password });
+    }
+
+    pub fn bytes(&self) -> Vec<u8> {
+        let username = self.username.as_bytes();
+        let mut bytes = vec![username.len().min(u8::MAX as usize) as u8];
+        bytes.extend(&username[..username.len().min(u8::MAX as usize)]);
+        bytes.extend(self.password.as_bytes());
+        return bytes;
+    }
+}
+
+/// Who the server took a connection for, sent at the end of the Connection, Register and Login
+/// acknowledgements from version 4 on. Player ids are always assigned by the server.
+///
+/// ```text
+/// | player id (i32) | guest (u8) |
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Identity {
+    pub player_id: i32,
+    pub guest: bool,
+}
+
+impl Identity {
+    pub const SIZE: usize = 5;
+
+    pub fn parse(bytes: &[u8]) -> Result<Self, Error> {
+        let b = bytes.get(..Self::SIZE).ok_or(Error::ConnectionFailed(54))?;
+        return Ok(Self {
+            player_id: i32::from_le_bytes([b[0], b[1], b[2], b[3]]),
+            guest: b[4] != 0,
+        });
+    }
+
+    pub fn bytes(&self) -> [u8; Self::SIZE] {
+        let id = self.player_id.to_le_bytes();
+        return [id[0], id[1], id[2], id[3], self.guest as u8];
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn round_trip() {
+        let credentials = Credentials {
+            username: "bunny".to_string(),
+            password: "correct horse battery".to_string(),
+        };
+        assert!(Credentials::parse(&credentials.bytes()).unwrap() == credentials);
+        assert!(Credentials::parse(&[9, b'a']).is_err());
+
+        let identity = Identity {
+            player_id: 42,
+            guest: true,
+        };
+        assert_eq!(Identity::parse(&identity.bytes()).unwrap(), identity);
+    }
+}
@@ -0,0 +1,49 @@
+use serde::{Deserialize, Serialize};
+
+#[derive(Serialize, Deserialize, PartialEq, Eq)]
+pub enum Broadcast {
+    /// Player has drawn a tile. Do not reveal the tile drawn.
+    DREW = 0,
+    /// Player discarded a tile. Reveals which tile was discarded.
+    DISCARDED = 1,
+    /// Broadcasts the new turn number and who is the next in line.
+    TURNCHANGE = 2,
+    /// A hand ended: its winners (if any), who dealt in, and who declared riichi or called.
+    WINNER = 3,
+    /// Broadcasts player's tile calls (KAN, CHI, PON). Reveals which sequence/triplet was called.
+    CALLS = 4,
+    /// Fatal server error broadcast to end the match.
+    ERROR = 5,
+    /// A seat was dealt its starting hand. Only reveals the hand to its owner.
+    DEALT = 6,
+}
+
+// Codes at the start of Broadcast bodies. Draws and discards share their Action code.
+impl Broadcast {
+    pub fn bytes(&self) -> [u8; 4] {
+        let leading = match self {
+            Self::DREW => 0x00,
+            Self::DISCARDED => 0x01,
+            Self::TURNCHANGE => 0x02,
+            Self::WINNER => 0x03,
+            Self::CALLS => 0x04,
+            Self::ERROR => 0x05,
+            Self::DEALT => 0x06,
+        };
+
+        return [leading, 0x00, 0x00, 0x00];
+    }
+
+    pub fn get(value: u8) -> Option<Broadcast> {
+        match value {
+            0 => Some(Self::DREW),
+            1 => Some(Self::DISCARDED),
+            2 => Some(Self::TURNCHANGE),
+            3 => Some(Self::WINNER),
+            4 => Some(Self::CALLS),
+            5 => Some(Self::ERROR),
+            6 => Some(Self::DEALT),
+            _ => None,
+        }
+    }
+}
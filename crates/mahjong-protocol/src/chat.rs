@@ -0,0 +1,147 @@
+use serde::Serialize;
+
+use crate::{
+    errors::Error,
+    packet::{Packet, PacketKind},
+};
+
+/// Preset reactions. The only chat allowed at tables where free text is off (ranked rooms).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub enum Reaction {
+    GoodLuck = 1,
+    NiceHand = 2,
+    Thanks = 3,
+    Oops = 4,
+    Hurry = 5,
+    GoodGame = 6,
+}
+
+impl Reaction {
+    pub fn get(id: u32) -> Option<Self> {
+        match id {
+            1 => Some(Self::GoodLuck),
+            2 => Some(Self::NiceHand),
+            3 => Some(Self::Thanks),
+            4 => Some(Self::Oops),
+            5 => Some(Self::Hurry),
+            6 => Some(Self::GoodGame),
+            _ => None,
+        }
+    }
+
+    pub fn id(&self) -> u32 {
+        return *self as u32;
+    }
+}
+
+/// Table chat. Clients send the operation and its payload; the server relays messages
+/// and reactions to the table with the sender's player id in front of the payload.
+/// Mutes only change what the server relays to the player who sent them.
+///
+/// ```text
+/// client: | op (u32) | payload |
+/// server: | op (u32) | sender player id (i32) | payload |
+/// ```
+/// Ops: 1 message (utf-8 text), 2 reaction (u32 id), 3 mute and 4 unmute (player id, i32).
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Chat {
+    Message(String),
+    Reaction(Reaction),
+    Mute(i32),
+    Unmute(i32),
+}
+
+impl Chat {
+    pub fn parse(bytes: &[u8]) -> Result<Self, Error> {
+        let op = bytes.get(..4).ok_or(Error::PacketParsingFailed(106))?;
+        let payload = &bytes[4..];
+        let int = || {
+            let b = payload.get(..4).ok_or(Error::PacketParsingFailed(106))?;
+            return Ok::<_, Error>([b[0], b[1], b[2], b[3]]);
+        };
+
+        match u32::from_le_bytes([op[0], op[1], op[2], op[3]]) {
+            1 => String::from_utf8(payload.to_vec())
+                .map(Self::Message)
+                .map_err(|_| Error::PacketParsingFailed(106)),
+            2 => Reaction::get(u32::from_le_bytes(int()?))
+                .map(Self::Reaction)
+                .ok_or(Error::OperationFailed(65)),
+            3 => Ok(Self::Mute(i32::from_le_bytes(int()?))),
+            4 => Ok(Self::Unmute(i32::from_le_bytes(int()?))),
+            _ => Err(Error::PacketParsingFailed(106)),
+        }
+    }
+
+    pub fn op(&self) -> [u8; 4] {
+        match self {
+            Self::Message(_) => [0x01, 0x00, 0x00, 0x00],
+            Self::Reaction(_) => [0x02, 0x00, 0x00, 0x00],
+            Self::Mute(_) => [0x03, 0x00, 0x00, 0x00],
+            Self::Unmute(_) => [0x04, 0x00, 0x00, 0x00],
+        }
+    }
+
+    fn payload(&self) -> Vec<u8> {
+        match self {
+            Self::Message(text) => text.as_bytes().to_vec(),
+            Self::Reaction(reaction) => reaction.id().to_le_bytes().to_vec(),
+            Self::Mute(id) | Self::Unmute(id) => id.to_le_bytes().to_vec(),
+        }
+    }
+
+    pub fn bytes(&self) -> Vec<u8> {
+        let mut bytes = self.op().to_vec();
+        bytes.extend(self.payload());
+        return bytes;
+    }
+
+    pub fn packet(&self, id: i32) -> Packet {
+        return Packet::create(id, PacketKind::Chat, &self.bytes());
+    }
+
+    // The message as relayed to the table.
+    pub fn relay(&self, id: i32, sender: i32) -> Packet {
+        let mut body = self.op().to_vec();
+        body.extend(sender.to_le_bytes());
+        body.extend(self.payload());
+        return Packet::create(id, PacketKind::Chat, &body);
+    }
+
+    // Reads a relayed message back into its sender and content.
+    pub fn parse_relay(bytes: &[u8]) -> Result<(i32, Self), Error> {
+        let sender = bytes.get(4..8).ok_or(Error::PacketParsingFailed(106))?;
+        let sender = i32::from_le_bytes([sender[0], sender[1], sender[2], sender[3]]);
+        let mut chat = bytes[..4].to_vec();
+        chat.extend(&bytes[8..]);
+        return Ok((sender, Self::parse(&chat)?));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn round_trip() {
+        for chat in [
+            Chat::Message("ron!".to_string()),
+            Chat::Reaction(Reaction::GoodGame),
+            Chat::Mute(-4),
+            Chat::Unmute(9),
+        ] {
+            let packet = chat.packet(3);
+            assert_eq!(packet.kind, PacketKind::Chat);
+            assert_eq!(Chat::parse(&packet.body).unwrap(), chat);
+
+            let relayed = chat.relay(8, 42);
+            assert_eq!(Chat::parse_relay(&relayed.body).unwrap(), (42, chat));
+        }
+
+        assert!(Chat::parse(&[0x02, 0, 0, 0, 0x63, 0, 0, 0]).is_err());
+        assert!(Chat::parse(&[0x03, 0, 0, 0, 0x01]).is_err());
+        assert!(Chat::parse(&[0x09, 0, 0, 0]).is_err());
+        assert!(Chat::parse_relay(&[0x01, 0, 0, 0]).is_err());
+    }
+}
@@ -0,0 +1,74 @@
+use serde::{Serialize, de::DeserializeOwned};
+
+use crate::{errors::Error, version::Capabilities};
+
+/// Encoding of serialized payloads (views, tiles, meld flags...).
+/// CBOR is the default; JSON is picked when the client asks for it in the handshake.
+/// Raw byte fields such as the Setup operation or Action code are not affected.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Codec {
+    Cbor,
+    Json,
+}
+
+impl Codec {
+    pub fn from_capabilities(capabilities: Capabilities) -> Self {
+        match capabilities.contains(Capabilities::JSON_PAYLOADS) {
+            true => Self::Json,
+            false => Self::Cbor,
+        }
+    }
+
+    pub fn encode<T: Serialize>(&self, value: &T) -> Result<Vec<u8>, Error> {
+        match self {
+            Self::Cbor => serde_cbor::to_vec(value).map_err(|_| Error::SerializationFailed(11)),
+            Self::Json => serde_json::to_vec(value).map_err(|_| Error::SerializationFailed(11)),
+        }
+    }
+
+    pub fn decode<T: DeserializeOwned>(&self, bytes: &[u8]) -> Result<T, Error> {
+        match self {
+            Self::Cbor => {
+                serde_cbor::from_slice(bytes).map_err(|_| Error::PacketParsingFailed(101))
+            }
+            Self::Json => {
+                serde_json::from_slice(bytes).map_err(|_| Error::PacketParsingFailed(101))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::enums::{Tile, TileKind};
+
+    #[test]
+    fn round_trip() {
+        let tile = Tile {
+            copy: 2,
+            kind: TileKind::Manzu5,
+        };
+
+        for codec in [Codec::Cbor, Codec::Json] {
+            let bytes = codec.encode(&tile).unwrap();
+            let decoded: Tile = codec.decode(&bytes).unwrap();
+            assert_eq!(decoded.copy, 2);
+            assert!(decoded.kind == TileKind::Manzu5);
+        }
+
+        let json = Codec::Json.encode(&tile).unwrap();
+        assert_eq!(
+            String::from_utf8(json).unwrap(),
+            r#"{"copy":2,"kind":"Manzu5"}"#
+        );
+        assert_eq!(
+            Codec::from_capabilities(Capabilities::JSON_PAYLOADS),
+            Codec::Json
+        );
+        assert_eq!(
+            Codec::from_capabilities(Capabilities::default()),
+            Codec::Cbor
+        );
+    }
+}
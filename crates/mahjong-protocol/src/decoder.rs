@@ -0,0 +1,189 @@
+use tokio::io::{AsyncRead, AsyncReadExt};
+
+use crate::{errors::Error, packet::Packet};
+
+/// Bytes before the body: id (4) + kind (4) + size (4).
+pub const HEADER_SIZE: usize = 12;
+/// Bytes after the body.
+pub const TRAILER_SIZE: usize = 2;
+/// Smallest valid `size` value: id + kind + trailer with an empty body.
+pub const MIN_PACKET_SIZE: usize = 10;
+/// Largest accepted `size` value. Anything bigger is treated as a broken stream.
+pub const MAX_PACKET_SIZE: usize = 1 << 20;
+
+/// Buffers bytes read from a stream and splits them into packets using the
+/// `size` field of the header. A single read may hold part of a packet or
+/// several packets, so nothing here assumes one read is one packet.
+pub struct PacketDecoder {
+    buffer: Vec<u8>,
+    chunk: Box<[u8]>,
+}
+
+impl Default for PacketDecoder {
+    fn default() -> Self {
+        Self {
+            buffer: Vec::new(),
+            chunk: vec![0; 4096].into_boxed_slice(),
+        }
+    }
+}
+
+impl PacketDecoder {
+    pub fn new() -> Self {
+        return Self::default();
+    }
+
+    pub fn extend(&mut self, bytes: &[u8]) {
+        self.buffer.extend_from_slice(bytes);
+    }
+
+    // Returns the next complete packet in the buffer, or None if more bytes are needed.
+    // A packet with an unknown kind is consumed before the error is returned, so the stream stays usable.
+    // A size out of bounds means the framing is lost: the buffer is cleared and the caller should drop the connection.
+    pub fn next_packet(&mut self) -> Result<Option<Packet>, Error> {
+        if self.buffer.len() < HEADER_SIZE {
+            return Ok(None);
+        }
+
+        let size = u32::from_le_bytes([
+            self.buffer[8],
+            self.buffer[9],
+            self.buffer[10],
+            self.buffer[11],
+        ]) as usize;
+
+        if size > MAX_PACKET_SIZE {
+            self.buffer.clear();
+            return Err(Error::PacketSizeInvalid(103));
+        }
+
+        if size < MIN_PACKET_SIZE {
+            self.buffer.clear();
+            return Err(Error::PacketSizeInvalid(104));
+        }
+
+        // The size field does not count itself.
+        let frame_len = size + 4;
+        if self.buffer.len() < frame_len {
+            return Ok(None);
+        }
+
+        let frame: Vec<u8> = self.buffer.drain(..frame_len).collect();
+        return Packet::from_bytes(&frame).map(Some);
+    }
+
+    // Reads from the stream until a whole packet is buffered.
+    // Returns None once the stream is closed or fails.
+    pub async fn read_packet<R: AsyncRead + Unpin>(
+        &mut self,
+        reader: &mut R,
+    ) -> Result<Option<Packet>, Error> {
+        loop {
+            if let Some(packet) = self.next_packet()? {
+                return Ok(Some(packet));
+            }
+
+            let bytes_read = match reader.read(&mut self.chunk).await {
+                Ok(0) | Err(_) => return Ok(None),
+                Ok(n) => n,
+            };
+
+            self.buffer.extend_from_slice(&self.chunk[..bytes_read]);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::packet::{PacketKind, ToBytes};
+
+    fn hello() -> Vec<u8> {
+        Packet::create(1, PacketKind::Broadcast, b"hello").to_bytes()
+    }
+
+    #[test]
+    fn split_across_reads() {
+        let bytes = hello();
+        let mut decoder = PacketDecoder::new();
+
+        decoder.extend(&bytes[..7]);
+        assert!(decoder.next_packet().unwrap().is_none());
+        decoder.extend(&bytes[7..15]);
+        assert!(decoder.next_packet().unwrap().is_none());
+        decoder.extend(&bytes[15..]);
+
+        let packet = decoder.next_packet().unwrap().unwrap();
+        assert_eq!(&*packet.body, b"hello");
+    }
+
+    #[test]
+    fn merged_in_one_read() {
+        let mut bytes = hello();
+        bytes.extend(Packet::create(2, PacketKind::Action, &[0x00; 8000]).to_bytes());
+        let mut decoder = PacketDecoder::new();
+        decoder.extend(&bytes);
+
+        assert_eq!(decoder.next_packet().unwrap().unwrap().id, 1);
+        let large = decoder.next_packet().unwrap().unwrap();
+        assert_eq!(large.id, 2);
+        assert_eq!(large.body.len(), 8000);
+        assert!(decoder.next_packet().unwrap().is_none());
+    }
+
+    #[test]
+    fn rejects_invalid_sizes() {
+        let mut too_large = hello();
+        too_large[8..12].copy_from_slice(&((MAX_PACKET_SIZE + 1) as u32).to_le_bytes());
+        let mut decoder = PacketDecoder::new();
+        decoder.extend(&too_large);
+        assert!(matches!(
+            decoder.next_packet(),
+            Err(Error::PacketSizeInvalid(103))
+        ));
+
+        let mut too_small = hello();
+        too_small[8..12].copy_from_slice(&9u32.to_le_bytes());
+        decoder.extend(&too_small);
+        assert!(matches!(
+            decoder.next_packet(),
+            Err(Error::PacketSizeInvalid(104))
+        ));
+    }
+
+    // The smallest valid packet has no body at all, so handlers cannot assume an opcode is there.
+    #[test]
+    fn empty_body() {
+        let mut decoder = PacketDecoder::default();
+        decoder.extend(&Packet::create(3, PacketKind::Setup, &[]).to_bytes());
+        let packet = decoder.next_packet().unwrap().unwrap();
+        assert!(packet.body.is_empty());
+    }
+
+    #[test]
+    fn skips_unknown_kind() {
+        let mut bytes = hello();
+        bytes[4] = 0x40;
+        bytes.extend(hello());
+        let mut decoder = PacketDecoder::new();
+        decoder.extend(&bytes);
+
+        assert!(matches!(
+            decoder.next_packet(),
+            Err(Error::PacketParsingFailed(102))
+        ));
+        assert!(decoder.next_packet().unwrap().is_some());
+    }
+
+    #[tokio::test]
+    async fn reads_from_stream() {
+        let mut bytes = hello();
+        bytes.extend(hello());
+        let mut stream: &[u8] = &bytes;
+        let mut decoder = PacketDecoder::new();
+
+        assert!(decoder.read_packet(&mut stream).await.unwrap().is_some());
+        assert!(decoder.read_packet(&mut stream).await.unwrap().is_some());
+        assert!(decoder.read_packet(&mut stream).await.unwrap().is_none());
+    }
+}
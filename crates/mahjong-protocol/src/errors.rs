@@ -26,10 +26,16 @@ pub enum Error {
     #[error("CLIENT ERROR ({0})")]
     ConnectionFailed(u16),
 
+    #[error("CLIENT ERROR (58): protocol version {0} is not supported (server speaks {1} to {2})")]
+    VersionUnsupported(u16, u16, u16),
+
     // Protocol Related Errors
     #[error("Could not parse received packet ({0})")]
     PacketParsingFailed(u16),
 
+    #[error("Packet size is invalid ({0})")]
+    PacketSizeInvalid(u16),
+
     #[error("request error: failed to parse target Tile")]
     TileParsingFailed,
 
@@ -54,4 +60,7 @@ pub enum Error {
 
     #[error("game error: could not discard tile ({0})")]
     DiscardFailed(u16),
+
+    #[error("game error: action refused ({0})")]
+    ActionFailed(u16),
 }
@@ -0,0 +1,78 @@
+use crate::{
+    errors::Error,
+    packet::{Packet, PacketKind},
+};
+
+/// Liveness probe. Either side may ping; the other side answers with a pong
+/// echoing the timestamp, so the sender can measure the round trip without
+/// keeping any state.
+///
+/// ```text
+/// | op (u32) | timestamp (u64) |
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Heartbeat {
+    Ping(u64),
+    Pong(u64),
+}
+
+impl Heartbeat {
+    pub const SIZE: usize = 12;
+
+    pub fn parse(bytes: &[u8]) -> Result<Self, Error> {
+        let b = bytes
+            .get(..Self::SIZE)
+            .ok_or(Error::PacketParsingFailed(105))?;
+        let timestamp = u64::from_le_bytes([b[4], b[5], b[6], b[7], b[8], b[9], b[10], b[11]]);
+        match u32::from_le_bytes([b[0], b[1], b[2], b[3]]) {
+            1 => Ok(Self::Ping(timestamp)),
+            2 => Ok(Self::Pong(timestamp)),
+            _ => Err(Error::PacketParsingFailed(105)),
+        }
+    }
+
+    pub fn op(&self) -> [u8; 4] {
+        match self {
+            Self::Ping(_) => [0x01, 0x00, 0x00, 0x00],
+            Self::Pong(_) => [0x02, 0x00, 0x00, 0x00],
+        }
+    }
+
+    pub fn bytes(&self) -> [u8; Self::SIZE] {
+        let timestamp = match self {
+            Self::Ping(timestamp) | Self::Pong(timestamp) => timestamp.to_le_bytes(),
+        };
+        let mut bytes = [0; Self::SIZE];
+        bytes[..4].copy_from_slice(&self.op());
+        bytes[4..].copy_from_slice(&timestamp);
+        return bytes;
+    }
+
+    pub fn packet(&self, id: i32) -> Packet {
+        return Packet::create(id, PacketKind::Heartbeat, &self.bytes());
+    }
+
+    // The answer to a ping, None for a pong.
+    pub fn reply(&self) -> Option<Heartbeat> {
+        match self {
+            Self::Ping(timestamp) => Some(Self::Pong(*timestamp)),
+            Self::Pong(_) => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn round_trip() {
+        let ping = Heartbeat::Ping(0x0102_0304_0506_0708);
+        let packet = ping.packet(5);
+        assert_eq!(packet.kind, PacketKind::Heartbeat);
+        assert_eq!(Heartbeat::parse(&packet.body).unwrap(), ping);
+        assert_eq!(ping.reply(), Some(Heartbeat::Pong(0x0102_0304_0506_0708)));
+        assert!(Heartbeat::parse(&[0x03, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0]).is_err());
+        assert!(Heartbeat::parse(&[0x01, 0, 0, 0]).is_err());
+    }
+}
@@ -0,0 +1,19 @@
+//! Wire types shared by the mahjong server and its clients:
+//! packet framing, handshake, payload codecs and the models carried in packets.
+pub mod account;
+pub mod broadcast;
+pub mod chat;
+pub mod codec;
+pub mod decoder;
+pub mod enums;
+pub mod errors;
+pub mod heartbeat;
+pub mod lobby;
+pub mod models;
+pub mod packet;
+pub mod schema;
+pub mod session;
+pub mod setup;
+pub mod stats;
+pub mod tournament;
+pub mod version;
@@ -0,0 +1,327 @@
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    enums::Seat,
+    errors::Error,
+    packet::{Packet, PacketKind},
+    session::SessionToken,
+};
+
+/// Characters room codes are made of. Look-alikes (0/O, 1/I) are left out so codes can be read aloud.
+pub const ROOM_CODE_ALPHABET: &[u8; 32] = b"ABCDEFGHJKLMNPQRSTUVWXYZ23456789";
+pub const ROOM_CODE_SIZE: usize = 6;
+
+/// Rules a room is played with, picked by the player who creates it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum Ruleset {
+    /// Four players, east and south rounds.
+    #[default]
+    Hanchan = 1,
+    /// Four players, east round only.
+    Tonpuusen = 2,
+    /// Three players, east and south rounds.
+    Sanma = 3,
+}
+
+impl Ruleset {
+    pub fn get(id: u8) -> Option<Self> {
+        match id {
+            1 => Some(Self::Hanchan),
+            2 => Some(Self::Tonpuusen),
+            3 => Some(Self::Sanma),
+            _ => None,
+        }
+    }
+
+    pub fn id(&self) -> u8 {
+        return *self as u8;
+    }
+
+    // Seats played under these rules. Three player games leave north empty.
+    pub fn seats(&self) -> &'static [Seat] {
+        match self {
+            Self::Hanchan | Self::Tonpuusen => &[Seat::East, Seat::South, Seat::West, Seat::North],
+            Self::Sanma => &[Seat::East, Seat::South, Seat::West],
+        }
+    }
+}
+
+/// Ranked rooms of the matchmaking queue, from the one every player may enter to the one kept
+/// for the top of the ladder. Rank points won and lost depend on the tier.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum Tier {
+    #[default]
+    General = 1,
+    /// From 1 kyu.
+    Advanced = 2,
+    /// From 4 dan.
+    Expert = 3,
+    /// From 7 dan.
+    Phoenix = 4,
+}
+
+impl Tier {
+    pub const ALL: [Tier; 4] = [Self::General, Self::Advanced, Self::Expert, Self::Phoenix];
+
+    pub fn get(id: u8) -> Option<Self> {
+        return Self::ALL.into_iter().find(|tier| tier.id() == id);
+    }
+
+    pub fn id(&self) -> u8 {
+        return *self as u8;
+    }
+}
+
+/// A room as listed in the lobby.
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct RoomInfo {
+    pub code: String,
+    pub table_id: i32,
+    pub ruleset: Ruleset,
+    pub seated: usize,
+    pub seats: usize,
+    /// Joining needs the room's password.
+    pub locked: bool,
+}
+
+/// Requests of a connected player who has no seat yet (protocol version 3 and up).
+///
+/// ```text
+/// | op (u32) | payload |
+/// ```
+/// Ops: 1 list the open rooms, 2 create a room (`| ruleset (u8) | password (utf-8)? |`),
+/// 3 join a room (`| room code (6 ascii) | password (utf-8)? |`), 4 quick play (any open room),
+/// 5 enter the matchmaking queue (`| ruleset (u8) | tier (u8)? |`, general when left out), 6 leave it,
+/// 7 ask for the player's rating and rank.
+/// An empty password leaves the room open.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Lobby {
+    List,
+    Create {
+        ruleset: Ruleset,
+        password: Option<String>,
+    },
+    Join {
+        code: String,
+        password: Option<String>,
+    },
+    QuickPlay,
+    Queue(Ruleset, Tier),
+    LeaveQueue,
+    Profile,
+}
+
+impl Lobby {
+    pub fn parse(bytes: &[u8]) -> Result<Self, Error> {
+        let op = bytes.get(..4).ok_or(Error::PacketParsingFailed(107))?;
+        let payload = &bytes[4..];
+        let password = |bytes: &[u8]| {
+            let password =
+                String::from_utf8(bytes.to_vec()).map_err(|_| Error::PacketParsingFailed(107))?;
+            return Ok::<_, Error>(Some(password).filter(|password| !password.is_empty()));
+        };
+
+        match u32::from_le_bytes([op[0], op[1], op[2], op[3]]) {
+            1 => Ok(Self::List),
+            2 => {
+                let ruleset = payload.first().ok_or(Error::PacketParsingFailed(107))?;
+                let ruleset = Ruleset::get(*ruleset).ok_or(Error::OperationFailed(70))?;
+                let password = password(&payload[1..])?;
+                Ok(Self::Create { ruleset, password })
+            }
+            3 => {
+                let code = payload
+                    .get(..ROOM_CODE_SIZE)
+                    .ok_or(Error::PacketParsingFailed(107))?;
+                let code = String::from_utf8(code.to_ascii_uppercase())
+                    .map_err(|_| Error::PacketParsingFailed(107))?;
+                let password = password(&payload[ROOM_CODE_SIZE..])?;
+                Ok(Self::Join { code, password })
+            }
+            4 => Ok(Self::QuickPlay),
+            5 => {
+                let ruleset = payload.first().ok_or(Error::PacketParsingFailed(107))?;
+                let ruleset = Ruleset::get(*ruleset).ok_or(Error::OperationFailed(70))?;
+                let tier = match payload.get(1) {
+                    None => Tier::default(),
+                    Some(tier) => Tier::get(*tier).ok_or(Error::PacketParsingFailed(107))?,
+                };
+                Ok(Self::Queue(ruleset, tier))
+            }
+            6 => Ok(Self::LeaveQueue),
+            7 => Ok(Self::Profile),
+            _ => Err(Error::PacketParsingFailed(107)),
+        }
+    }
+
+    pub fn op(&self) -> [u8; 4] {
+        match self {
+            Self::List => [0x01, 0x00, 0x00, 0x00],
+            Self::Create { .. } => [0x02, 0x00, 0x00, 0x00],
+            Self::Join { .. } => [0x03, 0x00, 0x00, 0x00],
+            Self::QuickPlay => [0x04, 0x00, 0x00, 0x00],
+            Self::Queue(..) => [0x05, 0x00, 0x00, 0x00],
+            Self::LeaveQueue => [0x06, 0x00, 0x00, 0x00],
+            Self::Profile => [0x07, 0x00, 0x00, 0x00],
+        }
+    }
+
+    pub fn bytes(&self) -> Vec<u8> {
+        let mut bytes = self.op().to_vec();
+        match self {
+            Self::List | Self::QuickPlay | Self::LeaveQueue | Self::Profile => {}
+            Self::Queue(ruleset, tier) => bytes.extend([ruleset.id(), tier.id()]),
+            Self::Create { ruleset, password } => {
+                bytes.push(ruleset.id());
+                bytes.extend(password.as_deref().unwrap_or_default().as_bytes());
+            }
+            Self::Join { code, password } => {
+                bytes.extend(code.as_bytes());
+                bytes.extend(password.as_deref().unwrap_or_default().as_bytes());
+            }
+        }
+        return bytes;
+    }
+
+    pub fn packet(&self, id: i32) -> Packet {
+        return Packet::create(id, PacketKind::Lobby, &self.bytes());
+    }
+}
+
+// Room list answer: | op 1 (u32) | encoded Vec<RoomInfo> |
+pub const ROOMS_OP: [u8; 4] = [0x01, 0x00, 0x00, 0x00];
+// Sent to queued players as the queue changes: | op 3 (u32) | encoded QueueState |
+pub const QUEUE_OP: [u8; 4] = [0x03, 0x00, 0x00, 0x00];
+// Answer to leaving the queue, without payload.
+pub const LEFT_QUEUE_OP: [u8; 4] = [0x04, 0x00, 0x00, 0x00];
+// Sent on entering the lobby and when asked: | op 5 (u32) | encoded Profile |
+pub const PROFILE_OP: [u8; 4] = [0x05, 0x00, 0x00, 0x00];
+
+/// A player's standing on the ranked ladder. Only ranked (matchmade) matches change it; guests
+/// keep the starting one.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub struct Profile {
+    pub player_id: i32,
+    pub guest: bool,
+    /// Elo rating, the one matchmaking compares.
+    pub rating: i32,
+    /// Rank on the dan ladder, e.g. "novice", "3 kyu" or "2 dan".
+    pub rank: String,
+    pub points: i32,
+    /// Points the next rank needs, None at the top of the ladder.
+    pub promotion: Option<i32>,
+    pub ranked_games: u32,
+    /// Ranked tiers the player's rank lets them queue for.
+    pub tiers: Vec<Tier>,
+}
+
+/// Where a queued player stands. The rating band is how far apart ratings at the table may be;
+/// it widens the longer the player waits, until bots take the seats nobody filled.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub struct QueueState {
+    pub ruleset: Ruleset,
+    pub tier: Tier,
+    pub rating: i32,
+    pub band: u32,
+    /// Players queued for the same rules and tier, this one included.
+    pub waiting: usize,
+    pub waited_ms: u64,
+    /// Time left before the free seats are given to bots.
+    pub bots_in_ms: u64,
+}
+
+/// Answer to a create, join or quick play request: the player now has a seat at the table.
+///
+/// ```text
+/// | op 2 (u32) | table id (i32) | room code (6 ascii) | session token (32 bytes) |
+/// ```
+#[derive(Clone, PartialEq, Eq)]
+pub struct Seated {
+    pub table_id: i32,
+    pub code: String,
+    pub token: SessionToken,
+}
+
+impl Seated {
+    pub const OP: [u8; 4] = [0x02, 0x00, 0x00, 0x00];
+
+    pub fn parse(bytes: &[u8]) -> Result<Self, Error> {
+        let table_id = bytes.get(..4).ok_or(Error::PacketParsingFailed(107))?;
+        let table_id = i32::from_le_bytes([table_id[0], table_id[1], table_id[2], table_id[3]]);
+        let code = bytes
+            .get(4..4 + ROOM_CODE_SIZE)
+            .ok_or(Error::PacketParsingFailed(107))?;
+        let code = String::from_utf8(code.to_vec()).map_err(|_| Error::PacketParsingFailed(107))?;
+        let token = SessionToken::parse(&bytes[4 + ROOM_CODE_SIZE..])?;
+        return Ok(Self {
+            table_id,
+            code,
+            token,
+        });
+    }
+
+    pub fn packet(&self, id: i32) -> Packet {
+        let mut body = Self::OP.to_vec();
+        body.extend(self.table_id.to_le_bytes());
+        body.extend(self.code.as_bytes());
+        body.extend(self.token.bytes());
+        return Packet::create(id, PacketKind::Lobby, &body);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn round_trip() {
+        for request in [
+            Lobby::List,
+            Lobby::QuickPlay,
+            Lobby::Create {
+                ruleset: Ruleset::Sanma,
+                password: Some("hunter2".to_string()),
+            },
+            Lobby::Create {
+                ruleset: Ruleset::Hanchan,
+                password: None,
+            },
+            Lobby::Join {
+                code: "K7QZ2M".to_string(),
+                password: None,
+            },
+            Lobby::Queue(Ruleset::Tonpuusen, Tier::Expert),
+            Lobby::LeaveQueue,
+            Lobby::Profile,
+        ] {
+            let packet = request.packet(4);
+            assert_eq!(packet.kind, PacketKind::Lobby);
+            assert_eq!(Lobby::parse(&packet.body).unwrap(), request);
+        }
+
+        let join = Lobby::parse(&[0x03, 0, 0, 0, b'k', b'7', b'q', b'z', b'2', b'm', b'p']);
+        assert!(
+            matches!(join, Ok(Lobby::Join { code, password: Some(p) }) if code == "K7QZ2M" && p == "p")
+        );
+        assert!(matches!(
+            Lobby::parse(&[0x02, 0, 0, 0, 0x09]),
+            Err(Error::OperationFailed(70))
+        ));
+        assert!(Lobby::parse(&[0x03, 0, 0, 0, b'A']).is_err());
+        assert_eq!(
+            Lobby::parse(&[0x05, 0, 0, 0, 0x03]).unwrap(),
+            Lobby::Queue(Ruleset::Sanma, Tier::General)
+        );
+        assert!(Lobby::parse(&[0x05, 0, 0, 0, 0x01, 0x09]).is_err());
+
+        let seated = Seated {
+            table_id: 3,
+            code: "K7QZ2M".to_string(),
+            token: SessionToken([9; SessionToken::SIZE]),
+        };
+        assert!(Seated::parse(&seated.packet(1).body[4..]).unwrap() == seated);
+    }
+}
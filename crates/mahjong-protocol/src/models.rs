@@ -0,0 +1,290 @@
+use std::sync::Arc;
+
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    broadcast::Broadcast,
+    codec::Codec,
+    enums::{Seat, Tile, TileKind},
+    errors::Error,
+    packet::{Packet, PacketKind},
+};
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct JoinRequest {
+    pub id: i32,
+    pub alias: String,
+}
+
+// | id (i32) | alias (utf-8) |
+impl JoinRequest {
+    pub fn parse(bytes: &[u8]) -> Result<Self, Error> {
+        let id = bytes
+            .get(0..4)
+            .ok_or(Error::ConnectionFailed(54))?
+            .try_into()
+            .ok()
+            .map(i32::from_le_bytes)
+            .ok_or(Error::ConnectionFailed(54))?;
+        let alias_bytes = bytes.get(4..).ok_or(Error::ConnectionFailed(54))?;
+        let alias =
+            String::from_utf8(alias_bytes.into()).map_err(|_| Error::ConnectionFailed(54))?;
+        return Ok(JoinRequest { id, alias });
+    }
+
+    pub fn bytes(&self) -> Vec<u8> {
+        let mut bytes = self.id.to_le_bytes().to_vec();
+        bytes.extend_from_slice(self.alias.as_bytes());
+        return bytes;
+    }
+}
+
+/// What a spectator is shown.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum SpectatorMode {
+    /// Only what every player at the table sees, live.
+    Public,
+    /// Every hand, behind the configured delay.
+    Full,
+    /// What the given player sees, behind the configured delay.
+    Player(i32),
+}
+
+// | mode (u8) | player id (i32, only for mode 2) |
+impl SpectatorMode {
+    pub fn parse(bytes: &[u8]) -> Result<Self, Error> {
+        match bytes.first() {
+            Some(0) => Ok(Self::Public),
+            Some(1) => Ok(Self::Full),
+            Some(2) => bytes
+                .get(1..5)
+                .and_then(|id| id.try_into().ok())
+                .map(|id| Self::Player(i32::from_le_bytes(id)))
+                .ok_or(Error::ConnectionFailed(61)),
+            _ => Err(Error::ConnectionFailed(61)),
+        }
+    }
+
+    pub fn bytes(&self) -> Vec<u8> {
+        match self {
+            Self::Public => vec![0],
+            Self::Full => vec![1],
+            Self::Player(id) => {
+                let mut bytes = vec![2];
+                bytes.extend(id.to_le_bytes());
+                bytes
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::models::{JoinRequest, SpectatorMode};
+
+    #[test]
+    fn test_parse() {
+        let bytes: &[u8] = &[0x44, 0x33, 0x22, 0x11, 0x42, 0x75, 0x6E, 0x6E, 0x79];
+        let parse = JoinRequest::parse(bytes);
+        assert!(parse.is_ok());
+        let request = parse.unwrap();
+        assert_eq!(request.id, 0x11223344);
+        assert_eq!(request.alias, "Bunny");
+        assert_eq!(request.bytes(), bytes);
+    }
+
+    #[test]
+    fn spectator_modes() {
+        for mode in [
+            SpectatorMode::Public,
+            SpectatorMode::Full,
+            SpectatorMode::Player(-3),
+        ] {
+            assert_eq!(SpectatorMode::parse(&mode.bytes()).unwrap(), mode);
+        }
+        assert!(SpectatorMode::parse(&[2, 1]).is_err());
+        assert!(SpectatorMode::parse(&[9]).is_err());
+        assert!(SpectatorMode::parse(&[]).is_err());
+    }
+}
+
+#[derive(Serialize, Deserialize, JsonSchema)]
+pub struct Discard {
+    pub player_id: i32,
+    pub tile_copy: u8,
+    pub tile_kind: TileKind,
+}
+
+impl Discard {
+    pub fn broadcast(id: i32, pid: i32, target: Tile, codec: Codec) -> Packet {
+        let broadcast = Discard {
+            player_id: pid,
+            tile_kind: target.kind,
+            tile_copy: target.copy,
+        };
+        match codec.encode(&broadcast) {
+            Err(_) => Packet::error(id, Error::InternalError),
+            Ok(bytes) => {
+                let mut body: Vec<u8> = Vec::new();
+                body.extend_from_slice(&Broadcast::DISCARDED.bytes());
+                body.extend_from_slice(&bytes);
+                Packet::create(id, PacketKind::Broadcast, &body.into_boxed_slice())
+            }
+        }
+    }
+}
+
+/// A seat drew a tile. The tile is only filled in for the player who drew it.
+#[derive(Serialize, Deserialize, JsonSchema)]
+pub struct Draw {
+    pub player_id: i32,
+    pub tile: Option<Tile>,
+}
+
+impl Draw {
+    // The tile must already be cut for the receiver, see the server's view layer.
+    pub fn broadcast(id: i32, pid: i32, tile: Option<Tile>, codec: Codec) -> Packet {
+        let broadcast = Draw {
+            player_id: pid,
+            tile,
+        };
+        match codec.encode(&broadcast) {
+            Err(_) => Packet::error(id, Error::InternalError),
+            Ok(bytes) => {
+                let mut body: Vec<u8> = Vec::new();
+                body.extend_from_slice(&Broadcast::DREW.bytes());
+                body.extend_from_slice(&bytes);
+                Packet::create(id, PacketKind::Broadcast, &body.into_boxed_slice())
+            }
+        }
+    }
+}
+
+/// A seat was dealt its starting hand. The hand is only filled in for its owner
+/// (and spectators allowed to see it).
+#[derive(Serialize, Deserialize, JsonSchema)]
+pub struct Deal {
+    pub player_id: i32,
+    pub seat: Seat,
+    pub hand: Option<Vec<Tile>>,
+}
+
+impl Deal {
+    pub fn broadcast(id: i32, deal: &Deal, codec: Codec) -> Packet {
+        match codec.encode(deal) {
+            Err(_) => Packet::error(id, Error::InternalError),
+            Ok(bytes) => {
+                let mut body: Vec<u8> = Vec::new();
+                body.extend_from_slice(&Broadcast::DEALT.bytes());
+                body.extend_from_slice(&bytes);
+                Packet::create(id, PacketKind::Broadcast, &body.into_boxed_slice())
+            }
+        }
+    }
+}
+
+/// How a hand ended, as shown to the table and kept for statistics.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub struct HandResult {
+    /// Winners and the value of their hand, empty when the hand was drawn.
+    pub wins: Vec<Win>,
+    /// Player whose discard was won on; None on tsumo or a drawn hand.
+    pub dealt_in: Option<i32>,
+    /// Players who declared riichi during the hand.
+    pub riichi: Vec<i32>,
+    /// Players who called a discard (chi, pon or open kan) during the hand.
+    pub called: Vec<i32>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub struct Win {
+    pub player_id: i32,
+    /// Points the hand was worth.
+    pub value: i32,
+    pub tsumo: bool,
+}
+
+impl HandResult {
+    pub fn broadcast(id: i32, result: &HandResult, codec: Codec) -> Packet {
+        match codec.encode(result) {
+            Err(_) => Packet::error(id, Error::InternalError),
+            Ok(bytes) => {
+                let mut body: Vec<u8> = Vec::new();
+                body.extend_from_slice(&Broadcast::WINNER.bytes());
+                body.extend_from_slice(&bytes);
+                Packet::create(id, PacketKind::Broadcast, &body.into_boxed_slice())
+            }
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, JsonSchema, Clone)]
+pub struct MeldFlags {
+    pub pid: i32,
+    pub ron: bool,
+    pub pon: bool,
+    pub chi: bool,
+    pub kan: bool,
+}
+
+#[derive(Deserialize, Serialize, JsonSchema)]
+pub struct InitialPlayerView {
+    pub seat: Seat,
+    pub is_first: bool,
+    pub hand: Vec<Arc<Tile>>,
+}
+
+#[derive(Serialize, Deserialize, JsonSchema, Clone, Copy, PartialEq, Eq)]
+pub enum MeldKind {
+    Chi,
+    Pon,
+    /// Kan called on a discard or added to a pon.
+    OpenKan,
+    ClosedKan,
+}
+
+/// A called or declared set, face up on the table.
+#[derive(Serialize, Deserialize, JsonSchema, Clone)]
+pub struct Meld {
+    pub kind: MeldKind,
+    pub tiles: Vec<Tile>,
+    /// Seat the called tile came from, None for a closed kan.
+    pub called_from: Option<Seat>,
+}
+
+/// Public state of one seat, as every player at the table sees it.
+#[derive(Serialize, Deserialize, JsonSchema)]
+pub struct SeatView {
+    pub player_id: i32,
+    pub seat: Seat,
+    pub alias: String,
+    pub connected: bool,
+    pub score: i32,
+    /// Round trip to the seat's client in milliseconds, None for bots and until it answered a ping.
+    #[serde(default)]
+    pub latency_ms: Option<u32>,
+    pub hand_size: usize,
+    pub pond: Vec<Tile>,
+    pub melds: Vec<Meld>,
+}
+
+/// Everything a player needs to redraw the table after reconnecting.
+/// Scoped to the receiving player: theirs is the only hand included.
+#[derive(Serialize, Deserialize, JsonSchema)]
+pub struct ResyncSnapshot {
+    pub seat: Seat,
+    pub hand: Vec<Tile>,
+    /// Every seated player in seat order (east, south, west, north), the receiver included.
+    pub seats: Vec<SeatView>,
+    pub dora_indicators: Vec<Tile>,
+    pub turn: i32,
+    pub current_seat: Seat,
+    /// Calls the player was offered on the last discard and has not answered yet.
+    pub pending_calls: Option<MeldFlags>,
+    /// Time spent on the current turn, None before the first turn started.
+    pub turn_elapsed_ms: Option<u64>,
+    /// Last match event sequence number read before the snapshot was taken.
+    /// Events after it are sent next; the newest of them may already show in the snapshot.
+    pub last_sequence: i32,
+}
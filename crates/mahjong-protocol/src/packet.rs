@@ -1,15 +1,21 @@
-use tokio::{
-    io::AsyncWriteExt,
-    net::{TcpStream, tcp::OwnedWriteHalf},
-};
+use tokio::io::{AsyncWrite, AsyncWriteExt};
 
-use crate::utils::errors::Error;
+use crate::{
+    decoder::{HEADER_SIZE, TRAILER_SIZE},
+    errors::Error,
+};
 
 #[derive(Debug, PartialEq, Clone)]
 pub enum PacketKind {
     Setup = 1,
     Action = 2,
     Broadcast = 3,
+    Heartbeat = 4,
+    Chat = 5,
+    Lobby = 6,
+    Stats = 7,
+    Leaderboard = 8,
+    Tournament = 9,
     Error = 255,
 }
 
@@ -19,6 +25,12 @@ impl PacketKind {
             1 => Some(Self::Setup),
             2 => Some(Self::Action),
             3 => Some(Self::Broadcast),
+            4 => Some(Self::Heartbeat),
+            5 => Some(Self::Chat),
+            6 => Some(Self::Lobby),
+            7 => Some(Self::Stats),
+            8 => Some(Self::Leaderboard),
+            9 => Some(Self::Tournament),
             255 => Some(Self::Error),
             _ => None,
         }
@@ -28,8 +40,14 @@ impl PacketKind {
         match self {
             PacketKind::Setup => [0x01, 0x00, 0x00, 0x00],
             PacketKind::Action => [0x02, 0x00, 0x00, 0x00],
-            PacketKind::Error => [0x05, 0x00, 0x00, 0x00],
+            PacketKind::Error => [0xFF, 0x00, 0x00, 0x00],
             PacketKind::Broadcast => [0x03, 0x00, 0x00, 0x00],
+            PacketKind::Heartbeat => [0x04, 0x00, 0x00, 0x00],
+            PacketKind::Chat => [0x05, 0x00, 0x00, 0x00],
+            PacketKind::Lobby => [0x06, 0x00, 0x00, 0x00],
+            PacketKind::Stats => [0x07, 0x00, 0x00, 0x00],
+            PacketKind::Leaderboard => [0x08, 0x00, 0x00, 0x00],
+            PacketKind::Tournament => [0x09, 0x00, 0x00, 0x00],
         }
     }
 }
@@ -44,7 +62,7 @@ pub struct Packet {
 
 impl Packet {
     pub fn from_bytes(b: &[u8]) -> Result<Packet, Error> {
-        if b.len() < 10 {
+        if b.len() < HEADER_SIZE + TRAILER_SIZE {
             return Err(Error::PacketParsingFailed(101));
         }
         match PacketKind::from_byte(u32::from_le_bytes([b[4], b[5], b[6], b[7]])) {
@@ -83,15 +101,7 @@ pub trait WriteBytesExt {
 }
 
 #[async_trait::async_trait]
-impl WriteBytesExt for TcpStream {
-    async fn send_packet<T: ToBytes + Send + Sync>(&mut self, value: &T) -> tokio::io::Result<()> {
-        let bytes = value.to_bytes();
-        self.write_all(&bytes).await
-    }
-}
-
-#[async_trait::async_trait]
-impl WriteBytesExt for OwnedWriteHalf {
+impl<W: AsyncWrite + Send + Unpin> WriteBytesExt for W {
     async fn send_packet<T: ToBytes + Send + Sync>(&mut self, value: &T) -> tokio::io::Result<()> {
         let bytes = value.to_bytes();
         self.write_all(&bytes).await
@@ -138,4 +148,13 @@ mod tests {
             assert_eq!(body, "hello");
         }
     }
+
+    #[test]
+    fn from_short_bytes() {
+        let bytes = [
+            0x01, 0x00, 0x00, 0x00, 0x03, 0x00, 0x00, 0x00, 0x0A, 0x00, 0x00,
+        ];
+        assert!(Packet::from_bytes(&bytes[..10]).is_err());
+        assert!(Packet::from_bytes(&bytes).is_err());
+    }
 }
@@ -0,0 +1,572 @@
+use schemars::{JsonSchema, Schema, SchemaGenerator, generate::SchemaSettings};
+use serde::Serialize;
+use serde_json::{Value, json};
+
+use crate::{
+    broadcast::Broadcast,
+    chat::{Chat, Reaction},
+    enums::{Action, Tile},
+    errors::Error,
+    heartbeat::Heartbeat,
+    lobby::{
+        LEFT_QUEUE_OP, Lobby, PROFILE_OP, Profile, QUEUE_OP, QueueState, ROOMS_OP, RoomInfo,
+        Ruleset, Seated, Tier,
+    },
+    models::{Deal, Discard, Draw, HandResult, InitialPlayerView, MeldFlags, ResyncSnapshot},
+    packet::PacketKind,
+    setup::Setup,
+    stats::{LeaderboardPage, PlayerStats},
+    tournament::{
+        TOURNAMENT_OP, TOURNAMENTS_OP, TournamentInfo, TournamentRequest, TournamentView,
+    },
+    version::{Handshake, PROTOCOL_VERSION},
+};
+
+pub const SCHEMA_PATH: &str = "./schema/protocol.json";
+
+#[derive(Serialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum Direction {
+    ClientToServer,
+    ServerToClient,
+}
+
+/// What follows the opcode in the packet body.
+pub enum Payload {
+    Empty,
+    /// Fixed byte layout, described as written in the README.
+    Raw(&'static str),
+    /// Model serialized with the negotiated codec (CBOR or JSON).
+    Encoded(fn(&mut SchemaGenerator) -> Schema),
+}
+
+/// One message of the protocol. Kind and opcode are read from the Rust enums,
+/// so renumbering a code shows up as a diff in the exported schema.
+pub struct MessageSpec {
+    pub name: &'static str,
+    pub direction: Direction,
+    pub kind: PacketKind,
+    /// Setup operation or Action code at the start of the body.
+    pub opcode: Option<[u8; 4]>,
+    /// Bytes the opcode takes. Client actions send a single byte (see `GameAction::parse`),
+    /// everything else the four byte little endian code.
+    pub opcode_size: usize,
+    pub payload: Payload,
+}
+
+fn encoded<T: JsonSchema>() -> Payload {
+    Payload::Encoded(SchemaGenerator::subschema_for::<T>)
+}
+
+fn setup(name: &'static str, direction: Direction, op: Setup, payload: Payload) -> MessageSpec {
+    MessageSpec {
+        name,
+        direction,
+        kind: PacketKind::Setup,
+        opcode: Some(op.bytes()),
+        opcode_size: 4,
+        payload,
+    }
+}
+
+fn action(name: &'static str, direction: Direction, op: Action, payload: Payload) -> MessageSpec {
+    MessageSpec {
+        name,
+        direction,
+        kind: PacketKind::Action,
+        opcode: Some(op.bytes()),
+        opcode_size: match direction {
+            Direction::ClientToServer => 1,
+            Direction::ServerToClient => 4,
+        },
+        payload,
+    }
+}
+
+fn broadcast(name: &'static str, op: Broadcast, payload: Payload) -> MessageSpec {
+    MessageSpec {
+        name,
+        direction: Direction::ServerToClient,
+        kind: PacketKind::Broadcast,
+        opcode: Some(op.bytes()),
+        opcode_size: 4,
+        payload,
+    }
+}
+
+fn chat(name: &'static str, direction: Direction, op: Chat, layout: &'static str) -> MessageSpec {
+    MessageSpec {
+        name,
+        direction,
+        kind: PacketKind::Chat,
+        opcode: Some(op.op()),
+        opcode_size: 4,
+        payload: Payload::Raw(layout),
+    }
+}
+
+fn lobby(name: &'static str, direction: Direction, op: [u8; 4], payload: Payload) -> MessageSpec {
+    MessageSpec {
+        name,
+        direction,
+        kind: PacketKind::Lobby,
+        opcode: Some(op),
+        opcode_size: 4,
+        payload,
+    }
+}
+
+fn tournament(
+    name: &'static str,
+    direction: Direction,
+    op: [u8; 4],
+    payload: Payload,
+) -> MessageSpec {
+    MessageSpec {
+        name,
+        direction,
+        kind: PacketKind::Tournament,
+        opcode: Some(op),
+        opcode_size: 4,
+        payload,
+    }
+}
+
+fn heartbeat(name: &'static str, direction: Direction, op: Heartbeat) -> MessageSpec {
+    MessageSpec {
+        name,
+        direction,
+        kind: PacketKind::Heartbeat,
+        opcode: Some(op.op()),
+        opcode_size: 4,
+        payload: Payload::Raw("timestamp (u64), echoed back unchanged in the pong"),
+    }
+}
+
+const JOIN_LAYOUT: &str = "version (u16) | capabilities (u32) | id (i32, ignored from version 4: the server assigns guest ids) | alias (utf-8)";
+const CREDENTIALS_LAYOUT: &str = "version (u16) | capabilities (u32) | username length (u8) | username (utf-8) | password (utf-8)";
+const IDENTIFIED_LAYOUT: &str =
+    "version (u16) | capabilities (u32) | player id (i32) | guest (u8: 1 guest, 0 account)";
+const SPECTATE_LAYOUT: &str = "version (u16) | capabilities (u32) | mode (u8: 0 public, 1 full, 2 player) | player id (i32, mode 2 only) | table id (i32, optional)";
+const SESSION_LAYOUT: &str = "version (u16) | capabilities (u32) | session token (32 bytes)";
+const CONNECTED_LAYOUT: &str = "version (u16) | capabilities (u32) | session token (32 bytes, only below version 3: later versions get it when the lobby seats them) | player id (i32) and guest (u8), from version 4";
+
+pub fn catalog() -> Vec<MessageSpec> {
+    use Direction::{ClientToServer as C2S, ServerToClient as S2C};
+    vec![
+        setup(
+            "Connection",
+            C2S,
+            Setup::Connection,
+            Payload::Raw(JOIN_LAYOUT),
+        ),
+        setup(
+            "Connected",
+            S2C,
+            Setup::Connection,
+            Payload::Raw(CONNECTED_LAYOUT),
+        ),
+        setup(
+            "Register",
+            C2S,
+            Setup::Register,
+            Payload::Raw(CREDENTIALS_LAYOUT),
+        ),
+        setup(
+            "Registered",
+            S2C,
+            Setup::Register,
+            Payload::Raw(IDENTIFIED_LAYOUT),
+        ),
+        setup("Login", C2S, Setup::Login, Payload::Raw(CREDENTIALS_LAYOUT)),
+        setup(
+            "LoggedIn",
+            S2C,
+            Setup::Login,
+            Payload::Raw(IDENTIFIED_LAYOUT),
+        ),
+        setup(
+            "Reconnection",
+            C2S,
+            Setup::Reconnection,
+            Payload::Raw(SESSION_LAYOUT),
+        ),
+        setup(
+            "Reconnected",
+            S2C,
+            Setup::Reconnection,
+            Payload::Raw(SESSION_LAYOUT),
+        ),
+        setup("Initialization", C2S, Setup::Initialization, Payload::Empty),
+        setup(
+            "InitialView",
+            S2C,
+            Setup::Initialization,
+            encoded::<InitialPlayerView>(),
+        ),
+        setup("Ready", C2S, Setup::Ready, Payload::Empty),
+        setup("Resync", C2S, Setup::Resync, Payload::Empty),
+        // Also sent unasked right after a successful Reconnection.
+        setup("Snapshot", S2C, Setup::Resync, encoded::<ResyncSnapshot>()),
+        setup(
+            "Replay",
+            C2S,
+            Setup::Replay,
+            Payload::Raw("last seen sequence (i32)"),
+        ),
+        // Sent after the replayed events, which keep their sequence number as packet id.
+        setup(
+            "ReplayDone",
+            S2C,
+            Setup::Replay,
+            Payload::Raw("last sequence sent (i32)"),
+        ),
+        setup(
+            "Spectate",
+            C2S,
+            Setup::Spectate,
+            Payload::Raw(SPECTATE_LAYOUT),
+        ),
+        // Followed by every match event so far, in the spectator's view.
+        setup(
+            "Spectating",
+            S2C,
+            Setup::Spectate,
+            Payload::Raw("version (u16) | capabilities (u32) | spectator id (i32)"),
+        ),
+        // The ready acknowledgement is a bare 0x00 byte without an operation code.
+        MessageSpec {
+            name: "ReadyAck",
+            direction: S2C,
+            kind: PacketKind::Setup,
+            opcode: None,
+            opcode_size: 0,
+            payload: Payload::Raw("0x00"),
+        },
+        action("Draw", C2S, Action::DRAW, Payload::Empty),
+        action("DrawnTile", S2C, Action::DRAW, encoded::<Tile>()),
+        action(
+            "Discard",
+            C2S,
+            Action::DISCARD,
+            Payload::Raw("tile kind (u8) | tile copy (u8)"),
+        ),
+        action(
+            "CallsAvailable",
+            S2C,
+            Action::DISCARD,
+            encoded::<MeldFlags>(),
+        ),
+        action("Kan", C2S, Action::KAN, Payload::Empty),
+        action("Pon", C2S, Action::PON, Payload::Empty),
+        action("Chi", C2S, Action::CHI, Payload::Empty),
+        action("Ron", C2S, Action::RON, Payload::Empty),
+        action("Tsumo", C2S, Action::TSUMO, Payload::Empty),
+        broadcast("Discarded", Broadcast::DISCARDED, encoded::<Discard>()),
+        // The tile is only set for the player who drew it.
+        broadcast("Drew", Broadcast::DREW, encoded::<Draw>()),
+        // The hand is only set for its owner and full-view spectators.
+        broadcast("Dealt", Broadcast::DEALT, encoded::<Deal>()),
+        broadcast("HandEnded", Broadcast::WINNER, encoded::<HandResult>()),
+        // Either side may ping; the other answers.
+        heartbeat("Ping", S2C, Heartbeat::Ping(0)),
+        heartbeat("Pong", C2S, Heartbeat::Pong(0)),
+        heartbeat("Ping", C2S, Heartbeat::Ping(0)),
+        heartbeat("Pong", S2C, Heartbeat::Pong(0)),
+        chat(
+            "ChatMessage",
+            C2S,
+            Chat::Message(String::new()),
+            "text (utf-8)",
+        ),
+        chat(
+            "ChatReaction",
+            C2S,
+            Chat::Reaction(Reaction::GoodLuck),
+            "reaction id (u32)",
+        ),
+        chat("Mute", C2S, Chat::Mute(0), "player id (i32)"),
+        chat("Unmute", C2S, Chat::Unmute(0), "player id (i32)"),
+        // Relayed through the match event log: the packet id is the sequence number.
+        chat(
+            "ChatMessage",
+            S2C,
+            Chat::Message(String::new()),
+            "sender player id (i32) | text (utf-8)",
+        ),
+        chat(
+            "ChatReaction",
+            S2C,
+            Chat::Reaction(Reaction::GoodLuck),
+            "sender player id (i32) | reaction id (u32)",
+        ),
+        lobby("ListRooms", C2S, Lobby::List.op(), Payload::Empty),
+        lobby(
+            "CreateRoom",
+            C2S,
+            Lobby::Create {
+                ruleset: Ruleset::default(),
+                password: None,
+            }
+            .op(),
+            Payload::Raw(
+                "ruleset (u8: 1 hanchan, 2 tonpuusen, 3 sanma) | password (utf-8, empty for an open room)",
+            ),
+        ),
+        lobby(
+            "JoinRoom",
+            C2S,
+            Lobby::Join {
+                code: String::new(),
+                password: None,
+            }
+            .op(),
+            Payload::Raw("room code (6 ascii) | password (utf-8, empty for an open room)"),
+        ),
+        lobby("QuickPlay", C2S, Lobby::QuickPlay.op(), Payload::Empty),
+        lobby("Rooms", S2C, ROOMS_OP, encoded::<Vec<RoomInfo>>()),
+        lobby(
+            "Seated",
+            S2C,
+            Seated::OP,
+            Payload::Raw("table id (i32) | room code (6 ascii) | session token (32 bytes)"),
+        ),
+        lobby(
+            "EnterQueue",
+            C2S,
+            Lobby::Queue(Ruleset::default(), Tier::default()).op(),
+            Payload::Raw(
+                "ruleset (u8: 1 hanchan, 2 tonpuusen, 3 sanma) | tier (u8, optional: 1 general, 2 advanced, 3 expert, 4 phoenix)",
+            ),
+        ),
+        lobby("LeaveQueue", C2S, Lobby::LeaveQueue.op(), Payload::Empty),
+        lobby("QueueState", S2C, QUEUE_OP, encoded::<QueueState>()),
+        lobby("LeftQueue", S2C, LEFT_QUEUE_OP, Payload::Empty),
+        lobby("GetProfile", C2S, Lobby::Profile.op(), Payload::Empty),
+        // Also sent unasked to version 4 clients entering the lobby.
+        lobby("Profile", S2C, PROFILE_OP, encoded::<Profile>()),
+        // Sent from the lobby or a table. Without a player id the sender's own statistics are sent.
+        MessageSpec {
+            name: "StatsQuery",
+            direction: C2S,
+            kind: PacketKind::Stats,
+            opcode: None,
+            opcode_size: 0,
+            payload: Payload::Raw("player id (i32, optional)"),
+        },
+        MessageSpec {
+            name: "Stats",
+            direction: S2C,
+            kind: PacketKind::Stats,
+            opcode: None,
+            opcode_size: 0,
+            payload: encoded::<PlayerStats>(),
+        },
+        // Sent from the lobby or a table.
+        MessageSpec {
+            name: "LeaderboardQuery",
+            direction: C2S,
+            kind: PacketKind::Leaderboard,
+            opcode: None,
+            opcode_size: 0,
+            payload: Payload::Raw(
+                "board (u8: 1 rating, 2 games, 3 placement) | ruleset (u8, 0 for every ruleset) | season (u32, 0 for the current one) | page (u32, from 0)",
+            ),
+        },
+        MessageSpec {
+            name: "Leaderboard",
+            direction: S2C,
+            kind: PacketKind::Leaderboard,
+            opcode: None,
+            opcode_size: 0,
+            payload: encoded::<LeaderboardPage>(),
+        },
+        // Sent from the lobby. Create, start and swap are for admins only.
+        tournament(
+            "ListTournaments",
+            C2S,
+            TournamentRequest::List.op(),
+            Payload::Empty,
+        ),
+        tournament(
+            "CreateTournament",
+            C2S,
+            TournamentRequest::Create {
+                ruleset: Ruleset::default(),
+                rounds: 1,
+                name: String::new(),
+            }
+            .op(),
+            Payload::Raw("ruleset (u8) | rounds (u8) | name (utf-8)"),
+        ),
+        tournament(
+            "RegisterTournament",
+            C2S,
+            TournamentRequest::Register(0).op(),
+            Payload::Raw("tournament id (u32)"),
+        ),
+        tournament(
+            "WithdrawTournament",
+            C2S,
+            TournamentRequest::Withdraw(0).op(),
+            Payload::Raw("tournament id (u32)"),
+        ),
+        tournament(
+            "StartTournament",
+            C2S,
+            TournamentRequest::Start(0).op(),
+            Payload::Raw("tournament id (u32)"),
+        ),
+        tournament(
+            "ShowTournament",
+            C2S,
+            TournamentRequest::Show(0).op(),
+            Payload::Raw("tournament id (u32)"),
+        ),
+        // Answered with the lobby's Seated.
+        tournament(
+            "PlayTournament",
+            C2S,
+            TournamentRequest::Play(0).op(),
+            Payload::Raw("tournament id (u32)"),
+        ),
+        tournament(
+            "SwapSeats",
+            C2S,
+            TournamentRequest::Swap {
+                tournament: 0,
+                first: 0,
+                second: 0,
+            }
+            .op(),
+            Payload::Raw("tournament id (u32) | player id (i32) | player id (i32)"),
+        ),
+        tournament(
+            "ForfeitSeat",
+            C2S,
+            TournamentRequest::Forfeit {
+                tournament: 0,
+                player: 0,
+            }
+            .op(),
+            Payload::Raw("tournament id (u32) | player id (i32)"),
+        ),
+        tournament(
+            "Tournaments",
+            S2C,
+            TOURNAMENTS_OP,
+            encoded::<Vec<TournamentInfo>>(),
+        ),
+        tournament(
+            "Tournament",
+            S2C,
+            TOURNAMENT_OP,
+            encoded::<TournamentView>(),
+        ),
+        MessageSpec {
+            name: "Error",
+            direction: S2C,
+            kind: PacketKind::Error,
+            opcode: None,
+            opcode_size: 0,
+            payload: Payload::Raw("message (utf-8)"),
+        },
+    ]
+}
+
+// Builds the document clients generate code from: packet layout, every message and
+// the JSON Schema of every encoded payload under `$defs`.
+pub fn export() -> Value {
+    let mut generator = SchemaGenerator::new(SchemaSettings::draft2020_12());
+    let messages: Vec<Value> = catalog()
+        .into_iter()
+        .map(|spec| {
+            let payload = match spec.payload {
+                Payload::Empty => json!({ "encoding": "none" }),
+                Payload::Raw(layout) => json!({ "encoding": "raw", "layout": layout }),
+                Payload::Encoded(schema) => {
+                    json!({ "encoding": "codec", "schema": schema(&mut generator) })
+                }
+            };
+
+            json!({
+                "name": spec.name,
+                "direction": spec.direction,
+                "kind": format!("{:?}", spec.kind),
+                "kind_code": u32::from_le_bytes(spec.kind.bytes()),
+                "opcode": spec.opcode.map(u32::from_le_bytes),
+                "opcode_size": spec.opcode_size,
+                "payload": payload,
+            })
+        })
+        .collect();
+
+    json!({
+        "$schema": "https://json-schema.org/draft/2020-12/schema",
+        "protocol_version": PROTOCOL_VERSION,
+        "packet": {
+            "header": "id (i32) | kind (u32) | size (u32)",
+            "trailer": "0x00 0x00",
+            "handshake_size": Handshake::SIZE,
+        },
+        "messages": messages,
+        "$defs": generator.definitions(),
+    })
+}
+
+pub fn write(path: &str) -> Result<(), Error> {
+    let document =
+        serde_json::to_string_pretty(&export()).map_err(|_| Error::SerializationFailed(12))?;
+    if let Some(parent) = std::path::Path::new(path).parent() {
+        std::fs::create_dir_all(parent).map_err(|_| Error::SerializationFailed(12))?;
+    }
+    std::fs::write(path, document + "\n").map_err(|_| Error::SerializationFailed(12))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn has(direction: Direction, kind: PacketKind, opcode: [u8; 4]) -> bool {
+        catalog()
+            .iter()
+            .any(|m| m.direction == direction && m.kind == kind && m.opcode == Some(opcode))
+    }
+
+    #[test]
+    fn covers_every_code() {
+        for code in 0..=u8::MAX {
+            if let Some(action) = Action::get(code) {
+                assert!(has(
+                    Direction::ClientToServer,
+                    PacketKind::Action,
+                    action.bytes()
+                ));
+            }
+            if let Some(op) = Setup::from(&[code, 0, 0, 0]) {
+                assert!(has(
+                    Direction::ClientToServer,
+                    PacketKind::Setup,
+                    op.bytes()
+                ));
+            }
+        }
+
+        for spec in catalog() {
+            let code = u32::from_le_bytes(spec.kind.bytes());
+            assert_eq!(PacketKind::from_byte(code), Some(spec.kind));
+        }
+    }
+
+    // Regenerate with `cargo run -- --export-schema` after changing the protocol.
+    #[test]
+    fn matches_checked_in_schema() {
+        let path = concat!(env!("CARGO_MANIFEST_DIR"), "/../../schema/protocol.json");
+        let checked_in: Value =
+            serde_json::from_str(&std::fs::read_to_string(path).unwrap()).unwrap();
+        assert!(
+            checked_in == export(),
+            "schema/protocol.json is out of date, run `cargo run -- --export-schema`"
+        );
+    }
+}
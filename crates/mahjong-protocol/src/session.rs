@@ -0,0 +1,75 @@
+use std::{
+    fmt::{self, Display},
+    str::FromStr,
+};
+
+use crate::errors::Error;
+
+/// Secret handed to a player when they first connect and required to take
+/// their seat back on reconnection. Never logged; shown as hex only to its owner.
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+pub struct SessionToken(pub [u8; SessionToken::SIZE]);
+
+impl SessionToken {
+    pub const SIZE: usize = 32;
+
+    pub fn parse(bytes: &[u8]) -> Result<Self, Error> {
+        let bytes = bytes
+            .get(..Self::SIZE)
+            .ok_or(Error::ReconnectionFailed(59))?;
+        let mut token = [0; Self::SIZE];
+        token.copy_from_slice(bytes);
+        return Ok(Self(token));
+    }
+
+    pub fn bytes(&self) -> [u8; Self::SIZE] {
+        self.0
+    }
+}
+
+impl Display for SessionToken {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for byte in self.0 {
+            write!(f, "{byte:02x}")?;
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for SessionToken {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s.len() != Self::SIZE * 2 || !s.is_ascii() {
+            return Err(Error::ReconnectionFailed(59));
+        }
+
+        let mut token = [0; Self::SIZE];
+        for (index, byte) in token.iter_mut().enumerate() {
+            let pair = &s[index * 2..index * 2 + 2];
+            *byte = u8::from_str_radix(pair, 16).map_err(|_| Error::ReconnectionFailed(59))?;
+        }
+        return Ok(Self(token));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn hex_round_trip() {
+        let mut bytes = [0; SessionToken::SIZE];
+        for (index, byte) in bytes.iter_mut().enumerate() {
+            *byte = (index * 7) as u8;
+        }
+        let token = SessionToken(bytes);
+        let hex = token.to_string();
+        assert_eq!(hex.len(), 64);
+        assert!(hex.parse::<SessionToken>().unwrap() == token);
+        assert!(SessionToken::parse(&token.bytes()).unwrap() == token);
+
+        assert!("zz".repeat(32).parse::<SessionToken>().is_err());
+        assert!(SessionToken::parse(&bytes[..31]).is_err());
+    }
+}
@@ -3,6 +3,11 @@ pub enum Setup {
     Reconnection = 2,
     Initialization = 3,
     Ready = 4,
+    Resync = 5,
+    Replay = 6,
+    Spectate = 7,
+    Register = 8,
+    Login = 9,
 }
 
 impl Setup {
@@ -12,6 +17,11 @@ impl Setup {
             2 => Some(Self::Reconnection),
             3 => Some(Self::Initialization),
             4 => Some(Self::Ready),
+            5 => Some(Self::Resync),
+            6 => Some(Self::Replay),
+            7 => Some(Self::Spectate),
+            8 => Some(Self::Register),
+            9 => Some(Self::Login),
             _ => None,
         }
     }
@@ -22,6 +32,11 @@ impl Setup {
             Self::Reconnection => [0x02, 0x00, 0x00, 0x00],
             Self::Initialization => [0x03, 0x00, 0x00, 0x00],
             Self::Ready => [0x04, 0x00, 0x00, 0x00],
+            Self::Resync => [0x05, 0x00, 0x00, 0x00],
+            Self::Replay => [0x06, 0x00, 0x00, 0x00],
+            Self::Spectate => [0x07, 0x00, 0x00, 0x00],
+            Self::Register => [0x08, 0x00, 0x00, 0x00],
+            Self::Login => [0x09, 0x00, 0x00, 0x00],
         }
     }
 }
@@ -0,0 +1,188 @@
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    errors::Error,
+    lobby::Ruleset,
+    packet::{Packet, PacketKind},
+};
+
+/// Statistics of an account, built from the hands of every finished match it played.
+/// Rates are shares of the hands played, from 0 to 1.
+///
+/// The game does not play wins, riichi or calls yet, so every hand ends in an exhaustive draw.
+/// The rates built from them are None until one of the player's hands recorded such an outcome.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct PlayerStats {
+    pub player_id: i32,
+    pub matches: u32,
+    pub hands: u32,
+    pub win_rate: Option<f64>,
+    pub deal_in_rate: Option<f64>,
+    pub riichi_rate: Option<f64>,
+    /// Hands in which the player called at least one discard.
+    pub call_rate: Option<f64>,
+    /// 1 is first place. 0 until a match was finished.
+    pub average_placement: f64,
+    /// Points per won hand. None until a hand was won.
+    pub average_win_value: Option<f64>,
+}
+
+/// Asks for a player's statistics, answered with a Stats packet holding the encoded `PlayerStats`.
+///
+/// ```text
+/// | player id (i32)? |
+/// ```
+/// Without a player id the sender's own statistics are sent.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct StatsQuery {
+    pub player_id: Option<i32>,
+}
+
+impl StatsQuery {
+    pub fn parse(bytes: &[u8]) -> Result<Self, Error> {
+        match bytes.len() {
+            0 => Ok(Self { player_id: None }),
+            4 => Ok(Self {
+                player_id: Some(i32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]])),
+            }),
+            _ => Err(Error::PacketParsingFailed(108)),
+        }
+    }
+
+    pub fn bytes(&self) -> Vec<u8> {
+        return self
+            .player_id
+            .map_or(Vec::new(), |id| id.to_le_bytes().to_vec());
+    }
+
+    pub fn packet(&self, id: i32) -> Packet {
+        return Packet::create(id, PacketKind::Stats, &self.bytes());
+    }
+}
+
+/// What a leaderboard is sorted by. Ties go to the player with more games.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum Board {
+    /// Highest rating first.
+    Rating = 1,
+    /// Most ranked games first.
+    Games = 2,
+    /// Lowest average placement first, among players with enough games.
+    Placement = 3,
+}
+
+impl Board {
+    pub fn get(id: u8) -> Option<Self> {
+        match id {
+            1 => Some(Self::Rating),
+            2 => Some(Self::Games),
+            3 => Some(Self::Placement),
+            _ => None,
+        }
+    }
+}
+
+/// Asks for one page of a leaderboard, answered with a Leaderboard packet holding the encoded
+/// `LeaderboardPage`.
+///
+/// ```text
+/// | board (u8) | ruleset (u8, 0 for every ruleset) | season (u32, 0 for the current one) | page (u32) |
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LeaderboardQuery {
+    pub board: Board,
+    pub ruleset: Option<Ruleset>,
+    pub season: Option<u32>,
+    pub page: u32, // From 0.
+}
+
+impl LeaderboardQuery {
+    pub const SIZE: usize = 10;
+
+    pub fn parse(bytes: &[u8]) -> Result<Self, Error> {
+        let b = bytes
+            .get(..Self::SIZE)
+            .ok_or(Error::PacketParsingFailed(109))?;
+        let board = Board::get(b[0]).ok_or(Error::PacketParsingFailed(109))?;
+        let ruleset = match b[1] {
+            0 => None,
+            id => Some(Ruleset::get(id).ok_or(Error::OperationFailed(70))?),
+        };
+        let season = u32::from_le_bytes([b[2], b[3], b[4], b[5]]);
+        return Ok(Self {
+            board,
+            ruleset,
+            season: Some(season).filter(|season| *season != 0),
+            page: u32::from_le_bytes([b[6], b[7], b[8], b[9]]),
+        });
+    }
+
+    pub fn bytes(&self) -> Vec<u8> {
+        let mut bytes = vec![self.board as u8, self.ruleset.map_or(0, |r| r.id())];
+        bytes.extend(self.season.unwrap_or(0).to_le_bytes());
+        bytes.extend(self.page.to_le_bytes());
+        return bytes;
+    }
+
+    pub fn packet(&self, id: i32) -> Packet {
+        return Packet::create(id, PacketKind::Leaderboard, &self.bytes());
+    }
+}
+
+/// One page of a leaderboard. Seasons are numbered from 1.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct LeaderboardPage {
+    pub board: Board,
+    pub ruleset: Option<Ruleset>,
+    pub season: u32,
+    pub page: u32,
+    pub pages: u32,
+    pub entries: Vec<LeaderboardEntry>,
+}
+
+/// A player on a leaderboard, with their standing at the end of the season (now for the current one)
+/// and their ranked games of the season under the board's rules.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct LeaderboardEntry {
+    /// From 1.
+    pub position: u32,
+    pub player_id: i32,
+    pub username: String,
+    pub rating: i32,
+    pub rank: String,
+    pub games: u32,
+    pub average_placement: f64,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn round_trip() {
+        for query in [
+            StatsQuery { player_id: None },
+            StatsQuery {
+                player_id: Some(12),
+            },
+        ] {
+            let packet = query.packet(3);
+            assert_eq!(packet.kind, PacketKind::Stats);
+            assert_eq!(StatsQuery::parse(&packet.body).unwrap(), query);
+        }
+        assert!(StatsQuery::parse(&[1, 2]).is_err());
+
+        let query = LeaderboardQuery {
+            board: Board::Placement,
+            ruleset: Some(Ruleset::Sanma),
+            season: None,
+            page: 2,
+        };
+        let packet = query.packet(1);
+        assert_eq!(packet.kind, PacketKind::Leaderboard);
+        assert_eq!(LeaderboardQuery::parse(&packet.body).unwrap(), query);
+        assert!(LeaderboardQuery::parse(&[4, 0, 0, 0, 0, 0, 0, 0, 0, 0]).is_err());
+    }
+}
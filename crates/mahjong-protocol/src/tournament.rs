@@ -0,0 +1,253 @@
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    errors::Error,
+    lobby::Ruleset,
+    packet::{Packet, PacketKind},
+};
+
+/// Tournament requests of a connected player who has no seat yet.
+///
+/// ```text
+/// | op (u32) | payload |
+/// ```
+/// Ops: 1 list the tournaments, 2 create one (`| ruleset (u8) | rounds (u8) | name (utf-8) |`),
+/// 3 register (`| tournament id (u32) |`), 4 withdraw (`| tournament id (u32) |`),
+/// 5 start (`| tournament id (u32) |`), 6 show standings and seating (`| tournament id (u32) |`),
+/// 7 take the seat of the current round (`| tournament id (u32) |`),
+/// 8 swap two players' seats (`| tournament id (u32) | player id (i32) | player id (i32) |`),
+/// 9 forfeit a player's seat to a bot (`| tournament id (u32) | player id (i32) |`).
+/// Create, start, swap and forfeit are for admins only.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TournamentRequest {
+    List,
+    Create {
+        ruleset: Ruleset,
+        rounds: u8,
+        name: String,
+    },
+    Register(u32),
+    Withdraw(u32),
+    Start(u32),
+    Show(u32),
+    Play(u32),
+    Swap {
+        tournament: u32,
+        first: i32,
+        second: i32,
+    },
+    Forfeit {
+        tournament: u32,
+        player: i32,
+    },
+}
+
+impl TournamentRequest {
+    pub fn parse(bytes: &[u8]) -> Result<Self, Error> {
+        let op = bytes.get(..4).ok_or(Error::PacketParsingFailed(110))?;
+        let payload = &bytes[4..];
+        let int = |at: usize| {
+            let b = payload
+                .get(at..at + 4)
+                .ok_or(Error::PacketParsingFailed(110))?;
+            return Ok::<_, Error>([b[0], b[1], b[2], b[3]]);
+        };
+
+        match u32::from_le_bytes([op[0], op[1], op[2], op[3]]) {
+            1 => Ok(Self::List),
+            2 => {
+                let header = payload.get(..2).ok_or(Error::PacketParsingFailed(110))?;
+                let ruleset = Ruleset::get(header[0]).ok_or(Error::OperationFailed(70))?;
+                let name = String::from_utf8(payload[2..].to_vec())
+                    .map_err(|_| Error::PacketParsingFailed(110))?;
+                let name = name.trim().to_string();
+                if header[1] == 0 || name.is_empty() {
+                    return Err(Error::PacketParsingFailed(110));
+                }
+                Ok(Self::Create {
+                    ruleset,
+                    rounds: header[1],
+                    name,
+                })
+            }
+            3 => Ok(Self::Register(u32::from_le_bytes(int(0)?))),
+            4 => Ok(Self::Withdraw(u32::from_le_bytes(int(0)?))),
+            5 => Ok(Self::Start(u32::from_le_bytes(int(0)?))),
+            6 => Ok(Self::Show(u32::from_le_bytes(int(0)?))),
+            7 => Ok(Self::Play(u32::from_le_bytes(int(0)?))),
+            8 => Ok(Self::Swap {
+                tournament: u32::from_le_bytes(int(0)?),
+                first: i32::from_le_bytes(int(4)?),
+                second: i32::from_le_bytes(int(8)?),
+            }),
+            9 => Ok(Self::Forfeit {
+                tournament: u32::from_le_bytes(int(0)?),
+                player: i32::from_le_bytes(int(4)?),
+            }),
+            _ => Err(Error::PacketParsingFailed(110)),
+        }
+    }
+
+    pub fn op(&self) -> [u8; 4] {
+        match self {
+            Self::List => [0x01, 0x00, 0x00, 0x00],
+            Self::Create { .. } => [0x02, 0x00, 0x00, 0x00],
+            Self::Register(_) => [0x03, 0x00, 0x00, 0x00],
+            Self::Withdraw(_) => [0x04, 0x00, 0x00, 0x00],
+            Self::Start(_) => [0x05, 0x00, 0x00, 0x00],
+            Self::Show(_) => [0x06, 0x00, 0x00, 0x00],
+            Self::Play(_) => [0x07, 0x00, 0x00, 0x00],
+            Self::Swap { .. } => [0x08, 0x00, 0x00, 0x00],
+            Self::Forfeit { .. } => [0x09, 0x00, 0x00, 0x00],
+        }
+    }
+
+    pub fn bytes(&self) -> Vec<u8> {
+        let mut bytes = self.op().to_vec();
+        match self {
+            Self::List => {}
+            Self::Create {
+                ruleset,
+                rounds,
+                name,
+            } => {
+                bytes.extend([ruleset.id(), *rounds]);
+                bytes.extend(name.as_bytes());
+            }
+            Self::Register(id)
+            | Self::Withdraw(id)
+            | Self::Start(id)
+            | Self::Show(id)
+            | Self::Play(id) => bytes.extend(id.to_le_bytes()),
+            Self::Swap {
+                tournament,
+                first,
+                second,
+            } => {
+                bytes.extend(tournament.to_le_bytes());
+                bytes.extend(first.to_le_bytes());
+                bytes.extend(second.to_le_bytes());
+            }
+            Self::Forfeit { tournament, player } => {
+                bytes.extend(tournament.to_le_bytes());
+                bytes.extend(player.to_le_bytes());
+            }
+        }
+        return bytes;
+    }
+
+    pub fn packet(&self, id: i32) -> Packet {
+        return Packet::create(id, PacketKind::Tournament, &self.bytes());
+    }
+}
+
+// Tournament list answer: | op 1 (u32) | encoded Vec<TournamentInfo> |
+pub const TOURNAMENTS_OP: [u8; 4] = [0x01, 0x00, 0x00, 0x00];
+// Answer to create, register, withdraw, start, show, swap and forfeit: | op 2 (u32) | encoded TournamentView |
+pub const TOURNAMENT_OP: [u8; 4] = [0x02, 0x00, 0x00, 0x00];
+
+/// Where a tournament is at.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum Stage {
+    /// Open for registration until an admin starts it.
+    Registering,
+    /// Rounds are being played.
+    Playing,
+    /// Every round was played, the standings are final.
+    Finished,
+}
+
+/// A tournament as listed.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub struct TournamentInfo {
+    pub id: u32,
+    pub name: String,
+    /// Every table of every round is played under these rules.
+    pub ruleset: Ruleset,
+    pub rounds: u8,
+    /// Round being played, from 1. 0 before the start.
+    pub round: u8,
+    pub stage: Stage,
+    pub participants: usize,
+}
+
+/// A tournament's standings and the seating of its current round.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct TournamentView {
+    pub info: TournamentInfo,
+    /// Best first.
+    pub standings: Vec<TournamentStanding>,
+    pub tables: Vec<TournamentTable>,
+}
+
+/// A participant's cumulative result. Points are the final scores against the return score, in
+/// thousands, plus uma (and oka for first place) of every finished table.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct TournamentStanding {
+    /// From 1.
+    pub position: u32,
+    pub player_id: i32,
+    pub username: String,
+    pub points: f64,
+    /// Placement at every finished table, 1 being first.
+    pub placements: Vec<u8>,
+}
+
+/// A table of the current round.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub struct TournamentTable {
+    /// From 1.
+    pub number: u32,
+    /// Server table the round is played at, None until it is opened.
+    pub table_id: Option<i32>,
+    /// Participants seated here. Seats they leave over go to bots.
+    pub players: Vec<i32>,
+    /// Participants who already took their seat.
+    pub seated: Vec<i32>,
+    pub finished: bool,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn round_trip() {
+        for request in [
+            TournamentRequest::List,
+            TournamentRequest::Create {
+                ruleset: Ruleset::Hanchan,
+                rounds: 4,
+                name: "October cup".to_string(),
+            },
+            TournamentRequest::Register(3),
+            TournamentRequest::Withdraw(3),
+            TournamentRequest::Start(3),
+            TournamentRequest::Show(3),
+            TournamentRequest::Play(3),
+            TournamentRequest::Swap {
+                tournament: 3,
+                first: 7,
+                second: 12,
+            },
+            TournamentRequest::Forfeit {
+                tournament: 3,
+                player: 7,
+            },
+        ] {
+            let packet = request.packet(2);
+            assert_eq!(packet.kind, PacketKind::Tournament);
+            assert_eq!(TournamentRequest::parse(&packet.body).unwrap(), request);
+        }
+
+        assert!(TournamentRequest::parse(&[0x03, 0, 0, 0, 1]).is_err());
+        assert!(TournamentRequest::parse(&[0x02, 0, 0, 0, 1, 0, b'a']).is_err());
+        assert!(TournamentRequest::parse(&[0x02, 0, 0, 0, 1, 2, b' ']).is_err());
+        assert!(matches!(
+            TournamentRequest::parse(&[0x02, 0, 0, 0, 9, 2, b'a']),
+            Err(Error::OperationFailed(70))
+        ));
+    }
+}
@@ -0,0 +1,124 @@
+use crate::errors::Error;
+
+/// Newest wire format version spoken by the server.
+pub const PROTOCOL_VERSION: u16 = 4;
+/// Oldest wire format version the server still answers to.
+/// Only raised once every client had time to move past it (see the README's compatibility policy).
+pub const MIN_PROTOCOL_VERSION: u16 = 1;
+/// First version whose Reconnection carries a session token instead of a join request.
+/// Older clients can still connect but cannot take a seat back.
+pub const SESSION_TOKEN_VERSION: u16 = 2;
+/// First version whose Connection lands in the lobby instead of taking a seat right away.
+/// Older clients are seated at any open room, as if they had asked for quick play.
+pub const LOBBY_VERSION: u16 = 3;
+/// First version that can register and log in to accounts. Its Connection (guest) and account
+/// acknowledgements end with the player id the server assigned.
+pub const ACCOUNT_VERSION: u16 = 4;
+
+/// Optional protocol features, agreed on during the handshake.
+/// Flags the server does not know about are dropped from the answer, so a client
+/// must only rely on the flags it gets back.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Capabilities(pub u32);
+
+impl Capabilities {
+    /// Serialized payloads are JSON instead of CBOR.
+    pub const JSON_PAYLOADS: Capabilities = Capabilities(1 << 0);
+
+    /// Every flag this server is able to honor.
+    pub const SUPPORTED: Capabilities = Capabilities(Self::JSON_PAYLOADS.0);
+
+    pub fn contains(&self, other: Capabilities) -> bool {
+        self.0 & other.0 == other.0
+    }
+
+    pub fn intersection(&self, other: Capabilities) -> Capabilities {
+        Capabilities(self.0 & other.0)
+    }
+}
+
+/// Version and capabilities carried right after the Setup operation
+/// in Connection and Reconnection packets, and echoed back by the server.
+///
+/// ```text
+/// | version (u16) | capabilities (u32) |
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Handshake {
+    pub version: u16,
+    pub capabilities: Capabilities,
+}
+
+impl Handshake {
+    pub const SIZE: usize = 6;
+
+    pub fn parse(bytes: &[u8]) -> Result<Self, Error> {
+        let b = bytes.get(..Self::SIZE).ok_or(Error::ConnectionFailed(54))?;
+        Ok(Self {
+            version: u16::from_le_bytes([b[0], b[1]]),
+            capabilities: Capabilities(u32::from_le_bytes([b[2], b[3], b[4], b[5]])),
+        })
+    }
+
+    // Picks the highest version both sides speak and the capabilities both sides know.
+    // Clients newer than the server are answered with the server's version and may downgrade.
+    pub fn negotiate(&self) -> Result<Handshake, Error> {
+        if self.version < MIN_PROTOCOL_VERSION {
+            return Err(Error::VersionUnsupported(
+                self.version,
+                MIN_PROTOCOL_VERSION,
+                PROTOCOL_VERSION,
+            ));
+        }
+
+        Ok(Handshake {
+            version: self.version.min(PROTOCOL_VERSION),
+            capabilities: self.capabilities.intersection(Capabilities::SUPPORTED),
+        })
+    }
+
+    pub fn bytes(&self) -> [u8; Self::SIZE] {
+        let version = self.version.to_le_bytes();
+        let capabilities = self.capabilities.0.to_le_bytes();
+        [
+            version[0],
+            version[1],
+            capabilities[0],
+            capabilities[1],
+            capabilities[2],
+            capabilities[3],
+        ]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn negotiate() {
+        let newer = Handshake {
+            version: PROTOCOL_VERSION + 1,
+            capabilities: Capabilities(u32::MAX),
+        };
+        let agreed = Handshake::parse(&newer.bytes())
+            .unwrap()
+            .negotiate()
+            .unwrap();
+        assert_eq!(agreed.version, PROTOCOL_VERSION);
+        assert_eq!(agreed.capabilities, Capabilities::SUPPORTED);
+
+        let older = Handshake {
+            version: MIN_PROTOCOL_VERSION - 1,
+            capabilities: Capabilities::default(),
+        };
+        assert!(matches!(
+            older.negotiate(),
+            Err(Error::VersionUnsupported(
+                _,
+                MIN_PROTOCOL_VERSION,
+                PROTOCOL_VERSION
+            ))
+        ));
+    }
+}
@@ -9,13 +9,20 @@ pub struct GameAction {
 }
 
 impl GameAction {
+    // Bodies can be empty, so the opcode and the discarded tile are checked before they are read.
     pub fn parse(b: &Box<[u8]>) -> Result<GameAction, Error> {
-        match Action::get(b[0]) {
+        let Some(opcode) = b.first() else {
+            return Err(Error::GameActionParsingFailed(111));
+        };
+        match Action::get(*opcode) {
             None => return Err(Error::GameActionParsingFailed(1)),
             Some(action) => {
                 return Ok(GameAction {
                     target: match action {
-                        Action::DISCARD => Some(Tile::from_bytes(b[1], b[2])?),
+                        Action::DISCARD => match b.get(1..3) {
+                            Some(tile) => Some(Tile::from_bytes(tile[0], tile[1])?),
+                            None => return Err(Error::GameActionParsingFailed(111)),
+                        },
                         _ => None,
                     },
                     action,
@@ -24,3 +31,38 @@ impl GameAction {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use mahjong_protocol::{
+        decoder::PacketDecoder,
+        packet::{Packet, PacketKind, ToBytes},
+    };
+
+    use super::*;
+
+    fn decode(body: &[u8]) -> Box<[u8]> {
+        let mut decoder = PacketDecoder::default();
+        decoder.extend(&Packet::create(3, PacketKind::Action, body).to_bytes());
+        return decoder.next_packet().unwrap().unwrap().body;
+    }
+
+    #[test]
+    fn empty_body() {
+        assert!(matches!(
+            GameAction::parse(&decode(&[])),
+            Err(Error::GameActionParsingFailed(111))
+        ));
+    }
+
+    #[test]
+    fn short_discard() {
+        let discard = Action::DISCARD.bytes()[0];
+        for body in [vec![discard], vec![discard, 0x00]] {
+            assert!(matches!(
+                GameAction::parse(&decode(&body)),
+                Err(Error::GameActionParsingFailed(111))
+            ));
+        }
+    }
+}
@@ -1,25 +1,69 @@
 use std::{collections::HashMap, sync::Arc};
-use tokio::sync::RwLock;
+use tokio::{sync::RwLock, time::Instant};
 
-use crate::game::{
-    enums::{Seat, Tile, TileKind},
-    player::Player,
+use crate::{
+    game::{
+        enums::{Seat, Tile, TileKind},
+        player::Player,
+    },
+    protocol::lobby::Ruleset,
 };
 
 pub struct GameState {
+    pub seed: u64, // The wall is shuffled from it, so a match can be dealt again.
     pub turn: Arc<RwLock<i32>>,
     pub wall: Arc<RwLock<Vec<Arc<Tile>>>>,
     pub last_discard: Arc<RwLock<Option<TileKind>>>,
+    pub dora_indicators: Arc<RwLock<Vec<Arc<Tile>>>>,
+    pub turn_started: Arc<RwLock<Option<Instant>>>,
     pub player_pool: Arc<RwLock<HashMap<Seat, Arc<Player>>>>,
 }
 
 impl GameState {
-    pub fn start_game() -> Self {
+    pub fn start_game(seed: u64, ruleset: Ruleset) -> Self {
         Self {
+            seed,
             turn: Arc::new(RwLock::new(0)),
-            wall: Arc::new(RwLock::new(Vec::new())),
+            wall: Arc::new(RwLock::new(build_wall(seed, ruleset))),
             last_discard: Arc::new(RwLock::new(None)),
+            dora_indicators: Arc::new(RwLock::new(Vec::new())),
+            turn_started: Arc::new(RwLock::new(None)),
             player_pool: Arc::new(RwLock::new(HashMap::new())),
         }
     }
 }
+
+// Four copies of every tile, shuffled with the seed. Three player rules play without 2m to 8m.
+pub fn build_wall(seed: u64, ruleset: Ruleset) -> Vec<Arc<Tile>> {
+    let mut wall: Vec<Arc<Tile>> = (11..=47)
+        .filter_map(TileKind::parse)
+        .filter(|kind| ruleset != Ruleset::Sanma || !(32..=38).contains(&(*kind as i8)))
+        .flat_map(|kind| (0..4).map(move |copy| Arc::new(Tile { copy, kind })))
+        .collect();
+
+    // Fisher-Yates over a splitmix64 stream, so the same seed always gives the same wall.
+    let mut state = seed;
+    for i in (1..wall.len()).rev() {
+        state = state.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^= z >> 31;
+        wall.swap(i, (z % (i as u64 + 1)) as usize);
+    }
+    return wall;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn seeded_wall() {
+        let kinds = |wall: Vec<Arc<Tile>>| wall.iter().map(|tile| tile.kind).collect::<Vec<_>>();
+        assert_eq!(build_wall(7, Ruleset::Hanchan).len(), 136);
+        assert_eq!(build_wall(7, Ruleset::Sanma).len(), 108);
+        assert!(kinds(build_wall(7, Ruleset::Hanchan)) == kinds(build_wall(7, Ruleset::Hanchan)));
+        assert!(kinds(build_wall(7, Ruleset::Hanchan)) != kinds(build_wall(8, Ruleset::Hanchan)));
+    }
+}
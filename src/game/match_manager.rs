@@ -4,16 +4,21 @@ use crate::{
         game_action::GameAction,
         game_state::GameState,
         lua_manager::LuaManager,
+        paifu::{Paifu, PaifuEvent},
         player::Player,
     },
+    protocol::lobby::Ruleset,
     utils::{
         errors::Error,
-        models::{JoinRequest, MeldFlags},
+        models::{HandResult, JoinRequest, MeldFlags, ResyncSnapshot},
     },
 };
 use lolg::Lolg;
 use std::{collections::HashMap, fmt::Display, sync::Arc};
-use tokio::sync::{RwLock, watch};
+use tokio::{
+    sync::{RwLock, watch},
+    time::Instant,
+};
 
 // RULES FOR THIS MANAGER
 // - IT SHOULD NOT HAVE TO CREATE ANY PACKETS AS IT HAS NO DIRECT ACCESS TO PROTOCOL
@@ -22,8 +27,11 @@ pub struct MatchManager {
     match_id: String,
     logger: Arc<Lolg>,
     lua: Arc<LuaManager>,
+    pub ruleset: Ruleset,
     pub state: Arc<GameState>,
+    pub paifu: Arc<Paifu>, // Every change made to the state, written once the table is closed.
     current_turn: Arc<RwLock<Seat>>,
+    pending_calls: Arc<RwLock<HashMap<i32, MeldFlags>>>, // Calls offered on the last discard, by player id.
     pub status: Arc<RwLock<MatchStatus>>,
     sttx: Arc<watch::Sender<MatchStatus>>,
 }
@@ -31,17 +39,30 @@ pub struct MatchManager {
 impl MatchManager {
     pub async fn next_turn(&self) -> Result<Arc<Player>, Error> {
         let mut guard = self.current_turn.write().await;
-        let next_seat = match *guard {
-            Seat::East => Seat::North,
-            Seat::North => Seat::West,
-            Seat::West => Seat::South,
-            Seat::South => Seat::East,
-        };
+        let mut next_seat = *guard;
+        // Seats the ruleset leaves empty are skipped.
+        loop {
+            next_seat = match next_seat {
+                Seat::East => Seat::North,
+                Seat::North => Seat::West,
+                Seat::West => Seat::South,
+                Seat::South => Seat::East,
+            };
+            if self.ruleset.seats().contains(&next_seat) {
+                break;
+            }
+        }
 
         *guard = next_seat;
         if let Some(player) = self.state.player_pool.read().await.get(&next_seat) {
             let mut turn_guard = self.state.turn.write().await;
             *turn_guard += 1;
+            *self.state.turn_started.write().await = Some(Instant::now());
+            let turn = PaifuEvent::Turn {
+                turn: *turn_guard,
+                seat: next_seat,
+            };
+            self.paifu.record(turn).await;
             return Ok(Arc::clone(player));
         }
         return Err(Error::NextPlayerFailed);
@@ -66,6 +87,13 @@ impl MatchManager {
         let tile_clone = Arc::clone(&tile);
 
         hand.push(tile);
+        // Drawing means nobody called the last discard.
+        self.pending_calls.write().await.clear();
+        let drew = PaifuEvent::Drew {
+            player_id: player.id,
+            tile: *tile_clone,
+        };
+        self.paifu.record(drew).await;
         return Ok(tile_clone);
     }
 
@@ -75,13 +103,20 @@ impl MatchManager {
         }
 
         let tile = action.target.ok_or(Error::TileParsingFailed)?;
-        match player.discard_tile(&tile).await {
-            true => return Ok(tile),
-            false => return Err(Error::DiscardFailed(164)),
+        if !player.discard_tile(&tile).await {
+            return Err(Error::DiscardFailed(164));
+        }
+        let discarded = PaifuEvent::Discarded {
+            player_id: player.id,
+            tile,
         };
+        self.paifu.record(discarded).await;
+        return Ok(tile);
     }
 
-    pub async fn check_calls(&self, tile: Tile) -> Result<HashMap<i32, Vec<u8>>, Error> {
+    // Returns the calls available to each player for the discarded tile.
+    // Encoding is left to the protocol since every client may use a different codec.
+    pub async fn check_calls(&self, tile: Tile) -> Result<HashMap<i32, MeldFlags>, Error> {
         let state = self
             .lua
             .lua
@@ -93,8 +128,11 @@ impl MatchManager {
             .set("tile", tile.kind as i8)
             .map_err(|_| Error::InternalError)?;
 
-        for seat in [Seat::North, Seat::South, Seat::East, Seat::West] {
-            let player = players.get(&seat).unwrap();
+        // Seats nobody took yet have no hand to call with.
+        for seat in self.ruleset.seats() {
+            let Some(player) = players.get(seat) else {
+                continue;
+            };
             let hand = player.get_hand().await;
             let table = self.lua.vec_to_luatable(&hand)?;
             state
@@ -103,40 +141,110 @@ impl MatchManager {
         }
 
         let check_result = self.lua.check_calls(state).await?;
-        let mut flags: HashMap<i32, Vec<u8>> = HashMap::new();
-        for seat in [Seat::North, Seat::South, Seat::East, Seat::West] {
+        let mut flags: HashMap<i32, MeldFlags> = HashMap::new();
+        for seat in self.ruleset.seats() {
             let melds: mlua::Table = check_result
                 .get(seat.to_string())
                 .map_err(|_| Error::InternalError)?;
-            let player = players.get(&seat).unwrap();
-            let meld_flags = MeldFlags::create(player.id, melds)?;
-            let meld_bytes = serde_cbor::to_vec(&meld_flags).map_err(|_| Error::InternalError)?;
-            flags.insert(player.id, meld_bytes);
+            let Some(player) = players.get(seat) else {
+                continue;
+            };
+            let meld_flags = meld_flags(player.id, melds)?;
+            flags.insert(player.id, meld_flags);
         }
 
+        *self.pending_calls.write().await = flags.clone();
+        let calls = flags
+            .values()
+            .filter(|flags| flags.chi || flags.pon || flags.kan || flags.ron)
+            .cloned()
+            .collect();
+        self.paifu
+            .record(PaifuEvent::CallsOffered { tile, calls })
+            .await;
         return Ok(flags);
     }
+
+    // Records how a hand ended.
+    pub async fn end_hand(&self, result: &HandResult) {
+        let ended = PaifuEvent::HandEnded {
+            result: result.clone(),
+        };
+        self.paifu.record(ended).await;
+    }
+
+    // Table state as the given player is allowed to see it, sent to them after a reconnection.
+    pub async fn snapshot(&self, player: &Player, last_sequence: i32) -> ResyncSnapshot {
+        let players = self.state.player_pool.read().await;
+        let mut seats = Vec::new();
+        for seat in [Seat::East, Seat::South, Seat::West, Seat::North] {
+            if let Some(seated) = players.get(&seat) {
+                seats.push(seated.seat_view().await);
+            }
+        }
+
+        let turn_started = *self.state.turn_started.read().await;
+        let dora_indicators = self.state.dora_indicators.read().await;
+        ResyncSnapshot {
+            seat: *player.seat.read().await,
+            hand: player.hand.read().await.iter().map(|tile| **tile).collect(),
+            seats,
+            dora_indicators: dora_indicators.iter().map(|tile| **tile).collect(),
+            turn: *self.state.turn.read().await,
+            current_seat: *self.current_turn.read().await,
+            pending_calls: self.pending_calls.read().await.get(&player.id).cloned(),
+            turn_elapsed_ms: turn_started.map(|started| started.elapsed().as_millis() as u64),
+            last_sequence,
+        }
+    }
+}
+
+// Reads the flags returned by the `check_calls` script for one player.
+fn meld_flags(pid: i32, flags: mlua::Table) -> Result<MeldFlags, Error> {
+    Ok(MeldFlags {
+        pid,
+        chi: flags.get("chi").map_err(|_| Error::InternalError)?,
+        pon: flags.get("pon").map_err(|_| Error::InternalError)?,
+        kan: flags.get("kan").map_err(|_| Error::InternalError)?,
+        ron: flags.get("ron").map_err(|_| Error::InternalError)?,
+    })
 }
 
 impl MatchManager {
     pub async fn new(
         log_manager: Arc<Lolg>,
         sender: watch::Sender<MatchStatus>,
+        ruleset: Ruleset,
     ) -> Result<Self, Error> {
+        let mut seed = [0; 8];
+        getrandom::fill(&mut seed).map_err(|_| Error::InternalError)?;
+        let seed = u64::from_le_bytes(seed);
+        let state = GameState::start_game(seed, ruleset);
+        let paifu = Paifu::new();
+        let wall = state.wall.read().await.iter().map(|tile| **tile).collect();
+        let shuffled = PaifuEvent::Shuffled {
+            seed,
+            ruleset,
+            wall,
+        };
+        paifu.record(shuffled).await;
         Ok(Self {
+            ruleset,
             logger: log_manager,
             sttx: Arc::new(sender),
             match_id: String::new(),
             lua: Arc::new(LuaManager::new().await?),
-            state: Arc::new(GameState::start_game()),
+            state: Arc::new(state),
+            paifu: Arc::new(paifu),
             current_turn: Arc::new(RwLock::new(Seat::East)),
+            pending_calls: Arc::new(RwLock::new(HashMap::new())),
             status: Arc::new(RwLock::new(MatchStatus::Waiting)),
         })
     }
 
     async fn check_seats(&self) -> Result<(), Error> {
         let player_pool = self.state.player_pool.read().await;
-        if player_pool.len() != 4 {
+        if player_pool.len() != self.ruleset.seats().len() {
             return Err(Error::MatchStartFailed(151));
         }
 
@@ -156,13 +264,16 @@ impl MatchManager {
             .then(|| 0)
             .ok_or(Error::MatchStartFailed(153))?;
 
-        player_pool
-            .get(&Seat::North)
-            .ok_or(Error::MatchStartFailed(154))?
-            .check_ready()
-            .await
-            .then(|| 0)
-            .ok_or(Error::MatchStartFailed(154))?;
+        // Three player rules leave north empty.
+        if self.ruleset.seats().contains(&Seat::North) {
+            player_pool
+                .get(&Seat::North)
+                .ok_or(Error::MatchStartFailed(154))?
+                .check_ready()
+                .await
+                .then(|| 0)
+                .ok_or(Error::MatchStartFailed(154))?;
+        }
 
         player_pool
             .get(&Seat::South)
@@ -194,19 +305,63 @@ impl MatchManager {
         *status_guard = status;
     }
 
-    pub async fn get_free_seat(&self) -> Option<Seat> {
-        let player_pool_guard = self.state.player_pool.read().await;
-        return if player_pool_guard.get(&Seat::East).is_none() {
-            Some(Seat::East)
-        } else if player_pool_guard.get(&Seat::North).is_none() {
-            Some(Seat::North)
-        } else if player_pool_guard.get(&Seat::West).is_none() {
-            Some(Seat::West)
-        } else if player_pool_guard.get(&Seat::South).is_none() {
-            Some(Seat::South)
-        } else {
-            None
-        };
+    // Starts the match once every seat is taken and ready. East plays the first turn.
+    // False when it was already started or is not ready yet.
+    pub async fn start(&self) -> bool {
+        let mut status = self.status.write().await;
+        if *status != MatchStatus::Waiting || !self.check_ready().await {
+            return false;
+        }
+        let _ = self.sttx.send(MatchStatus::Ongoing);
+        *status = MatchStatus::Ongoing;
+        drop(status);
+
+        // The far end of the wall from the draws holds the dora indicator.
+        let mut wall = self.state.wall.write().await;
+        if !wall.is_empty() {
+            let indicator = wall.remove(0);
+            let revealed = PaifuEvent::DoraRevealed { tile: *indicator };
+            self.state.dora_indicators.write().await.push(indicator);
+            self.paifu.record(revealed).await;
+        }
+        drop(wall);
+
+        let seat = *self.current_turn.read().await;
+        *self.state.turn.write().await = 1;
+        *self.state.turn_started.write().await = Some(Instant::now());
+        self.paifu.record(PaifuEvent::Turn { turn: 1, seat }).await;
+        return true;
+    }
+
+    // The player whose turn it is.
+    pub async fn current_player(&self) -> Option<Arc<Player>> {
+        let seat = *self.current_turn.read().await;
+        return self.state.player_pool.read().await.get(&seat).cloned();
+    }
+
+    pub async fn finish(&self) {
+        self.change_status(MatchStatus::Finished).await;
+    }
+
+    // Stops a match nobody is left to play. Matches that are already over are left as they are.
+    pub async fn interrupt(&self) {
+        let status = *self.status.read().await;
+        if matches!(status, MatchStatus::Waiting | MatchStatus::Ongoing) {
+            self.change_status(MatchStatus::Interrupted).await;
+        }
+    }
+
+    fn free_seat(&self, seated: &HashMap<Seat, Arc<Player>>) -> Option<Seat> {
+        return [Seat::East, Seat::North, Seat::West, Seat::South]
+            .into_iter()
+            .filter(|seat| self.ruleset.seats().contains(seat))
+            .find(|seat| seated.get(seat).is_none());
+    }
+
+    // Seats taken and seats the ruleset plays with, as shown in the lobby.
+    pub async fn occupancy(&self) -> (usize, usize) {
+        let seated = self.state.player_pool.read().await.len();
+        return (seated, self.ruleset.seats().len());
     }
 
     pub async fn get_initial_hand(&self) -> Vec<Arc<Tile>> {
@@ -216,13 +371,26 @@ impl MatchManager {
         wall_guard.drain(drain_start..).collect()
     }
 
+    // A player (an account logged in twice) only ever takes one seat at the table.
+    // The pool stays locked from the duplicate check to the insert, so concurrent joins
+    // can neither pick the same seat nor seat the same account twice.
     pub async fn assign_player(&self, req: &JoinRequest) -> Result<Arc<Player>, Error> {
-        match self.get_free_seat().await {
+        let mut player_pool_guard = self.state.player_pool.write().await;
+        if player_pool_guard.values().any(|player| player.id == req.id) {
+            return Err(Error::ConnectionFailed(77));
+        }
+
+        match self.free_seat(&player_pool_guard) {
             None => Err(Error::NoAvailableSeats),
             Some(seat) => {
                 let hand = self.get_initial_hand().await;
+                let dealt = PaifuEvent::Dealt {
+                    player_id: req.id,
+                    seat,
+                    hand: hand.iter().map(|tile| **tile).collect(),
+                };
+                self.paifu.record(dealt).await;
                 let player = Arc::new(Player::new(seat.clone(), &req, hand));
-                let mut player_pool_guard = self.state.player_pool.write().await;
                 player_pool_guard.insert(seat, player.clone());
                 return Ok(player);
             }
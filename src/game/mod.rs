@@ -3,4 +3,5 @@ pub mod game_action;
 pub mod game_state;
 pub mod lua_manager;
 pub mod match_manager;
+pub mod paifu;
 pub mod player;
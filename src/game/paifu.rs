@@ -0,0 +1,220 @@
+use serde::{Deserialize, Serialize};
+use std::{
+    path::Path,
+    sync::Arc,
+    time::{SystemTime, UNIX_EPOCH},
+};
+use tokio::{sync::RwLock, time::Instant};
+
+use crate::{
+    game::enums::{Seat, Tile},
+    protocol::lobby::Ruleset,
+    utils::{
+        errors::Error,
+        models::{HandResult, MeldFlags},
+    },
+};
+
+/// Something that changed the game state, in the order it happened. The wall order and the
+/// starting hands are enough to deal a match again; the rest replays it move by move.
+/// Riichi, wins and payments are not recorded: the game does not play them yet.
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum PaifuEvent {
+    /// The shuffled wall. Hands are dealt and tiles drawn from its end.
+    Shuffled {
+        seed: u64,
+        ruleset: Ruleset,
+        wall: Vec<Tile>,
+    },
+    Dealt {
+        player_id: i32,
+        seat: Seat,
+        hand: Vec<Tile>,
+    },
+    /// The first tile of the wall, turned over when the match starts. It is never drawn.
+    DoraRevealed {
+        tile: Tile,
+    },
+    Turn {
+        turn: i32,
+        seat: Seat,
+    },
+    Drew {
+        player_id: i32,
+        tile: Tile,
+    },
+    Discarded {
+        player_id: i32,
+        tile: Tile,
+    },
+    /// Calls the players may make on the last discard. Players with none are left out.
+    CallsOffered {
+        tile: Tile,
+        calls: Vec<MeldFlags>,
+    },
+    /// Always an exhaustive draw until wins are played.
+    HandEnded {
+        result: HandResult,
+    },
+}
+
+/// A recorded event with its position in the log and when it happened, in milliseconds after
+/// the log was started.
+#[derive(Serialize, Deserialize, Clone)]
+pub struct PaifuEntry {
+    pub seq: u32,
+    pub at_ms: u64,
+    pub event: PaifuEvent,
+}
+
+/// The paifu file of a match: when it started (unix milliseconds) and every entry.
+#[derive(Serialize, Deserialize)]
+pub struct PaifuDocument {
+    pub table_id: i32,
+    pub started: u64,
+    pub entries: Vec<PaifuEntry>,
+}
+
+/// The full record of a match, kept by its `MatchManager` as it changes the game state and
+/// written to `paifu_dir` once its table is closed.
+pub struct Paifu {
+    started: u64,
+    clock: Instant,
+    entries: Arc<RwLock<Vec<PaifuEntry>>>,
+}
+
+impl Paifu {
+    pub fn new() -> Self {
+        let started = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |elapsed| elapsed.as_millis() as u64);
+        Self {
+            started,
+            clock: Instant::now(),
+            entries: Arc::new(RwLock::new(Vec::new())),
+        }
+    }
+
+    pub async fn record(&self, event: PaifuEvent) {
+        let mut entries = self.entries.write().await;
+        let entry = PaifuEntry {
+            seq: entries.len() as u32 + 1,
+            at_ms: self.clock.elapsed().as_millis() as u64,
+            event,
+        };
+        entries.push(entry);
+    }
+
+    pub async fn entries(&self) -> Vec<PaifuEntry> {
+        return self.entries.read().await.clone();
+    }
+
+    // Writes the paifu to `<dir>/<table id>-<started>.json`.
+    pub async fn write(&self, dir: &str, table_id: i32) -> Result<(), Error> {
+        let document = PaifuDocument {
+            table_id,
+            started: self.started,
+            entries: self.entries().await,
+        };
+        let path = Path::new(dir).join(format!("{table_id}-{}.json", self.started));
+        let json =
+            serde_json::to_string_pretty(&document).map_err(|_| Error::SerializationFailed(17))?;
+        std::fs::create_dir_all(dir).map_err(|_| Error::SerializationFailed(17))?;
+        std::fs::write(path, json).map_err(|_| Error::SerializationFailed(17))?;
+        return Ok(());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::{enums::TileKind, game_state::build_wall};
+    use std::collections::HashMap;
+
+    // Deals the hands and plays every draw and discard of a paifu again on the wall its seed gives.
+    fn replay(entries: &[PaifuEntry]) -> HashMap<i32, Vec<TileKind>> {
+        let PaifuEvent::Shuffled { seed, ruleset, .. } = &entries[0].event else {
+            panic!("a paifu starts with the wall");
+        };
+        let mut wall = build_wall(*seed, *ruleset);
+        let mut hands: HashMap<i32, Vec<TileKind>> = HashMap::new();
+        for entry in entries {
+            match &entry.event {
+                PaifuEvent::Dealt { player_id, .. } => {
+                    let hand = wall.drain(wall.len() - 13..).map(|tile| tile.kind);
+                    hands.insert(*player_id, hand.collect());
+                }
+                PaifuEvent::DoraRevealed { .. } => {
+                    wall.remove(0);
+                }
+                PaifuEvent::Drew { player_id, .. } => {
+                    let tile = wall.pop().unwrap();
+                    hands.get_mut(player_id).unwrap().push(tile.kind);
+                }
+                PaifuEvent::Discarded { player_id, tile } => {
+                    let hand = hands.get_mut(player_id).unwrap();
+                    let at = hand.iter().position(|kind| *kind == tile.kind).unwrap();
+                    hand.remove(at);
+                }
+                _ => {}
+            }
+        }
+        return hands;
+    }
+
+    #[tokio::test]
+    async fn replays_a_hand() {
+        let (seed, ruleset) = (42, Ruleset::Sanma);
+        let tiles = |wall: &[Arc<Tile>]| wall.iter().map(|tile| **tile).collect::<Vec<_>>();
+        let mut wall = build_wall(seed, ruleset);
+        let paifu = Paifu::new();
+        let shuffled = PaifuEvent::Shuffled {
+            seed,
+            ruleset,
+            wall: tiles(&wall),
+        };
+        paifu.record(shuffled).await;
+        let mut hands = HashMap::new();
+        for (player_id, seat) in [(1, Seat::East), (2, Seat::South), (3, Seat::West)] {
+            let hand = tiles(&wall.split_off(wall.len() - 13));
+            hands.insert(player_id, hand.clone());
+            let dealt = PaifuEvent::Dealt {
+                player_id,
+                seat,
+                hand,
+            };
+            paifu.record(dealt).await;
+        }
+
+        let indicator = *wall.remove(0);
+        paifu
+            .record(PaifuEvent::DoraRevealed { tile: indicator })
+            .await;
+
+        // East draws, then lets go of the first tile they were dealt.
+        let drawn = *wall.pop().unwrap();
+        let discarded = hands[&1][0];
+        paifu
+            .record(PaifuEvent::Drew {
+                player_id: 1,
+                tile: drawn,
+            })
+            .await;
+        let discard = PaifuEvent::Discarded {
+            player_id: 1,
+            tile: discarded,
+        };
+        paifu.record(discard).await;
+
+        let json = serde_json::to_string(&paifu.entries().await).unwrap();
+        let entries: Vec<PaifuEntry> = serde_json::from_str(&json).unwrap();
+        assert!(entries.iter().map(|entry| entry.seq).eq(1..=7));
+        let kinds = |hand: &[Tile]| hand.iter().map(|tile| tile.kind).collect::<Vec<_>>();
+        let mut east = kinds(&hands[&1][1..]);
+        east.push(drawn.kind);
+        let replayed = replay(&entries);
+        assert!(replayed[&1] == east);
+        assert!(replayed[&2] == kinds(&hands[&2]) && replayed[&3] == kinds(&hands[&3]));
+    }
+}
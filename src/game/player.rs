@@ -1,13 +1,18 @@
-use std::sync::Arc;
+use std::{sync::Arc, time::Duration};
 
-use serde::{Deserialize, Serialize};
 use tokio::sync::RwLock;
 
 use crate::{
     game::enums::{PlayerStatus, Seat, Tile},
-    utils::{errors::Error, models::JoinRequest},
+    protocol::codec::Codec,
+    utils::{
+        errors::Error,
+        models::{InitialPlayerView, JoinRequest, Meld, SeatView},
+    },
 };
 
+pub const STARTING_SCORE: i32 = 25_000;
+
 pub struct Player {
     pub id: i32,
     pub alias: Arc<RwLock<String>>,
@@ -16,6 +21,9 @@ pub struct Player {
     pub seat: Arc<RwLock<Seat>>,
     pub hand: Arc<RwLock<Vec<Arc<Tile>>>>,
     pub discarded: Arc<RwLock<Vec<Arc<Tile>>>>,
+    pub melds: Arc<RwLock<Vec<Meld>>>,
+    pub score: Arc<RwLock<i32>>,
+    pub rtt: Arc<RwLock<Option<Duration>>>, // Round trip of the last ping the client answered, None while disconnected.
     pub player_state: Arc<RwLock<PlayerStatus>>,
 }
 
@@ -29,6 +37,9 @@ impl Player {
             player_state: Arc::new(RwLock::new(PlayerStatus::WAITING)),
             hand: Arc::new(RwLock::new(hand)),
             discarded: Arc::new(RwLock::new(Vec::new())),
+            melds: Arc::new(RwLock::new(Vec::new())),
+            score: Arc::new(RwLock::new(STARTING_SCORE)),
+            rtt: Arc::new(RwLock::new(None)),
         }
     }
 
@@ -51,9 +62,47 @@ impl Player {
         vec
     }
 
-    pub async fn get_initial_view(&self) -> Result<Vec<u8>, Error> {
-        let view = InitialPlayerView::get(&self).await;
-        serde_cbor::to_vec(&view).map_err(|_| Error::SerializationFailed(10))
+    pub async fn get_initial_view(&self, codec: Codec) -> Result<Vec<u8>, Error> {
+        let view = self.initial_view().await;
+        codec
+            .encode(&view)
+            .map_err(|_| Error::SerializationFailed(10))
+    }
+
+    pub async fn initial_view(&self) -> InitialPlayerView {
+        let hand = self.hand.read().await.to_owned();
+        let seat = self.seat.read().await.to_owned();
+        InitialPlayerView {
+            is_first: seat == Seat::East,
+            seat,
+            hand,
+        }
+    }
+
+    // What the other players can see of this seat.
+    pub async fn seat_view(&self) -> SeatView {
+        SeatView {
+            player_id: self.id,
+            seat: *self.seat.read().await,
+            alias: self.alias.read().await.clone(),
+            connected: *self.connected.read().await,
+            score: *self.score.read().await,
+            latency_ms: self.rtt.read().await.map(|rtt| rtt.as_millis() as u32),
+            hand_size: self.hand.read().await.len(),
+            pond: self
+                .discarded
+                .read()
+                .await
+                .iter()
+                .map(|tile| **tile)
+                .collect(),
+            melds: self.melds.read().await.clone(),
+        }
+    }
+
+    // Bots are seated with negative ids.
+    pub fn is_bot(&self) -> bool {
+        return self.id < 0;
     }
 
     pub async fn check_ready(&self) -> bool {
@@ -68,22 +117,3 @@ impl Player {
         *self.player_state.write().await = PlayerStatus::WAITING;
     }
 }
-
-#[derive(Deserialize, Serialize)]
-pub struct InitialPlayerView {
-    pub seat: Seat,
-    pub is_first: bool,
-    pub hand: Vec<Arc<Tile>>,
-}
-
-impl InitialPlayerView {
-    pub async fn get(p: &Player) -> Self {
-        let hand = p.hand.read().await.to_owned();
-        let seat = p.seat.read().await.to_owned();
-        InitialPlayerView {
-            is_first: seat == Seat::East,
-            seat,
-            hand,
-        }
-    }
-}
@@ -1,4 +1,11 @@
-use crate::{network::server::Server, utils::errors::Error};
+use crate::{
+    network::server::Server,
+    protocol::schema::{self, SCHEMA_PATH},
+    utils::{
+        config::{CONFIG_PATH, Config},
+        errors::Error,
+    },
+};
 
 mod game;
 mod network;
@@ -7,7 +14,15 @@ mod utils;
 
 #[tokio::main]
 async fn main() -> Result<(), Error> {
-    let server = Server::create_instance(3000).await?;
+    // `--export-schema [path]` writes the protocol catalog for client code generation and exits.
+    let args: Vec<String> = std::env::args().collect();
+    if args.get(1).map(String::as_str) == Some("--export-schema") {
+        let path = args.get(2).map(String::as_str).unwrap_or(SCHEMA_PATH);
+        return schema::write(path);
+    }
+
+    let config = Config::load(CONFIG_PATH)?;
+    let server = Server::create_instance(config).await?;
     server.start().await;
 
     Ok(())
@@ -0,0 +1,372 @@
+use crate::network::ranking::Standing;
+use crate::protocol::account::Credentials;
+use crate::utils::errors::Error;
+use argon2::Argon2;
+use argon2::password_hash::{PasswordHash, PasswordHasher, PasswordVerifier, SaltString};
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+use std::sync::Arc;
+use tokio::sync::{Mutex, RwLock};
+
+/// Guests get ids from here up, so they never collide with accounts (numbered from 1).
+pub const GUEST_ID_BASE: i32 = 1 << 30;
+
+/// A registered player. The password is only kept as an Argon2 hash (PHC string).
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Account {
+    pub id: i32,
+    pub username: String,
+    hash: String,
+    pub created: u64, // Seconds since the Unix epoch.
+    #[serde(default)]
+    pub standing: Standing, // Rating and rank, moved by ranked matches only.
+}
+
+// What the accounts file holds.
+#[derive(Serialize, Deserialize)]
+struct AccountFile {
+    next_id: i32,
+    accounts: Vec<Account>,
+    #[serde(default)]
+    season: Option<u32>, // Season the standings belong to, None until the first rollover check.
+}
+
+/// An account's standing at the end of a season, as archived at the rollover.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct ArchivedStanding {
+    pub player_id: i32,
+    pub username: String,
+    pub standing: Standing,
+}
+
+/// Accounts kept in a JSON file, loaded once and written back on every change.
+/// Usernames are unique regardless of case.
+pub struct AccountStore {
+    path: PathBuf,
+    seasons_dir: PathBuf, // One file of archived standings per past season.
+    file: Arc<RwLock<AccountFile>>,
+    guest_id: Arc<Mutex<i32>>, // Id tracker for guests, not persisted.
+}
+
+impl AccountStore {
+    // A missing file is an empty store; a file that exists but cannot be read or parsed is an error.
+    pub fn load(path: &str, seasons_dir: &str) -> Result<Self, Error> {
+        let file = match std::fs::read_to_string(path) {
+            Err(error) if error.kind() == std::io::ErrorKind::NotFound => AccountFile {
+                next_id: 1,
+                accounts: Vec::new(),
+                season: None,
+            },
+            Err(_) => return Err(Error::InitializationFailed(14)),
+            Ok(content) => {
+                serde_json::from_str(&content).map_err(|_| Error::InitializationFailed(14))?
+            }
+        };
+
+        return Ok(Self {
+            path: PathBuf::from(path),
+            seasons_dir: PathBuf::from(seasons_dir),
+            file: Arc::new(RwLock::new(file)),
+            guest_id: Arc::new(Mutex::new(GUEST_ID_BASE)),
+        });
+    }
+
+    // Creates the account and returns it. Usernames are 3 to 20 letters, digits, `_` or `-`,
+    // passwords at least 8 characters.
+    pub async fn register(&self, credentials: &Credentials) -> Result<Account, Error> {
+        let username = credentials.username.trim();
+        let valid_name = (3..=20).contains(&username.chars().count())
+            && username
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-');
+        if !valid_name || credentials.password.chars().count() < 8 {
+            return Err(Error::ConnectionFailed(75));
+        }
+        if self.find(username).await.is_some() {
+            return Err(Error::ConnectionFailed(73));
+        }
+
+        let hash = hash_password(credentials.password.clone()).await?;
+        let mut file = self.file.write().await;
+        // Someone may have taken the name while the password was being hashed.
+        if file
+            .accounts
+            .iter()
+            .any(|account| account.username.eq_ignore_ascii_case(username))
+        {
+            return Err(Error::ConnectionFailed(73));
+        }
+
+        let created = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map_or(0, |elapsed| elapsed.as_secs());
+        let account = Account {
+            id: file.next_id,
+            username: username.to_string(),
+            hash,
+            created,
+            standing: Standing::default(),
+        };
+        file.next_id += 1;
+        file.accounts.push(account.clone());
+        self.save(&file)?;
+        return Ok(account);
+    }
+
+    // The account the credentials belong to. Unknown usernames and wrong passwords get the same error.
+    pub async fn login(&self, credentials: &Credentials) -> Result<Account, Error> {
+        let account = self
+            .find(credentials.username.trim())
+            .await
+            .ok_or(Error::ConnectionFailed(74))?;
+        match verify_password(credentials.password.clone(), account.hash.clone()).await {
+            true => return Ok(account),
+            false => return Err(Error::ConnectionFailed(74)),
+        }
+    }
+
+    pub async fn get(&self, id: i32) -> Option<Account> {
+        let file = self.file.read().await;
+        return file
+            .accounts
+            .iter()
+            .find(|account| account.id == id)
+            .cloned();
+    }
+
+    // Standing of the account, None for guests and bots.
+    pub async fn standing(&self, id: i32) -> Option<Standing> {
+        return self.get(id).await.map(|account| account.standing);
+    }
+
+    // Saves the standings a ranked match left its accounts with. Ids without an account are skipped.
+    pub async fn update_standings(&self, standings: &[(i32, Standing)]) -> Result<(), Error> {
+        let mut file = self.file.write().await;
+        for (id, standing) in standings {
+            if let Some(account) = file.accounts.iter_mut().find(|account| account.id == *id) {
+                account.standing = *standing;
+            }
+        }
+        self.save(&file)?;
+        return Ok(());
+    }
+
+    // Every account with its standing, for the current season's leaderboards.
+    pub async fn standings(&self) -> Vec<ArchivedStanding> {
+        let file = self.file.read().await;
+        return file
+            .accounts
+            .iter()
+            .map(|account| ArchivedStanding {
+                player_id: account.id,
+                username: account.username.clone(),
+                standing: account.standing,
+            })
+            .collect();
+    }
+
+    // Moves the standings to `season` when they belong to an earlier one: the old standings are
+    // archived and every rating keeps `soft_reset` of its distance to the starting rating. Ranks
+    // are kept. Returns the season that was archived, if any.
+    pub async fn roll_season(&self, season: u32, soft_reset: f64) -> Result<Option<u32>, Error> {
+        let mut file = self.file.write().await;
+        let ended = match file.season {
+            Some(current) if current >= season => return Ok(None),
+            // Standings from before seasons were tracked start the current one as they are.
+            None => None,
+            Some(ended) => {
+                let archive: Vec<ArchivedStanding> = file
+                    .accounts
+                    .iter()
+                    .map(|account| ArchivedStanding {
+                        player_id: account.id,
+                        username: account.username.clone(),
+                        standing: account.standing,
+                    })
+                    .collect();
+                let document = serde_json::to_string_pretty(&archive)
+                    .map_err(|_| Error::SerializationFailed(14))?;
+                std::fs::create_dir_all(&self.seasons_dir)
+                    .map_err(|_| Error::SerializationFailed(14))?;
+                std::fs::write(self.season_path(ended), document)
+                    .map_err(|_| Error::SerializationFailed(14))?;
+
+                let start = Standing::default().rating;
+                for account in file.accounts.iter_mut() {
+                    let rating = account.standing.rating;
+                    account.standing.rating = start + (rating - start) * soft_reset;
+                }
+                Some(ended)
+            }
+        };
+
+        file.season = Some(season);
+        self.save(&file)?;
+        return Ok(ended);
+    }
+
+    // Standings archived at the end of a past season. Seasons never archived are error 79.
+    pub fn archived(&self, season: u32) -> Result<Vec<ArchivedStanding>, Error> {
+        let document = std::fs::read_to_string(self.season_path(season))
+            .map_err(|_| Error::OperationFailed(79))?;
+        return serde_json::from_str(&document).map_err(|_| Error::InitializationFailed(14));
+    }
+
+    fn season_path(&self, season: u32) -> PathBuf {
+        return self.seasons_dir.join(format!("season-{season}.json"));
+    }
+
+    pub async fn next_guest_id(&self) -> i32 {
+        let mut id = self.guest_id.lock().await;
+        *id += 1;
+        return *id;
+    }
+
+    async fn find(&self, username: &str) -> Option<Account> {
+        let file = self.file.read().await;
+        return file
+            .accounts
+            .iter()
+            .find(|account| account.username.eq_ignore_ascii_case(username))
+            .cloned();
+    }
+
+    // Writes next to the file and renames it over, so a crash never leaves half a store.
+    fn save(&self, file: &AccountFile) -> Result<(), Error> {
+        let document =
+            serde_json::to_string_pretty(file).map_err(|_| Error::SerializationFailed(14))?;
+        if let Some(dir) = self.path.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+            std::fs::create_dir_all(dir).map_err(|_| Error::SerializationFailed(14))?;
+        }
+        let temporary = self.path.with_extension("tmp");
+        std::fs::write(&temporary, document).map_err(|_| Error::SerializationFailed(14))?;
+        std::fs::rename(&temporary, &self.path).map_err(|_| Error::SerializationFailed(14))?;
+        return Ok(());
+    }
+}
+
+// Argon2 is slow on purpose, so hashing runs off the async workers.
+async fn hash_password(password: String) -> Result<String, Error> {
+    let mut salt = [0; 16];
+    getrandom::fill(&mut salt).map_err(|_| Error::InternalError)?;
+    return tokio::task::spawn_blocking(move || {
+        let salt = SaltString::encode_b64(&salt).map_err(|_| Error::InternalError)?;
+        let hash = Argon2::default()
+            .hash_password(password.as_bytes(), &salt)
+            .map_err(|_| Error::InternalError)?;
+        return Ok(hash.to_string());
+    })
+    .await
+    .map_err(|_| Error::InternalError)?;
+}
+
+async fn verify_password(password: String, hash: String) -> bool {
+    let verified = tokio::task::spawn_blocking(move || {
+        let Ok(hash) = PasswordHash::new(&hash) else {
+            return false;
+        };
+        return Argon2::default()
+            .verify_password(password.as_bytes(), &hash)
+            .is_ok();
+    });
+    return verified.await.unwrap_or(false);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn credentials(username: &str, password: &str) -> Credentials {
+        Credentials {
+            username: username.to_string(),
+            password: password.to_string(),
+        }
+    }
+
+    #[tokio::test]
+    async fn register_and_login() {
+        let dir = std::env::temp_dir().join(format!("mahjong-accounts-{}", std::process::id()));
+        let path = dir.join("accounts.json");
+        let path = path.to_str().unwrap();
+        let _ = std::fs::remove_file(path);
+
+        let seasons = dir.join("seasons");
+        let seasons = seasons.to_str().unwrap();
+        let store = AccountStore::load(path, seasons).unwrap();
+        let bunny = store
+            .register(&credentials("Bunny", "hunter2hunter2"))
+            .await
+            .unwrap();
+        assert_eq!(bunny.id, 1);
+        assert!(!bunny.hash.contains("hunter2"));
+        assert!(matches!(
+            store
+                .register(&credentials("bunny", "another password"))
+                .await,
+            Err(Error::ConnectionFailed(73))
+        ));
+        assert!(matches!(
+            store
+                .register(&credentials("no spaces", "hunter2hunter2"))
+                .await,
+            Err(Error::ConnectionFailed(75))
+        ));
+        assert!(matches!(
+            store.register(&credentials("fox", "short")).await,
+            Err(Error::ConnectionFailed(75))
+        ));
+
+        // The store survives a restart, and ids keep counting.
+        let store = AccountStore::load(path, seasons).unwrap();
+        assert_eq!(
+            store
+                .login(&credentials("BUNNY", "hunter2hunter2"))
+                .await
+                .unwrap()
+                .id,
+            1
+        );
+        assert!(matches!(
+            store.login(&credentials("bunny", "hunter3hunter3")).await,
+            Err(Error::ConnectionFailed(74))
+        ));
+        assert!(matches!(
+            store.login(&credentials("nobody", "hunter2hunter2")).await,
+            Err(Error::ConnectionFailed(74))
+        ));
+        let fox = store
+            .register(&credentials("fox", "hunter2hunter2"))
+            .await
+            .unwrap();
+        assert_eq!(fox.id, 2);
+        assert!(store.next_guest_id().await > GUEST_ID_BASE);
+
+        let standing = Standing {
+            rank: 3,
+            ..Standing::default()
+        };
+        store.update_standings(&[(2, standing)]).await.unwrap();
+        let store = AccountStore::load(path, seasons).unwrap();
+        assert_eq!(store.standing(2).await.unwrap().rank, 3);
+        assert_eq!(store.standing(-1).await, None);
+
+        // The first check only adopts the season; the next rollover archives it and soft resets.
+        let mut standing = store.standing(2).await.unwrap();
+        standing.rating = 1700.0;
+        store.update_standings(&[(2, standing)]).await.unwrap();
+        assert_eq!(store.roll_season(4, 0.5).await.unwrap(), None);
+        assert_eq!(store.roll_season(4, 0.5).await.unwrap(), None);
+        assert_eq!(store.roll_season(5, 0.5).await.unwrap(), Some(4));
+        assert_eq!(store.standing(2).await.unwrap().rating, 1600.0);
+        assert_eq!(store.standing(2).await.unwrap().rank, 3);
+        let archived = store.archived(4).unwrap();
+        assert!(
+            archived
+                .iter()
+                .any(|a| a.player_id == 2 && a.standing.rating == 1700.0)
+        );
+        assert!(matches!(store.archived(3), Err(Error::OperationFailed(79))));
+
+        let _ = std::fs::remove_dir_all(dir);
+    }
+}
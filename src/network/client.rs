@@ -1,14 +1,23 @@
 use crate::game::player::Player;
-use crate::protocol::packet::{Packet, WriteBytesExt};
+use crate::network::transport::{BoxedReader, BoxedWriter};
+use crate::protocol::codec::Codec;
+use crate::protocol::event_log::{MatchEvent, SequencedEvent};
+use crate::protocol::heartbeat::Heartbeat;
+use crate::protocol::packet::{Packet, PacketKind};
 use crate::protocol::protocol::Protocol;
+use crate::protocol::version::Handshake;
+use crate::protocol::view::Viewer;
+use crate::utils::config::{HeartbeatConfig, OutboundConfig};
+use crate::utils::errors::Error;
+use std::collections::{HashSet, VecDeque};
 use std::net::SocketAddr;
 use std::sync::Arc;
 use std::time::Duration;
-use tokio::io::AsyncReadExt;
-use tokio::net::TcpStream;
-use tokio::net::tcp::{OwnedReadHalf, OwnedWriteHalf};
-use tokio::sync::{RwLock, broadcast};
-use tokio::time::sleep;
+use tokio::sync::RwLock;
+use tokio::sync::broadcast::{self, error::RecvError};
+use tokio::sync::mpsc::{self, error::TrySendError};
+use tokio::task::JoinHandle;
+use tokio::time::{Instant, timeout};
 
 pub struct Client {
     pub id: i32,
@@ -16,101 +25,298 @@ pub struct Client {
     pub protocol: Arc<Protocol>,
     pub listening: Arc<RwLock<bool>>,
     pub addr: Arc<RwLock<SocketAddr>>,
-    pub read_half: Arc<RwLock<OwnedReadHalf>>,
-    pub write_half: Arc<RwLock<OwnedWriteHalf>>,
-    pub bcrx: Arc<RwLock<broadcast::Receiver<Packet>>>,
+    pub read_half: Arc<RwLock<BoxedReader>>,
+    pub handshake: Arc<RwLock<Handshake>>,
+    pub write_half: Arc<RwLock<BoxedWriter>>,
+    pub bcrx: Arc<RwLock<broadcast::Receiver<Arc<SequencedEvent>>>>,
+    pub last_seq: Arc<RwLock<i32>>, // Sequence number of the last match event sent to the client.
+    pub heartbeat: HeartbeatConfig,
+    pub outbound: OutboundConfig,
+    pub queue: Arc<RwLock<Option<mpsc::Sender<Packet>>>>, // Packets waiting for the writer task, None while disconnected.
+    pub epoch: Instant,                                   // Reference point for ping timestamps.
+    pub last_seen: Arc<RwLock<Instant>>,                  // Arrival of the last packet of any kind.
+    pub muted: Arc<RwLock<HashSet<i32>>>, // Players whose chat is not relayed to this client.
+    pub chat_sent: Arc<RwLock<VecDeque<Instant>>>, // Recent chat of this client, for the rate limit.
+    tasks: Arc<RwLock<Vec<JoinHandle<()>>>>, // Reader, writer, broadcast and heartbeat tasks of the current connection.
 }
 
 impl Client {
     pub async fn new(
         id: i32,
         addr: SocketAddr,
-        stream: TcpStream,
+        read: BoxedReader,
+        write: BoxedWriter,
+        handshake: Handshake,
         player: Arc<Player>,
         protocol: Arc<Protocol>,
-        bcrx: broadcast::Receiver<Packet>,
+        bcrx: broadcast::Receiver<Arc<SequencedEvent>>,
+        heartbeat: HeartbeatConfig,
+        outbound: OutboundConfig,
     ) -> Arc<Self> {
-        let (read, write) = stream.into_split();
         Arc::new(Self {
             id,
             player,
             protocol,
             addr: Arc::new(RwLock::new(addr)),
             bcrx: Arc::new(RwLock::new(bcrx)),
+            last_seq: Arc::new(RwLock::new(0)),
             read_half: Arc::new(RwLock::new(read)),
+            handshake: Arc::new(RwLock::new(handshake)),
             listening: Arc::new(RwLock::new(false)),
             write_half: Arc::new(RwLock::new(write)),
+            heartbeat,
+            outbound,
+            queue: Arc::new(RwLock::new(None)),
+            epoch: Instant::now(),
+            last_seen: Arc::new(RwLock::new(Instant::now())),
+            muted: Arc::new(RwLock::new(HashSet::new())),
+            chat_sent: Arc::new(RwLock::new(VecDeque::new())),
+            tasks: Arc::new(RwLock::new(Vec::new())),
         })
     }
 
     // Main client loop to listen to the pooling of the incoming packets.
     // If no bytes are read the connection is closed.
-    // Tries to parse bytes into a Packet struct. No penalty for invalid packets.
-    // Calls Protocol to handle each valid packet in a tokio async task.
+    // Tries to parse bytes into a Packet struct. No penalty for invalid packets,
+    // but a packet with an invalid size closes the connection as the framing is lost.
+    // Heartbeats are answered here; every other packet is handled by Protocol in a tokio async task.
+    // Outgoing packets go through a bounded queue drained by the writer task.
     pub async fn connect(self: Arc<Self>) {
-        tokio::spawn({
+        *self.listening.write().await = true;
+        *self.player.connected.write().await = true;
+        *self.last_seen.write().await = Instant::now();
+
+        let (queue, outbound) = mpsc::channel(self.outbound.capacity.max(1));
+        *self.queue.write().await = Some(queue);
+        let writer = tokio::spawn(Arc::clone(&self).write_queued(outbound));
+
+        let broadcast = tokio::spawn({
             let self_clone = Arc::clone(&self);
             async move {
                 let mut bcrx = self_clone.bcrx.write().await;
                 while *self_clone.listening.read().await {
-                    if let Ok(global_packet) = bcrx.recv().await {
-                        self_clone.send_packet(&global_packet).await;
+                    match bcrx.recv().await {
+                        Ok(event) => self_clone.send_event(&event).await,
+                        Err(RecvError::Closed) => break,
+                        // Events the channel dropped are still in the log.
+                        Err(RecvError::Lagged(_)) => {
+                            let last_seq = *self_clone.last_seq.read().await;
+                            for event in self_clone.protocol.events.after(last_seq).await {
+                                self_clone.send_event(&event).await;
+                            }
+                        }
                     }
                 }
             }
         });
 
-        tokio::spawn(async move {
-            let mut buffer = [0; 4096];
-            *self.listening.write().await = true;
-            *self.player.connected.write().await = true;
-
-            while *self.listening.read().await {
-                let mut read_stream = self.read_half.write().await;
-                let bytes_read = match read_stream.read(&mut buffer).await {
-                    Ok(0) => break,
-                    Ok(n) => n,
-                    Err(_) => continue,
-                };
-
-                match Packet::from_bytes(&buffer[..bytes_read]) {
-                    Err(error) => self.send_packet(&Packet::error(0, error)).await,
-                    Ok(packet) => {
-                        Arc::clone(&self.protocol)
-                            .handle_packet(Arc::clone(&self), packet)
-                            .await
-                    }
-                };
+        let heartbeat = tokio::spawn(Arc::clone(&self).heartbeat());
+
+        let reader = tokio::spawn({
+            let self_clone = Arc::clone(&self);
+            async move {
+                while *self_clone.listening.read().await {
+                    let mut read_stream = self_clone.read_half.write().await;
+                    match read_stream.read_packet().await {
+                        Ok(None) => break,
+                        Err(error) => {
+                            let framing_lost = matches!(error, Error::PacketSizeInvalid(_));
+                            self_clone.send_packet(&Packet::error(0, error)).await;
+                            if framing_lost {
+                                break;
+                            }
+                        }
+                        Ok(Some(packet)) => {
+                            *self_clone.last_seen.write().await = Instant::now();
+                            if packet.kind == PacketKind::Heartbeat {
+                                self_clone.handle_heartbeat(&packet).await;
+                                continue;
+                            }
+
+                            Arc::clone(&self_clone.protocol)
+                                .handle_packet(Arc::clone(&self_clone), packet)
+                                .await
+                        }
+                    };
+                }
+
+                self_clone.disconnect().await;
+            }
+        });
+
+        self.tasks
+            .write()
+            .await
+            .extend([writer, broadcast, heartbeat, reader]);
+    }
+
+    // Writes queued packets in order. A write that fails or stalls past the heartbeat
+    // timeout means the peer is gone, so the client is disconnected.
+    async fn write_queued(self: Arc<Self>, mut outbound: mpsc::Receiver<Packet>) {
+        while let Some(packet) = outbound.recv().await {
+            let mut write_guard = self.write_half.write().await;
+            let written =
+                timeout(self.heartbeat.timeout(), write_guard.write_packet(&packet)).await;
+            drop(write_guard);
+
+            if !matches!(written, Ok(Ok(()))) {
+                break;
             }
+        }
 
+        if *self.listening.read().await {
             self.disconnect().await;
-        });
+        }
+    }
+
+    // Pings the client every interval. A client silent for longer than the timeout is
+    // behind a half-open socket or hung, so its seat is marked disconnected.
+    async fn heartbeat(self: Arc<Self>) {
+        let mut interval = tokio::time::interval(self.heartbeat.interval());
+        interval.tick().await;
+        while *self.listening.read().await {
+            interval.tick().await;
+            if self.last_seen.read().await.elapsed() > self.heartbeat.timeout() {
+                let addr = self.addr.read().await;
+                let log_msg = format!("{addr}: no heartbeat, disconnecting");
+                self.protocol.logger.info(&log_msg).await;
+                self.disconnect().await;
+                return;
+            }
+
+            let timestamp = self.epoch.elapsed().as_micros() as u64;
+            let id = self.protocol.get_global_id().await;
+            self.send_packet(&Heartbeat::Ping(timestamp).packet(id))
+                .await;
+        }
+    }
+
+    // Answers pings from the client and measures the round trip from our own pongs.
+    async fn handle_heartbeat(&self, packet: &Packet) {
+        match Heartbeat::parse(&packet.body) {
+            Err(error) => self.send_packet(&Packet::error(packet.id, error)).await,
+            Ok(Heartbeat::Pong(timestamp)) => {
+                let sent = Duration::from_micros(timestamp);
+                *self.player.rtt.write().await = self.epoch.elapsed().checked_sub(sent);
+            }
+            Ok(ping) => {
+                if let Some(pong) = ping.reply() {
+                    self.send_packet(&pong.packet(packet.id)).await;
+                }
+            }
+        }
     }
 
-    pub async fn reconnect(self: Arc<Self>, stream: TcpStream, addr: SocketAddr) {
-        let (read, write) = stream.into_split();
+    pub async fn reconnect(
+        self: Arc<Self>,
+        read: BoxedReader,
+        write: BoxedWriter,
+        handshake: Handshake,
+        addr: SocketAddr,
+    ) {
+        // The old reader may still be blocked on a half-open socket holding the read half.
+        self.stop_tasks().await;
         *self.addr.write().await = addr;
+        *self.handshake.write().await = handshake;
         *self.read_half.write().await = read;
         *self.write_half.write().await = write;
         Arc::clone(&self).connect().await;
     }
 
-    pub async fn disconnect(self: Arc<Self>) {
+    // Marks the player disconnected and starts the grace period of their session token.
+    pub async fn disconnect(&self) {
         *self.listening.write().await = false;
         *self.player.connected.write().await = false;
+        *self.player.rtt.write().await = None;
+        *self.queue.write().await = None;
+        self.protocol.sessions.suspend(self.id).await;
+        self.stop_tasks().await;
     }
 
-    pub async fn send_packet(&self, packet: &Packet) {
-        let mut tries = 0;
-        while tries < 30 {
-            let mut write_guard = self.write_half.write().await;
-            if let Err(_) = write_guard.send_packet(packet).await {
-                sleep(Duration::from_secs(2)).await;
-                tries += 1;
-                continue;
+    // Stops the tasks of the current connection.
+    // Aborting may hit the calling task, so nothing is awaited after the tasks are taken.
+    async fn stop_tasks(&self) {
+        let tasks = std::mem::take(&mut *self.tasks.write().await);
+        for task in tasks {
+            task.abort();
+        }
+    }
+
+    // Queues a match event unless the client already got it from a replay
+    // or it is chat from a player this client muted.
+    pub async fn send_event(&self, event: &SequencedEvent) {
+        // The event is claimed under the lock, which is released before the write so a slow
+        // connection does not hold up replays or snapshots reading `last_seq`.
+        {
+            let mut last_seq = self.last_seq.write().await;
+            if event.seq <= *last_seq {
+                return;
             }
+            *last_seq = event.seq;
+        }
+
+        if self.mutes(&event.event).await {
+            return;
+        }
+
+        let packet = event.packet(Viewer::Player(self.id), self.codec().await);
+        self.feed_packet(&packet).await;
+    }
+
+    // Whether the event is chat from a player this client muted.
+    pub async fn mutes(&self, event: &MatchEvent) -> bool {
+        match event {
+            MatchEvent::Chat { player_id, .. } => self.muted.read().await.contains(player_id),
+            _ => false,
+        }
+    }
 
-            break;
+    // Counts a chat message against the rate limit, false when the window is already full.
+    pub async fn take_chat_slot(&self, limit: usize, window: Duration) -> bool {
+        let mut sent = self.chat_sent.write().await;
+        while sent.front().is_some_and(|at| at.elapsed() >= window) {
+            sent.pop_front();
+        }
+
+        if sent.len() >= limit {
+            return false;
+        }
+        sent.push_back(Instant::now());
+        return true;
+    }
+
+    // Payload encoding negotiated in the last handshake.
+    pub async fn codec(&self) -> Codec {
+        Codec::from_capabilities(self.handshake.read().await.capabilities)
+    }
+
+    // Queues a packet without waiting, so the caller never depends on this client's socket.
+    // A full queue means the client fell too far behind and it is disconnected; it gets
+    // a snapshot when it reconnects. Packets sent while disconnected are dropped.
+    pub async fn send_packet(&self, packet: &Packet) {
+        let Some(queue) = self.queue.read().await.clone() else {
+            return;
+        };
+
+        if let Err(TrySendError::Full(_)) = queue.try_send(packet.clone()) {
+            let addr = self.addr.read().await;
+            let log_msg = format!("{addr}: outbound queue full, disconnecting");
+            self.protocol.logger.info(&log_msg).await;
+            drop(addr);
+            self.disconnect().await;
+        }
+    }
+
+    // Queues a packet, waiting up to the heartbeat timeout for room. Used by this client's own
+    // tasks for bursts such as replays, where waiting only delays this client.
+    pub async fn feed_packet(&self, packet: &Packet) {
+        let Some(queue) = self.queue.read().await.clone() else {
+            return;
+        };
+
+        let queued = timeout(self.heartbeat.timeout(), queue.send(packet.clone())).await;
+        if queued.is_err() && *self.listening.read().await {
+            self.disconnect().await;
         }
     }
 }
@@ -1,125 +1,294 @@
+use crate::game::player::Player;
+use crate::network::accounts::{Account, AccountStore};
 use crate::network::client::Client;
+use crate::network::leaderboard;
+use crate::network::match_registry::MatchRegistry;
+use crate::network::matchmaker::{Matchmaker, QueueUpdate};
+use crate::network::results::ResultStore;
 use crate::network::setup::Setup;
-use crate::protocol::packet::{Packet, PacketKind, WriteBytesExt};
+use crate::network::spectator::Spectator;
+use crate::network::tournament::Tournaments;
+use crate::network::transport::{BoxedReader, BoxedWriter};
+use crate::protocol::account::{Credentials, Identity};
+use crate::protocol::codec::Codec;
+use crate::protocol::heartbeat::Heartbeat;
+use crate::protocol::lobby::{LEFT_QUEUE_OP, Lobby, PROFILE_OP, QUEUE_OP, ROOMS_OP, Seated};
+use crate::protocol::packet::{Packet, PacketKind};
 use crate::protocol::protocol::Protocol;
+use crate::protocol::tournament::{TOURNAMENT_OP, TOURNAMENTS_OP, TournamentRequest};
+use crate::protocol::version::{ACCOUNT_VERSION, Handshake, LOBBY_VERSION};
+use crate::utils::config::{
+    Config, HeartbeatConfig, OutboundConfig, RankingConfig, SpectatorConfig,
+};
 use crate::utils::errors::Error;
-use crate::utils::types::ClientPool;
+use crate::utils::models::JoinRequest;
 use lolg::Lolg;
-use std::collections::HashMap;
 use std::net::SocketAddr;
 use std::sync::Arc;
-use tokio::io::AsyncReadExt;
-use tokio::net::TcpStream;
-use tokio::sync::RwLock;
+use tokio::sync::{Mutex, mpsc};
+use tokio::time::{Instant, timeout};
 
 pub struct ClientManager {
     pub logger: Arc<Lolg>,
-    pub protocol: Arc<Protocol>,
-    pub client_pool: ClientPool,
+    pub registry: Arc<MatchRegistry>, // Tables clients are routed to.
+    pub matchmaker: Arc<Matchmaker>,  // Queue that seats lobby players at tables it opens.
+    pub accounts: Arc<AccountStore>,  // Registered players; guests get ids from it too.
+    pub tournaments: Arc<Tournaments>, // Swiss tournaments, seated from the lobby.
+    guests: bool,                     // Whether players may connect without an account.
+    ranking: RankingConfig,           // Seasons and pages of the leaderboards.
+    pub heartbeat: HeartbeatConfig,
+    pub outbound: OutboundConfig,
+    pub spectator: SpectatorConfig,
+    global_id: Arc<Mutex<i32>>, // Id tracker for packets sent before the client is at a table.
+    spectator_id: Arc<Mutex<i32>>, // Id tracker for spectators, separate from player ids.
 }
 
 impl ClientManager {
-    pub async fn new(logger: Arc<Lolg>) -> Result<Self, Error> {
-        let client_pool: ClientPool = Arc::new(RwLock::new(HashMap::default()));
-        let protocol = Protocol::new(Arc::clone(&logger), Arc::clone(&client_pool)).await?;
+    pub async fn new(logger: Arc<Lolg>, config: &Config) -> Result<Self, Error> {
+        let results = Arc::new(ResultStore::load(&config.results_path, config.ranking)?);
+        let accounts = AccountStore::load(&config.accounts.path, &config.accounts.seasons_dir)?;
+        let season = config.ranking.current_season();
+        if let Some(ended) = accounts
+            .roll_season(season, config.ranking.soft_reset)
+            .await?
+        {
+            logger
+                .info(&format!("Season {ended} ended, season {season} started."))
+                .await;
+        }
+        let accounts = Arc::new(accounts);
+        let registry =
+            MatchRegistry::new(Arc::clone(&logger), config, results, Arc::clone(&accounts));
+        let matchmaker = Matchmaker::new(
+            Arc::clone(&logger),
+            Arc::clone(&registry),
+            config.matchmaking,
+        );
+        let tournaments = Tournaments::load(
+            Arc::clone(&logger),
+            Arc::clone(&registry),
+            &config.tournaments,
+        )
+        .await?;
         Ok(Self {
             logger,
-            protocol,
-            client_pool: Arc::new(RwLock::new(HashMap::default())),
+            registry,
+            matchmaker,
+            accounts,
+            tournaments,
+            guests: config.accounts.guests,
+            ranking: config.ranking,
+            heartbeat: config.heartbeat,
+            outbound: config.outbound,
+            spectator: config.spectator,
+            global_id: Arc::new(Mutex::new(0)),
+            spectator_id: Arc::new(Mutex::new(0)),
         })
     }
 
+    async fn get_global_id(&self) -> i32 {
+        let mut id = self.global_id.lock().await;
+        *id += 1;
+        return *id;
+    }
+
     // Handles the initial client state (unauthenticated)
     // Client has ~~five~~ one attempt~~s~~ to send a connection packet.
     // Create a Client struct when/if successfully authenticated.
     // Store client and run the main listen loop for the definitive Client.
-    pub async fn accept(self: Arc<Self>, mut stream: TcpStream, addr: SocketAddr) {
+    // Players are seated at a table with a free seat and reconnect to the table that issued their token.
+    // The transport (TCP or WebSocket) is already split into packet reader and writer.
+    pub async fn accept(
+        self: Arc<Self>,
+        mut reader: BoxedReader,
+        mut writer: BoxedWriter,
+        addr: SocketAddr,
+    ) {
         tokio::spawn(async move {
             let mut attempts = 0;
-            let mut buffer = [0; 1024];
 
             while attempts < 5 {
-                let read_bytes = match stream.read(&mut buffer).await {
-                    Ok(0) => break,
-                    Ok(n) => n,
-                    Err(_) => {
-                        attempts += 1;
-                        continue;
-                    }
-                };
-
-                let _ = match Packet::from_bytes(&buffer[..read_bytes]) {
+                let _ = match reader.read_packet().await {
+                    Ok(None) => break,
                     Err(error) => {
+                        let framing_lost = matches!(error, Error::PacketSizeInvalid(_));
                         self.logger.error(&format!("{addr}: {error}")).await;
-                        let id = self.protocol.get_global_id().await;
-                        let _ = stream.send_packet(&Packet::error(id, error)).await;
+                        let id = self.get_global_id().await;
+                        let _ = writer.write_packet(&Packet::error(id, error)).await;
+                        if framing_lost {
+                            return;
+                        }
                     }
-                    Ok(packet) => {
+                    Ok(Some(packet)) => {
                         if packet.kind == PacketKind::Setup {
-                            let Some(operation) = Setup::from(&packet.body[..4]) else {
+                            let Some(operation) = packet.body.get(..4).and_then(Setup::from) else {
                                 let error = Error::ConnectionNeeded;
                                 self.logger.error(&format!("{addr}: {error}")).await;
-                                let _ = stream.send_packet(&Packet::error(packet.id, error)).await;
+                                let _ = writer.write_packet(&Packet::error(packet.id, error)).await;
                                 return;
                             };
 
                             match operation {
-                                Setup::Connection => {
-                                    match self.protocol.handle_connect(&packet).await {
+                                Setup::Connection => match Protocol::parse_join(&packet) {
+                                    Err(error) => {
+                                        self.logger.error(&format!("{addr}: {error}")).await;
+                                        let response = Packet::error(packet.id, error);
+                                        let _ = writer.write_packet(&response).await;
+                                    }
+                                    Ok(_) if !self.guests => {
+                                        let error = Error::ConnectionFailed(76);
+                                        self.logger.error(&format!("{addr}: {error}")).await;
+                                        let response = Packet::error(packet.id, error);
+                                        let _ = writer.write_packet(&response).await;
+                                    }
+                                    // Ids are assigned by the server, the one in the request is ignored.
+                                    // Clients that predate the lobby are seated right away.
+                                    Ok((handshake, mut req))
+                                        if handshake.version < LOBBY_VERSION =>
+                                    {
+                                        req.id = self.accounts.next_guest_id().await;
+                                        match self.registry.quick_play(&req).await {
+                                            Err(error) => {
+                                                self.logger
+                                                    .error(&format!("{addr}: {error}"))
+                                                    .await;
+                                                let response = Packet::error(packet.id, error);
+                                                let _ = writer.write_packet(&response).await;
+                                            }
+                                            Ok((protocol, player, token)) => {
+                                                let response = Protocol::handshake_response(
+                                                    packet.id,
+                                                    Setup::Connection,
+                                                    &handshake,
+                                                    &token,
+                                                );
+                                                let _ = writer.write_packet(&response).await;
+                                                self.take_seat(
+                                                    protocol, player, handshake, reader, writer,
+                                                    addr,
+                                                )
+                                                .await;
+                                                return;
+                                            }
+                                        }
+                                    }
+                                    Ok((handshake, mut req)) => {
+                                        req.id = self.accounts.next_guest_id().await;
+                                        let identity = Identity {
+                                            player_id: req.id,
+                                            guest: true,
+                                        };
+                                        Arc::clone(&self)
+                                            .enter_lobby(
+                                                packet.id, operation, handshake, identity, req,
+                                                reader, writer, addr,
+                                            )
+                                            .await;
+                                        return;
+                                    }
+                                },
+                                Setup::Register | Setup::Login => {
+                                    match self.authenticate(&operation, &packet).await {
                                         Err(error) => {
                                             self.logger.error(&format!("{addr}: {error}")).await;
                                             let response = Packet::error(packet.id, error);
-                                            let _ = stream.send_packet(&response).await;
+                                            let _ = writer.write_packet(&response).await;
                                         }
-                                        Ok(player) => {
-                                            let id = player.id;
-                                            let protocol = self.protocol.clone();
-                                            let bcrx = protocol.bctx.subscribe();
-                                            {
-                                                let alias = &player.alias.read().await;
-                                                let log_msg =
-                                                    format!("{addr}: connected as {alias}");
-                                                self.logger.info(&log_msg).await;
-                                            }
-                                            let client = Client::new(
-                                                id, addr, stream, player, protocol, bcrx,
-                                            )
-                                            .await;
-
-                                            Arc::clone(&client).connect().await;
-                                            self.client_pool.write().await.insert(id, client);
-                                            self.protocol.match_manager.check_ready().await;
+                                        Ok((handshake, account)) => {
+                                            let identity = Identity {
+                                                player_id: account.id,
+                                                guest: false,
+                                            };
+                                            let req = JoinRequest {
+                                                id: account.id,
+                                                alias: account.username,
+                                            };
+                                            Arc::clone(&self)
+                                                .enter_lobby(
+                                                    packet.id, operation, handshake, identity, req,
+                                                    reader, writer, addr,
+                                                )
+                                                .await;
                                             return;
                                         }
                                     }
                                 }
                                 Setup::Reconnection => {
-                                    match self.protocol.handle_reconnect(&packet) {
+                                    match self.registry.reconnect(&packet).await {
                                         Err(error) => {
                                             self.logger.error(&format!("{addr}: {error}")).await;
                                             let response = Packet::error(packet.id, error);
-                                            let _ = stream.send_packet(&response).await;
+                                            let _ = writer.write_packet(&response).await;
                                         }
-                                        Ok(request) => {
-                                            match self.client_pool.read().await.get(&request.id) {
-                                                None => {
-                                                    let error = Error::ReconnectionFailed(55);
-                                                    self.logger
-                                                        .error(&format!("{addr}: {error}"))
-                                                        .await;
-                                                    let response = Packet::error(packet.id, error);
-                                                    let _ = stream.send_packet(&response).await;
-                                                }
-                                                Some(client) => {
-                                                    self.logger
-                                                        .info(&format!("{addr}: reconnected"))
-                                                        .await;
-                                                    Arc::clone(&client)
-                                                        .reconnect(stream, addr)
-                                                        .await;
-                                                    return;
-                                                }
-                                            }
+                                        Ok((protocol, client, token, handshake)) => {
+                                            self.logger.info(&format!("{addr}: reconnected")).await;
+                                            let response = Protocol::handshake_response(
+                                                packet.id,
+                                                Setup::Reconnection,
+                                                &handshake,
+                                                &token,
+                                            );
+                                            let _ = writer.write_packet(&response).await;
+                                            Arc::clone(&client)
+                                                .reconnect(reader, writer, handshake, addr)
+                                                .await;
+
+                                            // The player missed everything while away.
+                                            let id = protocol.get_global_id().await;
+                                            let snapshot = protocol.resync(&client, id).await;
+                                            client.send_packet(&snapshot).await;
+                                            return;
+                                        }
+                                    }
+                                }
+                                Setup::Spectate => {
+                                    let spectated = match self.registry.spectate(&packet).await {
+                                        Err(error) => Err(error),
+                                        Ok(protocol) => protocol
+                                            .handle_spectate(&packet)
+                                            .await
+                                            .map(|(handshake, mode)| (protocol, handshake, mode)),
+                                    };
+                                    match spectated {
+                                        Err(error) => {
+                                            self.logger.error(&format!("{addr}: {error}")).await;
+                                            let response = Packet::error(packet.id, error);
+                                            let _ = writer.write_packet(&response).await;
+                                        }
+                                        Ok((protocol, handshake, mode)) => {
+                                            let id = {
+                                                let mut id = self.spectator_id.lock().await;
+                                                *id += 1;
+                                                *id
+                                            };
+                                            let mut body = Setup::Spectate.bytes().to_vec();
+                                            body.extend(handshake.bytes());
+                                            body.extend(id.to_le_bytes());
+                                            let response =
+                                                Packet::create(packet.id, PacketKind::Setup, &body);
+                                            let _ = writer.write_packet(&response).await;
+
+                                            let table = protocol.table_id;
+                                            let log_msg = format!(
+                                                "{addr}: spectating table {table} ({mode:?})"
+                                            );
+                                            self.logger.info(&log_msg).await;
+                                            let bcrx = protocol.events.subscribe();
+                                            let spectator = Spectator::new(
+                                                id,
+                                                addr,
+                                                mode,
+                                                handshake,
+                                                Arc::clone(&protocol),
+                                                self.heartbeat,
+                                                self.outbound,
+                                                self.spectator,
+                                            );
+                                            let mut spectators = protocol.spectators.write().await;
+                                            spectators.insert(id, Arc::clone(&spectator));
+                                            drop(spectators);
+                                            spectator.connect(reader, writer, bcrx).await;
+                                            return;
                                         }
                                     }
                                 }
@@ -127,14 +296,14 @@ impl ClientManager {
                                     let error = Error::ConnectionNeeded;
                                     self.logger.error(&format!("{addr}: {error}")).await;
                                     let response = Packet::error(packet.id, error);
-                                    let _ = stream.send_packet(&response).await;
+                                    let _ = writer.write_packet(&response).await;
                                     return;
                                 }
                             };
                         } else {
                             let error = Error::ConnectionNeeded;
                             self.logger.error(&format!("{addr}: {error}")).await;
-                            let _ = stream.send_packet(&Packet::error(packet.id, error)).await;
+                            let _ = writer.write_packet(&Packet::error(packet.id, error)).await;
                             return;
                         }
                     }
@@ -144,4 +313,401 @@ impl ClientManager {
             }
         });
     }
+
+    // Register and Login bodies: | setup (4) | handshake (6) | credentials |
+    async fn authenticate(
+        &self,
+        operation: &Setup,
+        packet: &Packet,
+    ) -> Result<(Handshake, Account), Error> {
+        let handshake_bytes = packet.body.get(4..).ok_or(Error::ConnectionFailed(54))?;
+        let handshake = Handshake::parse(handshake_bytes)?.negotiate()?;
+        if handshake.version < ACCOUNT_VERSION {
+            return Err(Error::OperationFailed(57));
+        }
+
+        let credentials = Credentials::parse(&handshake_bytes[Handshake::SIZE..])?;
+        let account = match operation {
+            Setup::Register => self.accounts.register(&credentials).await?,
+            _ => self.accounts.login(&credentials).await?,
+        };
+        return Ok((handshake, account));
+    }
+
+    // Acknowledges the connection and hands it to the lobby. From version 4 the acknowledgement
+    // ends with the player's identity: | setup (4) | handshake (6) | identity (5) |
+    // and is followed by the player's profile.
+    async fn enter_lobby(
+        self: Arc<Self>,
+        id: i32,
+        operation: Setup,
+        handshake: Handshake,
+        identity: Identity,
+        req: JoinRequest,
+        reader: BoxedReader,
+        mut writer: BoxedWriter,
+        addr: SocketAddr,
+    ) {
+        let mut body = operation.bytes().to_vec();
+        body.extend(handshake.bytes());
+        if handshake.version >= ACCOUNT_VERSION {
+            body.extend(identity.bytes());
+        }
+        let response = Packet::create(id, PacketKind::Setup, &body);
+        let _ = writer.write_packet(&response).await;
+
+        let (alias, player_id) = (&req.alias, req.id);
+        let kind = if identity.guest { "guest" } else { "account" };
+        let log_msg = format!("{addr}: in the lobby as {alias} ({kind} {player_id})");
+        self.logger.info(&log_msg).await;
+        if handshake.version >= ACCOUNT_VERSION {
+            let codec = Codec::from_capabilities(handshake.capabilities);
+            let profile = self
+                .profile(self.get_global_id().await, player_id, codec)
+                .await;
+            let _ = writer.write_packet(&profile).await;
+        }
+        self.lobby(reader, writer, addr, handshake, req).await;
+    }
+
+    // The player's rating and rank: | op 5 (u32) | encoded Profile |
+    async fn profile(&self, id: i32, player_id: i32, codec: Codec) -> Packet {
+        let standing = self.accounts.standing(player_id).await;
+        let guest = standing.is_none();
+        let profile = standing.unwrap_or_default().profile(player_id, guest);
+        match codec.encode(&profile) {
+            Err(error) => return Packet::error(id, error),
+            Ok(bytes) => {
+                let mut body = PROFILE_OP.to_vec();
+                body.extend(bytes);
+                return Packet::create(id, PacketKind::Lobby, &body);
+            }
+        }
+    }
+
+    // Answers the tournament requests that leave the player in the lobby: | op 1 (u32) | encoded
+    // Vec<TournamentInfo> | for the list, | op 2 (u32) | encoded TournamentView | for the rest.
+    // Registering takes an account; creating, starting, swapping and forfeiting seats take an admin's.
+    async fn tournament(
+        self: &Arc<Self>,
+        id: i32,
+        request: TournamentRequest,
+        player_id: i32,
+        codec: Codec,
+    ) -> Packet {
+        let account = self.accounts.get(player_id).await;
+        let admin = account
+            .as_ref()
+            .is_some_and(|account| self.tournaments.is_admin(account));
+        let tournaments = &self.tournaments;
+        let view = match request {
+            TournamentRequest::List => {
+                let list = tournaments.list().await;
+                match codec.encode(&list) {
+                    Err(error) => return Packet::error(id, error),
+                    Ok(bytes) => {
+                        let mut body = TOURNAMENTS_OP.to_vec();
+                        body.extend(bytes);
+                        return Packet::create(id, PacketKind::Tournament, &body);
+                    }
+                }
+            }
+            TournamentRequest::Show(tournament) => tournaments.show(tournament).await,
+            TournamentRequest::Register(tournament) => match &account {
+                None => Err(Error::OperationFailed(84)),
+                Some(account) => tournaments.register(tournament, account).await,
+            },
+            TournamentRequest::Withdraw(tournament) => {
+                tournaments.withdraw(tournament, player_id).await
+            }
+            TournamentRequest::Create { .. }
+            | TournamentRequest::Start(_)
+            | TournamentRequest::Swap { .. }
+            | TournamentRequest::Forfeit { .. }
+                if !admin =>
+            {
+                Err(Error::OperationFailed(83))
+            }
+            TournamentRequest::Create {
+                ruleset,
+                rounds,
+                name,
+            } => tournaments.create(ruleset, rounds, &name).await,
+            TournamentRequest::Start(tournament) => tournaments.start(tournament).await,
+            TournamentRequest::Swap {
+                tournament,
+                first,
+                second,
+            } => tournaments.swap(tournament, first, second).await,
+            TournamentRequest::Forfeit { tournament, player } => {
+                tournaments.forfeit(tournament, player).await
+            }
+            // Seats the player, the lobby handles it.
+            TournamentRequest::Play(_) => Err(Error::OperationFailed(57)),
+        };
+
+        match view.and_then(|view| codec.encode(&view)) {
+            Err(error) => return Packet::error(id, error),
+            Ok(bytes) => {
+                let mut body = TOURNAMENT_OP.to_vec();
+                body.extend(bytes);
+                return Packet::create(id, PacketKind::Tournament, &body);
+            }
+        }
+    }
+
+    // Turns a seated connection into a Client of its table.
+    async fn take_seat(
+        &self,
+        protocol: Arc<Protocol>,
+        player: Arc<Player>,
+        handshake: Handshake,
+        reader: BoxedReader,
+        writer: BoxedWriter,
+        addr: SocketAddr,
+    ) {
+        let id = player.id;
+        let bcrx = protocol.events.subscribe();
+        {
+            let alias = &player.alias.read().await;
+            let table = protocol.table_id;
+            let log_msg = format!("{addr}: connected as {alias} (table {table})");
+            self.logger.info(&log_msg).await;
+        }
+        let client = Client::new(
+            id,
+            addr,
+            reader,
+            writer,
+            handshake,
+            player,
+            Arc::clone(&protocol),
+            bcrx,
+            self.heartbeat,
+            self.outbound,
+        )
+        .await;
+
+        Arc::clone(&client).connect().await;
+        protocol.client_pool.write().await.insert(id, client);
+        protocol.try_start().await;
+    }
+
+    // A connected player without a seat. Lobby and tournament requests (and stats queries) are answered until one seats the player,
+    // who then becomes a Client of that table. Queued players are also sent the queue state as it
+    // changes and seated when the queue forms their table. Idle players are pinged like seated
+    // ones and dropped once silent for longer than the heartbeat timeout.
+    async fn lobby(
+        self: Arc<Self>,
+        reader: BoxedReader,
+        writer: BoxedWriter,
+        addr: SocketAddr,
+        handshake: Handshake,
+        req: JoinRequest,
+    ) {
+        let mut queued = None;
+        Arc::clone(&self)
+            .serve_lobby(reader, writer, addr, handshake, req, &mut queued)
+            .await;
+        // A player who left the lobby while queued is taken out of the queue right away.
+        if let Some((ticket, _)) = queued {
+            self.matchmaker.leave(ticket).await;
+        }
+    }
+
+    // Answers the player until they are seated or gone; `queued` holds their ticket while queued.
+    async fn serve_lobby(
+        self: Arc<Self>,
+        mut reader: BoxedReader,
+        mut writer: BoxedWriter,
+        addr: SocketAddr,
+        handshake: Handshake,
+        req: JoinRequest,
+        queued: &mut Option<(i32, mpsc::Receiver<QueueUpdate>)>,
+    ) {
+        let codec = Codec::from_capabilities(handshake.capabilities);
+        let epoch = Instant::now();
+        let mut last_seen = Instant::now();
+        loop {
+            let (id, seated) = tokio::select! {
+                update = next_update(queued) => match update {
+                    None => {
+                        *queued = None;
+                        continue;
+                    }
+                    Some(QueueUpdate::State(state)) => {
+                        let id = self.get_global_id().await;
+                        if let Ok(bytes) = codec.encode(&state) {
+                            let mut body = QUEUE_OP.to_vec();
+                            body.extend(bytes);
+                            let packet = Packet::create(id, PacketKind::Lobby, &body);
+                            let _ = writer.write_packet(&packet).await;
+                        }
+                        continue;
+                    }
+                    Some(QueueUpdate::Seated(seated)) => {
+                        *queued = None;
+                        (self.get_global_id().await, seated)
+                    }
+                },
+                read = timeout(self.heartbeat.interval(), reader.read_packet()) => {
+                    let packet = match read {
+                        Err(_) => {
+                            if last_seen.elapsed() > self.heartbeat.timeout() {
+                                let log_msg = format!("{addr}: no heartbeat, leaving the lobby");
+                                self.logger.info(&log_msg).await;
+                                return;
+                            }
+                            let id = self.get_global_id().await;
+                            let timestamp = epoch.elapsed().as_micros() as u64;
+                            let _ = writer
+                                .write_packet(&Heartbeat::Ping(timestamp).packet(id))
+                                .await;
+                            continue;
+                        }
+                        Ok(Ok(None)) => return,
+                        Ok(Err(error)) => {
+                            let framing_lost = matches!(error, Error::PacketSizeInvalid(_));
+                            let _ = writer.write_packet(&Packet::error(0, error)).await;
+                            if framing_lost {
+                                return;
+                            }
+                            continue;
+                        }
+                        Ok(Ok(Some(packet))) => packet,
+                    };
+
+                    last_seen = Instant::now();
+                    let seated = match packet.kind {
+                        PacketKind::Heartbeat => {
+                            let reply =
+                                Heartbeat::parse(&packet.body).map(|heartbeat| heartbeat.reply());
+                            if let Ok(Some(pong)) = reply {
+                                let _ = writer.write_packet(&pong.packet(packet.id)).await;
+                            }
+                            continue;
+                        }
+                        PacketKind::Stats => {
+                            let results = &self.registry.results;
+                            let response = results.answer(&packet, req.id, codec).await;
+                            let _ = writer.write_packet(&response).await;
+                            continue;
+                        }
+                        PacketKind::Leaderboard => {
+                            let (accounts, results) = (&self.accounts, &self.registry.results);
+                            let response =
+                                leaderboard::answer(&packet, codec, accounts, results, self.ranking)
+                                    .await;
+                            let _ = writer.write_packet(&response).await;
+                            continue;
+                        }
+                        PacketKind::Tournament => match TournamentRequest::parse(&packet.body) {
+                            Err(error) => Err(error),
+                            // Queued players wait for the queue to seat them.
+                            Ok(TournamentRequest::Play(_)) if queued.is_some() => {
+                                Err(Error::OperationFailed(71))
+                            }
+                            Ok(TournamentRequest::Play(tournament)) => {
+                                self.tournaments.play(tournament, &req).await
+                            }
+                            Ok(request) => {
+                                let response =
+                                    self.tournament(packet.id, request, req.id, codec).await;
+                                let _ = writer.write_packet(&response).await;
+                                continue;
+                            }
+                        },
+                        PacketKind::Lobby => match Lobby::parse(&packet.body) {
+                            Err(error) => Err(error),
+                            Ok(Lobby::List) => {
+                                let rooms = self.registry.rooms().await;
+                                let response = match codec.encode(&rooms) {
+                                    Err(error) => Packet::error(packet.id, error),
+                                    Ok(bytes) => {
+                                        let mut body = ROOMS_OP.to_vec();
+                                        body.extend(bytes);
+                                        Packet::create(packet.id, PacketKind::Lobby, &body)
+                                    }
+                                };
+                                let _ = writer.write_packet(&response).await;
+                                continue;
+                            }
+                            Ok(Lobby::LeaveQueue) => match queued.take() {
+                                Some((ticket, _)) if self.matchmaker.leave(ticket).await => {
+                                    let response =
+                                        Packet::create(packet.id, PacketKind::Lobby, &LEFT_QUEUE_OP);
+                                    let _ = writer.write_packet(&response).await;
+                                    continue;
+                                }
+                                // Already picked for a table, the seat is on its way.
+                                Some(ticket) => {
+                                    *queued = Some(ticket);
+                                    Err(Error::OperationFailed(72))
+                                }
+                                None => Err(Error::OperationFailed(72)),
+                            },
+                            Ok(Lobby::Profile) => {
+                                let response = self.profile(packet.id, req.id, codec).await;
+                                let _ = writer.write_packet(&response).await;
+                                continue;
+                            }
+                            // Queued players wait for the queue to seat them.
+                            Ok(_) if queued.is_some() => Err(Error::OperationFailed(71)),
+                            Ok(Lobby::Queue(ruleset, tier)) => {
+                                let standing = self.accounts.standing(req.id).await;
+                                let standing = standing.unwrap_or_default();
+                                if standing.admits(tier) {
+                                    let rating = standing.rating();
+                                    let ticket =
+                                        self.matchmaker.enqueue(&req, ruleset, tier, rating).await;
+                                    *queued = Some(ticket);
+                                    let log_msg = format!("{addr}: queued for {ruleset:?} ({tier:?})");
+                                    self.logger.info(&log_msg).await;
+                                    continue;
+                                }
+                                Err(Error::OperationFailed(78))
+                            }
+                            Ok(Lobby::Create { ruleset, password }) => {
+                                self.registry.create(ruleset, password, &req).await
+                            }
+                            Ok(Lobby::Join { code, password }) => {
+                                self.registry.join(&code, password.as_deref(), &req).await
+                            }
+                            Ok(Lobby::QuickPlay) => self.registry.quick_play(&req).await,
+                        },
+                        _ => Err(Error::OperationFailed(57)),
+                    };
+                    (packet.id, seated)
+                }
+            };
+
+            match seated {
+                Err(error) => {
+                    self.logger.error(&format!("{addr}: {error}")).await;
+                    let _ = writer.write_packet(&Packet::error(id, error)).await;
+                }
+                Ok((protocol, player, token)) => {
+                    let seated = Seated {
+                        table_id: protocol.table_id,
+                        code: protocol.room.code.clone(),
+                        token,
+                    };
+                    let _ = writer.write_packet(&seated.packet(id)).await;
+                    self.take_seat(protocol, player, handshake, reader, writer, addr)
+                        .await;
+                    return;
+                }
+            }
+        }
+    }
+}
+
+// Next message of the queue the player is in; never resolves while they are not queued.
+async fn next_update(
+    queued: &mut Option<(i32, mpsc::Receiver<QueueUpdate>)>,
+) -> Option<QueueUpdate> {
+    match queued {
+        None => return std::future::pending().await,
+        Some((_, updates)) => return updates.recv().await,
+    }
 }
@@ -0,0 +1,163 @@
+use crate::network::accounts::{AccountStore, ArchivedStanding};
+use crate::network::results::{Placings, ResultStore};
+use crate::protocol::codec::Codec;
+use crate::protocol::packet::{Packet, PacketKind};
+use crate::protocol::stats::{Board, LeaderboardEntry, LeaderboardPage, LeaderboardQuery};
+use crate::utils::config::RankingConfig;
+use crate::utils::errors::Error;
+use std::collections::HashMap;
+
+// Answers a leaderboard query with one page of the board. The current season is rolled over
+// first so a board is never built from standings of a season that has ended.
+pub async fn answer(
+    packet: &Packet,
+    codec: Codec,
+    accounts: &AccountStore,
+    results: &ResultStore,
+    ranking: RankingConfig,
+) -> Packet {
+    let page = match LeaderboardQuery::parse(&packet.body) {
+        Err(error) => Err(error),
+        Ok(query) => build(query, accounts, results, ranking).await,
+    };
+    match page.and_then(|page| codec.encode(&page)) {
+        Err(error) => return Packet::error(packet.id, error),
+        Ok(bytes) => return Packet::create(packet.id, PacketKind::Leaderboard, &bytes),
+    }
+}
+
+async fn build(
+    query: LeaderboardQuery,
+    accounts: &AccountStore,
+    results: &ResultStore,
+    ranking: RankingConfig,
+) -> Result<LeaderboardPage, Error> {
+    let current = ranking.current_season();
+    accounts.roll_season(current, ranking.soft_reset).await?;
+
+    // Past seasons are ranked by the standings archived when they ended.
+    let season = query.season.unwrap_or(current);
+    let standings = match season {
+        season if season >= current => accounts.standings().await,
+        season => accounts.archived(season)?,
+    };
+    let placings = results.placings(season, query.ruleset).await;
+    return Ok(page(query, season, standings, &placings, ranking));
+}
+
+// Sorts the players with ranked games in the season into the board and cuts out the page asked for.
+fn page(
+    query: LeaderboardQuery,
+    season: u32,
+    standings: Vec<ArchivedStanding>,
+    placings: &HashMap<i32, Placings>,
+    ranking: RankingConfig,
+) -> LeaderboardPage {
+    let mut ranked: Vec<(ArchivedStanding, Placings)> = standings
+        .into_iter()
+        .filter_map(|standing| {
+            let placings = placings.get(&standing.player_id)?;
+            return Some((standing, *placings));
+        })
+        .filter(|(_, placings)| match query.board {
+            Board::Placement => placings.games >= ranking.placement_games,
+            Board::Rating | Board::Games => true,
+        })
+        .collect();
+
+    ranked.sort_by(|(a, a_placings), (b, b_placings)| {
+        let order = match query.board {
+            Board::Rating => b.standing.rating.total_cmp(&a.standing.rating),
+            Board::Games => b_placings.games.cmp(&a_placings.games),
+            Board::Placement => a_placings.average().total_cmp(&b_placings.average()),
+        };
+        return order
+            .then(b_placings.games.cmp(&a_placings.games))
+            .then(a.player_id.cmp(&b.player_id));
+    });
+
+    let size = ranking.page_size.max(1);
+    let pages = ranked.len().div_ceil(size) as u32;
+    let skip = query.page as usize * size;
+    let entries = ranked
+        .into_iter()
+        .enumerate()
+        .skip(skip)
+        .take(size)
+        .map(|(index, (standing, placings))| LeaderboardEntry {
+            position: index as u32 + 1,
+            player_id: standing.player_id,
+            username: standing.username,
+            rating: standing.standing.rating(),
+            rank: standing.standing.rank_name().to_string(),
+            games: placings.games,
+            average_placement: placings.average(),
+        })
+        .collect();
+
+    return LeaderboardPage {
+        board: query.board,
+        ruleset: query.ruleset,
+        season,
+        page: query.page,
+        pages,
+        entries,
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::network::ranking::Standing;
+
+    fn standing(player_id: i32, rating: f64) -> ArchivedStanding {
+        ArchivedStanding {
+            player_id,
+            username: format!("player{player_id}"),
+            standing: Standing {
+                rating,
+                ..Default::default()
+            },
+        }
+    }
+
+    #[test]
+    fn sorts_and_pages() {
+        let standings = vec![
+            standing(1, 1600.0),
+            standing(2, 1450.0),
+            standing(3, 1700.0),
+            standing(4, 1800.0), // No ranked games this season.
+        ];
+        let placed = |games, placements| Placings { games, placements };
+        let placings = HashMap::from([(1, placed(6, 15)), (2, placed(9, 18)), (3, placed(2, 2))]);
+        let ranking = RankingConfig {
+            page_size: 2,
+            ..Default::default()
+        };
+        let board = |board, page_number| {
+            let query = LeaderboardQuery {
+                board,
+                ruleset: None,
+                season: None,
+                page: page_number,
+            };
+            return page(query, 1, standings.clone(), &placings, ranking);
+        };
+        let ids = |page: &LeaderboardPage| -> Vec<i32> {
+            return page.entries.iter().map(|e| e.player_id).collect();
+        };
+
+        let rating = board(Board::Rating, 0);
+        assert_eq!((ids(&rating), rating.pages), (vec![3, 1], 2));
+        let second = board(Board::Rating, 1);
+        assert_eq!(ids(&second), vec![2]);
+        assert_eq!(second.entries[0].position, 3);
+        assert_eq!(ids(&board(Board::Games, 0)), vec![2, 1]);
+
+        // Player 3 has too few games to be ranked by placement.
+        let placement = board(Board::Placement, 0);
+        assert_eq!((ids(&placement), placement.pages), (vec![2, 1], 1));
+        assert_eq!(placement.entries[0].average_placement, 2.0);
+    }
+}
@@ -0,0 +1,477 @@
+use crate::game::match_manager::MatchStatus;
+use crate::game::player::Player;
+use crate::network::accounts::AccountStore;
+use crate::network::client::Client;
+use crate::network::results::ResultStore;
+use crate::protocol::lobby::{ROOM_CODE_ALPHABET, ROOM_CODE_SIZE, RoomInfo, Ruleset, Tier};
+use crate::protocol::packet::Packet;
+use crate::protocol::protocol::Protocol;
+use crate::protocol::session::SessionToken;
+use crate::protocol::version::Handshake;
+use crate::utils::config::Config;
+use crate::utils::errors::Error;
+use crate::utils::models::{JoinRequest, SpectatorMode};
+use crate::utils::types::TablePool;
+use lolg::Lolg;
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::{Mutex, RwLock};
+use tokio::time::Instant;
+
+/// What a table was opened with: the code players join it by, its rules and its password.
+/// Tables the matchmaking queue opens are seated by the queue alone and never listed. They are
+/// the ranked ones, played in a tier; lobby rooms are casual and leave ratings alone. Tournament
+/// tables are seated by their tournament alone and are not ranked either.
+pub struct Room {
+    pub code: String,
+    pub ruleset: Ruleset,
+    password: Option<String>,
+    pub matchmade: bool,
+    pub tier: Option<Tier>,      // Set for ranked tables only.
+    pub tournament: Option<u32>, // Set for tournament tables only.
+}
+
+impl Room {
+    pub fn new(code: String, ruleset: Ruleset, password: Option<String>) -> Self {
+        Self {
+            code,
+            ruleset,
+            password,
+            matchmade: false,
+            tier: None,
+            tournament: None,
+        }
+    }
+
+    pub fn matchmade(code: String, ruleset: Ruleset, tier: Tier) -> Self {
+        Self {
+            code,
+            ruleset,
+            password: None,
+            matchmade: true,
+            tier: Some(tier),
+            tournament: None,
+        }
+    }
+
+    pub fn tournament(code: String, ruleset: Ruleset, tournament: u32) -> Self {
+        Self {
+            code,
+            ruleset,
+            password: None,
+            matchmade: false,
+            tier: None,
+            tournament: Some(tournament),
+        }
+    }
+
+    // Lobby rooms are listed and joined by code; queue and tournament tables are not.
+    pub fn listed(&self) -> bool {
+        return !self.matchmade && self.tournament.is_none();
+    }
+
+    pub fn locked(&self) -> bool {
+        return self.password.is_some();
+    }
+
+    // Open rooms admit anyone, locked ones only the right password.
+    pub fn admits(&self, password: Option<&str>) -> bool {
+        match &self.password {
+            None => true,
+            Some(expected) => password == Some(expected.as_str()),
+        }
+    }
+}
+
+/// Every table the server hosts, by table id. Tables are opened from the lobby (or by quick
+/// play when no open room has a free seat) or by the matchmaking queue, and closed a while after their match finished or
+/// was interrupted. Tables everybody left are interrupted.
+pub struct MatchRegistry {
+    pub logger: Arc<Lolg>,
+    pub tables: TablePool,
+    pub results: Arc<ResultStore>, // Finished matches, written by the tables they were played at.
+    pub accounts: Arc<AccountStore>, // Ranked tables update the standings of their accounts.
+    config: Config,
+    table_id: Arc<Mutex<i32>>, // Id tracker for tables, never reused while the server runs.
+}
+
+impl MatchRegistry {
+    pub fn new(
+        logger: Arc<Lolg>,
+        config: &Config,
+        results: Arc<ResultStore>,
+        accounts: Arc<AccountStore>,
+    ) -> Arc<Self> {
+        Arc::new(Self {
+            logger,
+            results,
+            accounts,
+            tables: Arc::new(RwLock::new(HashMap::new())),
+            config: config.clone(),
+            table_id: Arc::new(Mutex::new(0)),
+        })
+    }
+
+    // Opens a lobby room, or a ranked table of the matchmaking queue when given a tier. Ranked
+    // tables get no password and are left out of the lobby.
+    pub async fn open(
+        self: &Arc<Self>,
+        ruleset: Ruleset,
+        password: Option<String>,
+        ranked: Option<Tier>,
+    ) -> Result<Arc<Protocol>, Error> {
+        return self
+            .host(|code| match ranked {
+                Some(tier) => Room::matchmade(code, ruleset, tier),
+                None => Room::new(code, ruleset, password),
+            })
+            .await;
+    }
+
+    // Opens a table of a tournament round, left out of the lobby.
+    pub async fn open_tournament(
+        self: &Arc<Self>,
+        ruleset: Ruleset,
+        tournament: u32,
+    ) -> Result<Arc<Protocol>, Error> {
+        return self
+            .host(|code| Room::tournament(code, ruleset, tournament))
+            .await;
+    }
+
+    // Opens a table with its own match, event log and Lua state, and starts watching it.
+    async fn host(
+        self: &Arc<Self>,
+        room: impl FnOnce(String) -> Room,
+    ) -> Result<Arc<Protocol>, Error> {
+        let mut tables = self.tables.write().await;
+        if tables.len() >= self.config.tables.max {
+            return Err(Error::ConnectionFailed(66));
+        }
+
+        let id = {
+            let mut id = self.table_id.lock().await;
+            *id += 1;
+            *id
+        };
+        let code = loop {
+            let code = room_code()?;
+            if !tables.values().any(|table| table.room.code == code) {
+                break code;
+            }
+        };
+        let table = Protocol::new(
+            id,
+            room(code),
+            Arc::clone(&self.logger),
+            &self.config,
+            Arc::clone(&self.results),
+            Arc::clone(&self.accounts),
+        )
+        .await?;
+        tables.insert(id, Arc::clone(&table));
+        drop(tables);
+
+        self.logger.info(&format!("table {id}: opened")).await;
+        tokio::spawn(Arc::clone(self).watch(Arc::clone(&table)));
+        return Ok(table);
+    }
+
+    // Seats the player at the oldest open room with the default rules that is still waiting
+    // for players, opening one when all are full.
+    pub async fn quick_play(
+        self: &Arc<Self>,
+        req: &JoinRequest,
+    ) -> Result<(Arc<Protocol>, Arc<Player>, SessionToken), Error> {
+        for table in self.waiting().await {
+            if table.room.locked() || table.room.ruleset != Ruleset::default() {
+                continue;
+            }
+            match table.seat(req).await {
+                Err(Error::NoAvailableSeats) => continue,
+                Err(error) => return Err(error),
+                Ok((player, token)) => return Ok((table, player, token)),
+            }
+        }
+
+        return self.create(Ruleset::default(), None, req).await;
+    }
+
+    // Opens a room and seats its creator.
+    pub async fn create(
+        self: &Arc<Self>,
+        ruleset: Ruleset,
+        password: Option<String>,
+        req: &JoinRequest,
+    ) -> Result<(Arc<Protocol>, Arc<Player>, SessionToken), Error> {
+        let table = self.open(ruleset, password, None).await?;
+        let (player, token) = table.seat(req).await?;
+        return Ok((table, player, token));
+    }
+
+    // Seats the player at the room with the given code, if they know its password.
+    pub async fn join(
+        &self,
+        code: &str,
+        password: Option<&str>,
+        req: &JoinRequest,
+    ) -> Result<(Arc<Protocol>, Arc<Player>, SessionToken), Error> {
+        let table = self
+            .tables
+            .read()
+            .await
+            .values()
+            .find(|table| table.room.code == code && table.room.listed())
+            .cloned()
+            .ok_or(Error::ConnectionFailed(68))?;
+        if !table.room.admits(password) {
+            return Err(Error::ConnectionFailed(69));
+        }
+        if *table.status().borrow() != MatchStatus::Waiting {
+            return Err(Error::NoAvailableSeats);
+        }
+
+        let (player, token) = table.seat(req).await?;
+        return Ok((table, player, token));
+    }
+
+    // Rooms still waiting for players, oldest first. Locked rooms are listed but need their password.
+    pub async fn rooms(&self) -> Vec<RoomInfo> {
+        let mut rooms = Vec::new();
+        for table in self.waiting().await {
+            let room = table.room_info().await;
+            if room.seated < room.seats {
+                rooms.push(room);
+            }
+        }
+        return rooms;
+    }
+
+    // Finds the table that issued the session token. Tokens are random, so only one table knows it.
+    pub async fn reconnect(
+        &self,
+        packet: &Packet,
+    ) -> Result<(Arc<Protocol>, Arc<Client>, SessionToken, Handshake), Error> {
+        let tables: Vec<Arc<Protocol>> = self.tables.read().await.values().cloned().collect();
+        for table in tables {
+            match table.handle_reconnect(packet).await {
+                Err(Error::ReconnectionFailed(59)) => continue,
+                Err(error) => return Err(error),
+                Ok((client, token, handshake)) => {
+                    return Ok((table, client, token, handshake));
+                }
+            }
+        }
+
+        return Err(Error::ReconnectionFailed(59));
+    }
+
+    // Spectate bodies may name the table after the mode, and a locked room's password after
+    // that: | mode | table id (i32)? | password (utf-8)? |
+    // Without a table the oldest one anybody may watch is picked. Queue and tournament tables
+    // are only watched with `spectator.unlisted` on.
+    pub async fn spectate(&self, packet: &Packet) -> Result<Arc<Protocol>, Error> {
+        let bytes = packet
+            .body
+            .get(4 + Handshake::SIZE..)
+            .ok_or(Error::ConnectionFailed(54))?;
+        let mode = SpectatorMode::parse(bytes)?;
+        let target = &bytes[mode.bytes().len()..];
+        let table_id = target
+            .get(..4)
+            .map(|id| i32::from_le_bytes([id[0], id[1], id[2], id[3]]));
+        let password = match target.get(4..) {
+            None | Some([]) => None,
+            Some(password) => {
+                Some(std::str::from_utf8(password).map_err(|_| Error::ConnectionFailed(54))?)
+            }
+        };
+
+        let tables = self.tables.read().await;
+        let watchable = |table: &Protocol| table.room.listed() || self.config.spectator.unlisted;
+        let Some(id) = table_id else {
+            return tables
+                .values()
+                .filter(|table| watchable(table) && !table.room.locked())
+                .min_by_key(|table| table.table_id)
+                .cloned()
+                .ok_or(Error::ConnectionFailed(67));
+        };
+
+        let table = tables.get(&id).ok_or(Error::ConnectionFailed(67))?;
+        if !watchable(table) {
+            return Err(Error::ConnectionFailed(87));
+        }
+        if !table.room.admits(password) {
+            return Err(Error::ConnectionFailed(69));
+        }
+        return Ok(Arc::clone(table));
+    }
+
+    // Lobby tables whose match has not started, oldest first.
+    async fn waiting(&self) -> Vec<Arc<Protocol>> {
+        let mut waiting: Vec<Arc<Protocol>> = self
+            .tables
+            .read()
+            .await
+            .values()
+            .filter(|table| table.room.listed())
+            .filter(|table| *table.status().borrow() == MatchStatus::Waiting)
+            .cloned()
+            .collect();
+        waiting.sort_by_key(|table| table.table_id);
+        return waiting;
+    }
+
+    // Closes the table once its match is over, leaving the players `tables.linger_ms` to read the results.
+    // Every session grace period the table is checked for players, and interrupted once abandoned.
+    async fn watch(self: Arc<Self>, table: Arc<Protocol>) {
+        let mut status = table.status();
+        let grace = self.config.session.grace().max(Duration::from_secs(1));
+        let mut check = tokio::time::interval_at(Instant::now() + grace, grace);
+        loop {
+            let current = *status.borrow_and_update();
+            if matches!(current, MatchStatus::Finished | MatchStatus::Interrupted) {
+                break;
+            }
+            tokio::select! {
+                changed = status.changed() => {
+                    if changed.is_err() {
+                        break;
+                    }
+                }
+                _ = check.tick() => {
+                    if table.abandoned().await {
+                        let log_msg = format!("table {}: abandoned", table.table_id);
+                        self.logger.info(&log_msg).await;
+                        table.interrupt().await;
+                    }
+                }
+            }
+        }
+
+        tokio::time::sleep(self.config.tables.linger()).await;
+        self.tables.write().await.remove(&table.table_id);
+        table.close().await;
+        let log_msg = format!("table {}: closed", table.table_id);
+        self.logger.info(&log_msg).await;
+    }
+}
+
+// Random room code. 32 letters divide 256 evenly, so every letter is as likely.
+fn room_code() -> Result<String, Error> {
+    let mut bytes = [0; ROOM_CODE_SIZE];
+    getrandom::fill(&mut bytes).map_err(|_| Error::InternalError)?;
+    let code = bytes
+        .iter()
+        .map(|byte| ROOM_CODE_ALPHABET[*byte as usize % ROOM_CODE_ALPHABET.len()] as char)
+        .collect();
+    return Ok(code);
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use crate::protocol::packet::PacketKind;
+    use std::path::PathBuf;
+
+    // A registry whose results, accounts and paifu are kept in a temporary directory of its own,
+    // removed again once it is dropped.
+    pub(crate) struct TestRegistry {
+        pub registry: Arc<MatchRegistry>,
+        pub logger: Arc<Lolg>,
+        dir: PathBuf,
+    }
+
+    impl TestRegistry {
+        pub(crate) async fn open(name: &str) -> Self {
+            let dir = std::env::temp_dir().join(format!("mahjong-{name}-{}", std::process::id()));
+            let _ = std::fs::remove_dir_all(&dir);
+            let path = |file: &str| dir.join(file).to_string_lossy().into_owned();
+            let config = Config {
+                paifu_dir: Some(path("paifu")),
+                results_path: path("results.jsonl"),
+                ..Config::default()
+            };
+            let results = ResultStore::load(&config.results_path, config.ranking).unwrap();
+            let accounts = AccountStore::load(&path("accounts.json"), &path("seasons")).unwrap();
+            let logger = Lolg::init(0, false).await.unwrap();
+            let registry = MatchRegistry::new(
+                Arc::clone(&logger),
+                &config,
+                Arc::new(results),
+                Arc::new(accounts),
+            );
+            return Self {
+                registry,
+                logger,
+                dir,
+            };
+        }
+
+        pub(crate) fn path(&self, file: &str) -> String {
+            return self.dir.join(file).to_string_lossy().into_owned();
+        }
+    }
+
+    impl Drop for TestRegistry {
+        fn drop(&mut self) {
+            let _ = std::fs::remove_dir_all(&self.dir);
+        }
+    }
+
+    #[test]
+    fn room_codes_and_passwords() {
+        let code = room_code().unwrap();
+        assert_eq!(code.len(), ROOM_CODE_SIZE);
+        assert!(code.bytes().all(|c| ROOM_CODE_ALPHABET.contains(&c)));
+
+        let open = Room::new(code.clone(), Ruleset::Hanchan, None);
+        assert!(!open.locked());
+        assert!(open.admits(None) && open.admits(Some("anything")));
+
+        let locked = Room::new(code, Ruleset::Sanma, Some("hunter2".to_string()));
+        assert!(locked.locked());
+        assert!(locked.admits(Some("hunter2")));
+        assert!(!locked.admits(Some("hunter3")));
+        assert!(!locked.admits(None));
+
+        assert!(open.listed());
+        assert!(!Room::tournament(room_code().unwrap(), Ruleset::Hanchan, 1).listed());
+    }
+
+    #[tokio::test]
+    async fn spectators_need_the_password_and_a_listed_table() {
+        let test = TestRegistry::open("spectate").await;
+        let registry = &test.registry;
+        let ranked = registry
+            .open(Ruleset::Sanma, None, Some(Tier::General))
+            .await;
+        let password = Some("hunter2".to_string());
+        let locked = registry.open(Ruleset::Sanma, password, None).await;
+        let open = registry.open(Ruleset::Sanma, None, None).await;
+        let (ranked, locked, open) = (ranked.unwrap(), locked.unwrap(), open.unwrap());
+
+        // | setup | handshake | public mode | table id? | password? |
+        let spectate = |target: &[u8]| {
+            let mut body = vec![0; 4 + Handshake::SIZE];
+            body.push(0);
+            body.extend(target);
+            Packet::create(1, PacketKind::Setup, &body)
+        };
+        let table = |table: &Protocol, password: &str| {
+            let mut target = table.table_id.to_le_bytes().to_vec();
+            target.extend(password.as_bytes());
+            spectate(&target)
+        };
+        let watched = registry.spectate(&spectate(&[])).await.unwrap();
+        assert_eq!(watched.table_id, open.table_id);
+        let watched = registry.spectate(&table(&locked, "hunter2")).await.unwrap();
+        assert_eq!(watched.table_id, locked.table_id);
+        let refused = registry.spectate(&table(&locked, "")).await;
+        assert!(matches!(refused, Err(Error::ConnectionFailed(69))));
+        let refused = registry.spectate(&table(&ranked, "")).await;
+        assert!(matches!(refused, Err(Error::ConnectionFailed(87))));
+    }
+}
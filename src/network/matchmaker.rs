@@ -0,0 +1,367 @@
+use crate::game::player::Player;
+use crate::network::match_registry::MatchRegistry;
+use crate::protocol::lobby::{QueueState, Ruleset, Tier};
+use crate::protocol::protocol::Protocol;
+use crate::protocol::session::SessionToken;
+use crate::utils::config::MatchmakingConfig;
+use crate::utils::errors::Error;
+use crate::utils::models::JoinRequest;
+use lolg::Lolg;
+use std::sync::Arc;
+use tokio::sync::mpsc;
+use tokio::sync::{Mutex, RwLock};
+use tokio::time::Instant;
+
+/// Starting rating of accounts, and the rating of guests and bots.
+pub const DEFAULT_RATING: i32 = 1500;
+
+/// What the queue tells a waiting player: where they stand, or the seat it found them.
+pub enum QueueUpdate {
+    State(QueueState),
+    Seated(Result<(Arc<Protocol>, Arc<Player>, SessionToken), Error>),
+}
+
+/// A player waiting in the queue. The lobby connection holds the receiving end of `updates` and
+/// takes the ticket out when it ends; tickets whose receiver is gone are pruned every tick too.
+pub struct Ticket {
+    pub id: i32,
+    pub req: JoinRequest,
+    pub ruleset: Ruleset,
+    pub tier: Tier,
+    pub rating: i32,
+    joined: Instant,
+    updates: mpsc::Sender<QueueUpdate>,
+}
+
+impl Ticket {
+    // Tickets only share tables with tickets for the same rules and tier.
+    fn same_room(&self, other: &Ticket) -> bool {
+        return self.ruleset == other.ruleset && self.tier == other.tier;
+    }
+
+    fn state(&self, config: &MatchmakingConfig, waiting: usize, now: Instant) -> QueueState {
+        let waited = now.duration_since(self.joined);
+        return QueueState {
+            ruleset: self.ruleset,
+            tier: self.tier,
+            rating: self.rating,
+            band: config.band_after(waited),
+            waiting,
+            waited_ms: waited.as_millis() as u64,
+            bots_in_ms: config.bots_after().saturating_sub(waited).as_millis() as u64,
+        };
+    }
+}
+
+/// Forms tables out of queued players with close enough ratings. Every tick the oldest ticket
+/// looks for players of the same rules and tier within both their bands; a full table is opened right
+/// away, and a ticket that waited `bots_after_ms` is seated with whoever it found and bots.
+pub struct Matchmaker {
+    logger: Arc<Lolg>,
+    registry: Arc<MatchRegistry>,
+    config: MatchmakingConfig,
+    queue: Arc<RwLock<Vec<Ticket>>>, // Oldest ticket first.
+    ticket_id: Arc<Mutex<i32>>,
+}
+
+impl Matchmaker {
+    pub fn new(
+        logger: Arc<Lolg>,
+        registry: Arc<MatchRegistry>,
+        config: MatchmakingConfig,
+    ) -> Arc<Self> {
+        let matchmaker = Arc::new(Self {
+            logger,
+            registry,
+            config,
+            queue: Arc::new(RwLock::new(Vec::new())),
+            ticket_id: Arc::new(Mutex::new(0)),
+        });
+
+        tokio::spawn(Arc::clone(&matchmaker).run());
+        return matchmaker;
+    }
+
+    // Puts the player in the queue and sends them where they stand.
+    pub async fn enqueue(
+        &self,
+        req: &JoinRequest,
+        ruleset: Ruleset,
+        tier: Tier,
+        rating: i32,
+    ) -> (i32, mpsc::Receiver<QueueUpdate>) {
+        let id = {
+            let mut id = self.ticket_id.lock().await;
+            *id += 1;
+            *id
+        };
+        let (updates, receiver) = mpsc::channel(8);
+        let ticket = Ticket {
+            id,
+            req: req.clone(),
+            ruleset,
+            tier,
+            rating,
+            joined: Instant::now(),
+            updates,
+        };
+
+        let mut queue = self.queue.write().await;
+        queue.push(ticket);
+        let waiting = queue
+            .iter()
+            .filter(|t| t.same_room(&queue[queue.len() - 1]))
+            .count();
+        let ticket = queue.last().unwrap();
+        let state = ticket.state(&self.config, waiting, Instant::now());
+        let _ = ticket.updates.try_send(QueueUpdate::State(state));
+        return (id, receiver);
+    }
+
+    // Takes the ticket out of the queue. False when it was already gone (seated or never queued).
+    pub async fn leave(&self, ticket: i32) -> bool {
+        let mut queue = self.queue.write().await;
+        let before = queue.len();
+        queue.retain(|t| t.id != ticket);
+        return queue.len() != before;
+    }
+
+    async fn run(self: Arc<Self>) {
+        let mut interval = tokio::time::interval(self.config.tick());
+        loop {
+            interval.tick().await;
+            self.tick().await;
+        }
+    }
+
+    // Forms what tables it can and tells everyone left in the queue where they stand.
+    async fn tick(&self) {
+        let now = Instant::now();
+        let mut queue = self.queue.write().await;
+        // Dropped receivers mean the player left the lobby.
+        queue.retain(|ticket| !ticket.updates.is_closed());
+        let tables = form_tables(&queue, &self.config, now);
+        let mut table_of = vec![None; queue.len()];
+        for (table, (members, _)) in tables.iter().enumerate() {
+            for index in members {
+                table_of[*index] = Some(table);
+            }
+        }
+        let mut formed: Vec<(Vec<Ticket>, usize)> =
+            tables.iter().map(|(_, bots)| (Vec::new(), *bots)).collect();
+        let mut kept = Vec::new();
+        for (index, ticket) in queue.drain(..).enumerate() {
+            match table_of[index] {
+                Some(table) => formed[table].0.push(ticket),
+                None => kept.push(ticket),
+            }
+        }
+        *queue = kept;
+
+        for ticket in queue.iter() {
+            let waiting = queue.iter().filter(|t| t.same_room(ticket)).count();
+            let state = ticket.state(&self.config, waiting, now);
+            let _ = ticket.updates.try_send(QueueUpdate::State(state));
+        }
+        drop(queue);
+
+        for (tickets, bots) in formed {
+            self.seat(tickets, bots).await;
+        }
+    }
+
+    // Opens a table for the tickets and fills the rest of its seats with bots. A seat is only
+    // taken once there is room to tell its player; players who left meanwhile get a bot instead.
+    async fn seat(&self, tickets: Vec<Ticket>, mut bots: usize) {
+        let (ruleset, tier) = (tickets[0].ruleset, tickets[0].tier);
+        let table = match self.registry.open(ruleset, None, Some(tier)).await {
+            Err(error) => {
+                self.logger.error(&error.to_string()).await;
+                for ticket in tickets {
+                    let error = Error::ConnectionFailed(66);
+                    let _ = ticket.updates.send(QueueUpdate::Seated(Err(error))).await;
+                }
+                return;
+            }
+            Ok(table) => table,
+        };
+
+        let mut humans = 0;
+        for ticket in tickets {
+            let Ok(permit) = ticket.updates.reserve().await else {
+                bots += 1;
+                continue;
+            };
+            let seated = table
+                .seat(&ticket.req)
+                .await
+                .map(|(player, token)| (Arc::clone(&table), player, token));
+            humans += seated.is_ok() as usize;
+            permit.send(QueueUpdate::Seated(seated));
+        }
+        // Nobody is left to play with the bots; the registry closes the empty table.
+        if humans == 0 {
+            return;
+        }
+        for number in 1..=bots as i32 {
+            if let Err(error) = table.seat_bot(number).await {
+                self.logger.error(&error.to_string()).await;
+            }
+        }
+
+        let table_id = table.table_id;
+        let log_msg = format!("table {table_id}: matchmade {humans} players and {bots} bots");
+        self.logger.info(&log_msg).await;
+    }
+}
+
+// Tables the queue can form now, as ticket indices (ascending) and the bots to add.
+fn form_tables(
+    queue: &[Ticket],
+    config: &MatchmakingConfig,
+    now: Instant,
+) -> Vec<(Vec<usize>, usize)> {
+    let mut taken = vec![false; queue.len()];
+    let mut tables = Vec::new();
+    for (anchor, ticket) in queue.iter().enumerate() {
+        if taken[anchor] {
+            continue;
+        }
+
+        let seats = ticket.ruleset.seats().len();
+        let waited = now.duration_since(ticket.joined);
+        let band = config.band_after(waited);
+        let mut members = vec![anchor];
+        for (index, other) in queue.iter().enumerate().skip(anchor + 1) {
+            if members.len() == seats {
+                break;
+            }
+            if taken[index] || !other.same_room(ticket) {
+                continue;
+            }
+            let other_band = config.band_after(now.duration_since(other.joined));
+            if ticket.rating.abs_diff(other.rating) <= band.min(other_band) {
+                members.push(index);
+            }
+        }
+
+        if members.len() == seats || waited >= config.bots_after() {
+            for index in &members {
+                taken[*index] = true;
+            }
+            let bots = seats - members.len();
+            tables.push((members, bots));
+        }
+    }
+    return tables;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::network::match_registry::tests::TestRegistry;
+    use std::time::Duration;
+
+    fn ticket(id: i32, ruleset: Ruleset, rating: i32, waited_s: u64, now: Instant) -> Ticket {
+        return tiered(id, ruleset, Tier::General, rating, waited_s, now);
+    }
+
+    fn tiered(
+        id: i32,
+        ruleset: Ruleset,
+        tier: Tier,
+        rating: i32,
+        waited_s: u64,
+        now: Instant,
+    ) -> Ticket {
+        let (updates, _) = mpsc::channel(1);
+        Ticket {
+            id,
+            req: JoinRequest {
+                id,
+                alias: format!("p{id}"),
+            },
+            ruleset,
+            tier,
+            rating,
+            joined: now - Duration::from_secs(waited_s),
+            updates,
+        }
+    }
+
+    #[test]
+    fn band_widens_and_bots_fill() {
+        let config = MatchmakingConfig::default();
+        assert_eq!(config.band_after(Duration::ZERO), 100);
+        assert_eq!(config.band_after(Duration::from_secs(30)), 400);
+        assert_eq!(
+            config.band_after(Duration::from_secs(3_600)),
+            config.max_band
+        );
+
+        let now = Instant::now() + Duration::from_secs(120);
+        let queue = vec![
+            ticket(1, Ruleset::Hanchan, 1500, 5, now),
+            ticket(2, Ruleset::Hanchan, 1550, 5, now),
+            ticket(3, Ruleset::Hanchan, 1900, 5, now),
+            ticket(4, Ruleset::Sanma, 1500, 5, now),
+            ticket(5, Ruleset::Hanchan, 1450, 4, now),
+            ticket(6, Ruleset::Hanchan, 1520, 3, now),
+        ];
+        // 1900 is out of everyone's band, and nobody waited long enough for bots.
+        assert_eq!(
+            form_tables(&queue, &config, now),
+            vec![(vec![0, 1, 4, 5], 0)]
+        );
+
+        // After a minute the band reaches 1900 and the sanma ticket gets two bots.
+        let queue = vec![
+            ticket(1, Ruleset::Hanchan, 1500, 61, now),
+            ticket(2, Ruleset::Hanchan, 1900, 61, now),
+            ticket(3, Ruleset::Sanma, 1500, 60, now),
+            ticket(4, Ruleset::Sanma, 1200, 10, now),
+        ];
+        assert_eq!(
+            form_tables(&queue, &config, now),
+            vec![(vec![0, 1], 2), (vec![2], 2)]
+        );
+
+        // Tiers never share a table.
+        let queue = vec![
+            ticket(1, Ruleset::Sanma, 1500, 5, now),
+            tiered(2, Ruleset::Sanma, Tier::Expert, 1500, 5, now),
+            ticket(3, Ruleset::Sanma, 1500, 5, now),
+            ticket(4, Ruleset::Sanma, 1500, 5, now),
+        ];
+        assert_eq!(form_tables(&queue, &config, now), vec![(vec![0, 2, 3], 0)]);
+    }
+
+    #[tokio::test]
+    async fn leavers_are_pruned_before_tables_form() {
+        let test = TestRegistry::open("queue").await;
+        let registry = Arc::clone(&test.registry);
+        let config = MatchmakingConfig {
+            tick_ms: 3_600_000,
+            ..MatchmakingConfig::default()
+        };
+        let matchmaker = Matchmaker::new(Arc::clone(&test.logger), Arc::clone(&registry), config);
+
+        let mut tickets = Vec::new();
+        for id in 1..=3 {
+            let req = JoinRequest {
+                id,
+                alias: format!("p{id}"),
+            };
+            let ticket = matchmaker
+                .enqueue(&req, Ruleset::Sanma, Tier::General, DEFAULT_RATING)
+                .await;
+            tickets.push(ticket);
+        }
+
+        // The third player disconnected, so the other two keep waiting instead of being seated.
+        drop(tickets.pop());
+        matchmaker.tick().await;
+        assert!(registry.tables.read().await.is_empty());
+        assert_eq!(matchmaker.queue.read().await.len(), 2);
+    }
+}
@@ -1,4 +1,16 @@
+pub mod accounts;
 pub mod client;
 pub mod client_manager;
+pub mod leaderboard;
+pub mod match_registry;
+pub mod matchmaker;
+pub mod ranking;
+pub mod results;
 pub mod server;
-pub mod setup;
+pub mod session;
+pub mod spectator;
+pub mod tls;
+pub mod tournament;
+pub mod transport;
+
+pub use mahjong_protocol::setup;
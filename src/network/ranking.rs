@@ -0,0 +1,227 @@
+use crate::network::matchmaker::DEFAULT_RATING;
+use crate::protocol::lobby::{Profile, Ruleset, Tier};
+use serde::{Deserialize, Serialize};
+
+// A rank of the ladder: the points a player enters it with, the points that promote them out of
+// it (none at the top) and whether falling below zero drops them a rank.
+struct Step {
+    name: &'static str,
+    start: i32,
+    promotion: Option<i32>,
+    demotes: bool,
+}
+
+const fn step(name: &'static str, start: i32, promotion: i32, demotes: bool) -> Step {
+    return Step {
+        name,
+        start,
+        promotion: Some(promotion),
+        demotes,
+    };
+}
+
+// Tenhou's ladder: kyu ranks are never lost, dan ranks are once the points run out.
+const LADDER: [Step; 20] = [
+    step("novice", 0, 20, false),
+    step("9 kyu", 0, 20, false),
+    step("8 kyu", 0, 20, false),
+    step("7 kyu", 0, 20, false),
+    step("6 kyu", 0, 40, false),
+    step("5 kyu", 0, 60, false),
+    step("4 kyu", 0, 80, false),
+    step("3 kyu", 0, 100, false),
+    step("2 kyu", 0, 100, false),
+    step("1 kyu", 0, 100, false),
+    step("1 dan", 200, 400, true),
+    step("2 dan", 400, 800, true),
+    step("3 dan", 600, 1200, true),
+    step("4 dan", 800, 1600, true),
+    step("5 dan", 1000, 2000, true),
+    step("6 dan", 1200, 2400, true),
+    step("7 dan", 1400, 2800, true),
+    step("8 dan", 1600, 3200, true),
+    step("9 dan", 1800, 3600, true),
+    Step {
+        name: "10 dan",
+        start: 2000,
+        promotion: None,
+        demotes: true,
+    },
+];
+
+// Rank points for first and second place, by tier. Third place (of four) scores nothing and
+// last place loses its rank's penalty.
+const FIRST: [i32; 4] = [20, 40, 50, 60];
+const SECOND: [i32; 4] = [10, 10, 20, 30];
+
+/// Where an account stands on the ranked ladder, kept with the account.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Standing {
+    pub rating: f64,
+    pub rank: usize, // Index into the ladder, 0 being novice.
+    pub points: i32,
+    pub games: u32,
+}
+
+impl Default for Standing {
+    fn default() -> Self {
+        Self {
+            rating: DEFAULT_RATING as f64,
+            rank: 0,
+            points: 0,
+            games: 0,
+        }
+    }
+}
+
+impl Standing {
+    pub fn rating(&self) -> i32 {
+        return self.rating.round() as i32;
+    }
+
+    // Advanced rooms take players from 1 kyu, expert from 4 dan and phoenix from 7 dan.
+    pub fn admits(&self, tier: Tier) -> bool {
+        let required = match tier {
+            Tier::General => 0,
+            Tier::Advanced => 9,
+            Tier::Expert => 13,
+            Tier::Phoenix => 16,
+        };
+        return self.rank >= required;
+    }
+
+    pub fn rank_name(&self) -> &'static str {
+        return LADDER[self.rank.min(LADDER.len() - 1)].name;
+    }
+
+    pub fn profile(&self, player_id: i32, guest: bool) -> Profile {
+        let step = &LADDER[self.rank.min(LADDER.len() - 1)];
+        return Profile {
+            player_id,
+            guest,
+            rating: self.rating(),
+            rank: step.name.to_string(),
+            points: self.points,
+            promotion: step.promotion,
+            ranked_games: self.games,
+            tiers: Tier::ALL
+                .into_iter()
+                .filter(|tier| self.admits(*tier))
+                .collect(),
+        };
+    }
+
+    // Adds the rank points and moves up or down the ladder when they cross its thresholds.
+    fn climb(&mut self, points: i32) {
+        self.points += points;
+        let step = &LADDER[self.rank];
+        if step
+            .promotion
+            .is_some_and(|promotion| self.points >= promotion)
+        {
+            self.rank += 1;
+            self.points = LADDER[self.rank].start;
+        } else if self.points < 0 {
+            match step.demotes {
+                true => {
+                    self.rank -= 1;
+                    self.points = LADDER[self.rank].start;
+                }
+                false => self.points = 0,
+            }
+        }
+    }
+}
+
+// Rank points for a placement (1 is first). Dan ranks lose 10 points a dan for last place, and
+// matches with south rounds (hanchan, sanma) are worth half as much again as tonpuusen.
+fn rank_points(ruleset: Ruleset, tier: Tier, rank: usize, placement: u8) -> i32 {
+    let tier = tier.id() as usize - 1;
+    let last = ruleset.seats().len() as u8;
+    let penalty = 10 * rank.saturating_sub(9) as i32;
+    let points = match placement {
+        1 => FIRST[tier],
+        _ if placement == last => -penalty,
+        2 if last == 4 => SECOND[tier],
+        _ => 0,
+    };
+    match ruleset {
+        Ruleset::Tonpuusen => return points,
+        Ruleset::Hanchan | Ruleset::Sanma => return points * 3 / 2,
+    }
+}
+
+/// The standings of a ranked table after its match, in the order given. Ratings move by Elo over
+/// every pair of players (each pair is a game won by the better placed one), rank points by the
+/// ladder of the table's tier.
+pub fn rate(
+    ruleset: Ruleset,
+    tier: Tier,
+    k_factor: f64,
+    seats: &[(u8, Standing)],
+) -> Vec<Standing> {
+    let opponents = seats.len().saturating_sub(1).max(1) as f64;
+    return seats
+        .iter()
+        .map(|(placement, standing)| {
+            let mut delta = 0.0;
+            for (other_placement, other) in seats {
+                if other_placement == placement {
+                    continue;
+                }
+                let expected = 1.0 / (1.0 + 10f64.powf((other.rating - standing.rating) / 400.0));
+                let actual = if placement < other_placement {
+                    1.0
+                } else {
+                    0.0
+                };
+                delta += actual - expected;
+            }
+
+            let mut standing = *standing;
+            standing.rating += k_factor / opponents * delta;
+            standing.games += 1;
+            standing.climb(rank_points(ruleset, tier, standing.rank, *placement));
+            return standing;
+        })
+        .collect();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn at(rank: usize, points: i32) -> Standing {
+        Standing {
+            rank,
+            points,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn ladder_and_ratings() {
+        let seats: Vec<(u8, Standing)> = (1..=4).map(|place| (place, at(0, 0))).collect();
+        let rated = rate(Ruleset::Hanchan, Tier::General, 32.0, &seats);
+        // Equal ratings: first gains what last loses, and the sum stays the same.
+        assert!(rated[0].rating > 1500.0 && rated[3].rating < 1500.0);
+        assert!((rated.iter().map(|s| s.rating).sum::<f64>() - 6000.0).abs() < 1e-9);
+        // 30 points promote a novice, kyu ranks never lose points.
+        assert_eq!((rated[0].rank, rated[0].points), (1, 0));
+        assert_eq!((rated[1].rank, rated[1].points), (0, 15));
+        assert_eq!((rated[3].rank, rated[3].points), (0, 0));
+
+        // A 2 dan with 10 points drops to 1 dan on a last place (-30 in hanchan).
+        let mut standing = at(11, 10);
+        standing.climb(rank_points(Ruleset::Hanchan, Tier::Expert, 11, 4));
+        assert_eq!((standing.rank, standing.points), (10, 200));
+        assert_eq!(rank_points(Ruleset::Sanma, Tier::Phoenix, 16, 2), 0);
+        assert_eq!(rank_points(Ruleset::Tonpuusen, Tier::Phoenix, 16, 4), -70);
+
+        assert!(at(9, 0).admits(Tier::Advanced) && !at(9, 0).admits(Tier::Expert));
+        let profile = at(19, 2500).profile(3, false);
+        assert_eq!(profile.rank, "10 dan");
+        assert_eq!(profile.promotion, None);
+        assert_eq!(profile.tiers, Tier::ALL.to_vec());
+    }
+}
@@ -0,0 +1,365 @@
+use crate::game::enums::Seat;
+use crate::network::accounts::GUEST_ID_BASE;
+use crate::protocol::codec::Codec;
+use crate::protocol::lobby::{Ruleset, Tier};
+use crate::protocol::packet::{Packet, PacketKind};
+use crate::protocol::stats::{PlayerStats, StatsQuery};
+use crate::utils::config::RankingConfig;
+use crate::utils::errors::Error;
+use crate::utils::models::HandResult;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::io::Write;
+use std::path::PathBuf;
+use std::sync::Arc;
+use tokio::sync::RwLock;
+
+/// How one seat ended a match.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct SeatResult {
+    pub player_id: i32,
+    pub alias: String,
+    pub seat: Seat,
+    pub score: i32,
+    pub placement: u8, // 1 is first.
+}
+
+/// A finished match as written to the results file: final scores and placements, the rules,
+/// the seed the wall was shuffled from and how every hand ended.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct MatchRecord {
+    pub table_id: i32,
+    pub ruleset: Ruleset,
+    #[serde(default)]
+    pub tier: Option<Tier>, // Ranked matches only.
+    pub seed: u64,
+    pub finished: u64, // Seconds since the Unix epoch.
+    pub players: Vec<SeatResult>,
+    pub hands: Vec<HandResult>,
+}
+
+impl MatchRecord {
+    // Places the seats by score. Ties go to the seat closest to east, as in riichi rules.
+    pub fn new(
+        table_id: i32,
+        ruleset: Ruleset,
+        tier: Option<Tier>,
+        seed: u64,
+        mut players: Vec<SeatResult>,
+        hands: Vec<HandResult>,
+    ) -> Self {
+        let order = |seat: &Seat| ruleset.seats().iter().position(|s| s == seat);
+        players.sort_by(|a, b| {
+            b.score
+                .cmp(&a.score)
+                .then(order(&a.seat).cmp(&order(&b.seat)))
+        });
+        for (place, player) in players.iter_mut().enumerate() {
+            player.placement = place as u8 + 1;
+        }
+
+        let finished = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map_or(0, |elapsed| elapsed.as_secs());
+        return Self {
+            table_id,
+            ruleset,
+            tier,
+            seed,
+            finished,
+            players,
+            hands,
+        };
+    }
+}
+
+// Running sums a player's statistics are derived from.
+#[derive(Default)]
+struct StatTotals {
+    matches: u32,
+    placements: u32,
+    hands: u32,
+    wins: u32,
+    win_value: i64,
+    deal_ins: u32,
+    riichis: u32,
+    calls: u32,
+    outcomes: u32, // Hands that recorded a win, deal-in, riichi or call, of anyone at the table.
+}
+
+/// An account's ranked games in one season under one ruleset.
+#[derive(Clone, Copy, Default, Debug, PartialEq)]
+pub struct Placings {
+    pub games: u32,
+    pub placements: u32, // Sum of the placements, 1 being first.
+}
+
+impl Placings {
+    pub fn average(&self) -> f64 {
+        match self.games {
+            0 => return 0.0,
+            games => return self.placements as f64 / games as f64,
+        }
+    }
+}
+
+// Ranked placings by season and ruleset, then by account.
+type Boards = HashMap<(u32, Ruleset), HashMap<i32, Placings>>;
+
+/// Finished matches, appended to a JSON lines file. Per player totals and the placings of ranked
+/// matches by season are rebuilt from it on startup and kept in memory, for accounts only: guest
+/// ids are not kept between sessions and bots are not players.
+pub struct ResultStore {
+    path: PathBuf,
+    ranking: RankingConfig, // Seasons the placings are counted in.
+    totals: Arc<RwLock<HashMap<i32, StatTotals>>>,
+    boards: Arc<RwLock<Boards>>,
+}
+
+impl ResultStore {
+    // A missing file is an empty store; a file that exists but cannot be read or parsed is an error.
+    pub fn load(path: &str, ranking: RankingConfig) -> Result<Self, Error> {
+        let mut totals = HashMap::new();
+        let mut boards = HashMap::new();
+        match std::fs::read_to_string(path) {
+            Err(error) if error.kind() == std::io::ErrorKind::NotFound => {}
+            Err(_) => return Err(Error::InitializationFailed(15)),
+            Ok(content) => {
+                for line in content.lines().filter(|line| !line.trim().is_empty()) {
+                    let record: MatchRecord =
+                        serde_json::from_str(line).map_err(|_| Error::InitializationFailed(15))?;
+                    tally(&mut totals, &record);
+                    place(&mut boards, &record, &ranking);
+                }
+            }
+        }
+
+        return Ok(Self {
+            path: PathBuf::from(path),
+            ranking,
+            totals: Arc::new(RwLock::new(totals)),
+            boards: Arc::new(RwLock::new(boards)),
+        });
+    }
+
+    // Appends the match to the file and counts it towards its players' statistics.
+    pub async fn record(&self, record: &MatchRecord) -> Result<(), Error> {
+        let mut line = serde_json::to_string(record).map_err(|_| Error::SerializationFailed(15))?;
+        line.push('\n');
+
+        let mut totals = self.totals.write().await;
+        if let Some(dir) = self.path.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+            std::fs::create_dir_all(dir).map_err(|_| Error::SerializationFailed(15))?;
+        }
+        std::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)
+            .and_then(|mut file| file.write_all(line.as_bytes()))
+            .map_err(|_| Error::SerializationFailed(15))?;
+        tally(&mut totals, record);
+        place(&mut *self.boards.write().await, record, &self.ranking);
+        return Ok(());
+    }
+
+    // Answers a stats query: | player id (i32)? |, the sender's own statistics without one.
+    pub async fn answer(&self, packet: &Packet, sender: i32, codec: Codec) -> Packet {
+        let query = match StatsQuery::parse(&packet.body) {
+            Err(error) => return Packet::error(packet.id, error),
+            Ok(query) => query,
+        };
+        let stats = self.stats(query.player_id.unwrap_or(sender)).await;
+        match codec.encode(&stats) {
+            Err(error) => return Packet::error(packet.id, error),
+            Ok(bytes) => return Packet::create(packet.id, PacketKind::Stats, &bytes),
+        }
+    }
+
+    // Statistics of the player, all zero when they never finished a match. Rates built from hand
+    // outcomes are left out while none of their hands recorded one, rather than reported as zeros.
+    pub async fn stats(&self, player_id: i32) -> PlayerStats {
+        let totals = self.totals.read().await;
+        let Some(totals) = totals.get(&player_id) else {
+            return PlayerStats {
+                player_id,
+                ..Default::default()
+            };
+        };
+
+        let share = |count: u32, of: u32| match of {
+            0 => 0.0,
+            of => count as f64 / of as f64,
+        };
+        let recorded = |count: u32| match totals.outcomes {
+            0 => None,
+            _ => Some(share(count, totals.hands)),
+        };
+        return PlayerStats {
+            player_id,
+            matches: totals.matches,
+            hands: totals.hands,
+            win_rate: recorded(totals.wins),
+            deal_in_rate: recorded(totals.deal_ins),
+            riichi_rate: recorded(totals.riichis),
+            call_rate: recorded(totals.calls),
+            average_placement: share(totals.placements, totals.matches),
+            average_win_value: match totals.wins {
+                0 => None,
+                wins => Some(totals.win_value as f64 / wins as f64),
+            },
+        };
+    }
+
+    // Ranked placings of the season by account, under one ruleset or summed over all of them.
+    pub async fn placings(&self, season: u32, ruleset: Option<Ruleset>) -> HashMap<i32, Placings> {
+        let boards = self.boards.read().await;
+        let mut placings: HashMap<i32, Placings> = HashMap::new();
+        let boards = boards
+            .iter()
+            .filter(|((s, r), _)| *s == season && ruleset.is_none_or(|ruleset| ruleset == *r));
+        for (_, board) in boards {
+            for (id, board_placings) in board {
+                let total = placings.entry(*id).or_default();
+                total.games += board_placings.games;
+                total.placements += board_placings.placements;
+            }
+        }
+        return placings;
+    }
+}
+
+fn is_account(player_id: i32) -> bool {
+    return player_id > 0 && player_id < GUEST_ID_BASE;
+}
+
+// Only ranked matches count towards the boards, in the season they finished in.
+fn place(boards: &mut Boards, record: &MatchRecord, ranking: &RankingConfig) {
+    if record.tier.is_none() {
+        return;
+    }
+
+    let season = ranking.season_at(record.finished);
+    let board = boards.entry((season, record.ruleset)).or_default();
+    for player in record.players.iter().filter(|p| is_account(p.player_id)) {
+        let placings = board.entry(player.player_id).or_default();
+        placings.games += 1;
+        placings.placements += player.placement as u32;
+    }
+}
+
+// Only accounts get statistics.
+fn tally(totals: &mut HashMap<i32, StatTotals>, record: &MatchRecord) {
+    for player in &record.players {
+        if !is_account(player.player_id) {
+            continue;
+        }
+
+        let id = player.player_id;
+        let player_totals = totals.entry(id).or_default();
+        player_totals.matches += 1;
+        player_totals.placements += player.placement as u32;
+        for hand in &record.hands {
+            player_totals.hands += 1;
+            if let Some(win) = hand.wins.iter().find(|win| win.player_id == id) {
+                player_totals.wins += 1;
+                player_totals.win_value += win.value as i64;
+            }
+            player_totals.deal_ins += (hand.dealt_in == Some(id)) as u32;
+            player_totals.riichis += hand.riichi.contains(&id) as u32;
+            player_totals.calls += hand.called.contains(&id) as u32;
+            player_totals.outcomes += (*hand != HandResult::default()) as u32;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::models::Win;
+
+    fn seat(player_id: i32, seat: Seat, score: i32) -> SeatResult {
+        SeatResult {
+            player_id,
+            alias: format!("Player {player_id}"),
+            seat,
+            score,
+            placement: 0,
+        }
+    }
+
+    #[tokio::test]
+    async fn records_and_stats() {
+        let dir = std::env::temp_dir().join(format!("mahjong-results-{}", std::process::id()));
+        let path = dir.join("results.jsonl");
+        let path = path.to_str().unwrap();
+        let _ = std::fs::remove_file(path);
+
+        let hands = vec![
+            HandResult {
+                wins: vec![Win {
+                    player_id: 1,
+                    value: 8_000,
+                    tsumo: false,
+                }],
+                dealt_in: Some(2),
+                riichi: vec![1],
+                called: vec![2],
+            },
+            HandResult::default(),
+        ];
+        // East and north tie, east places higher.
+        let players = vec![
+            seat(2, Seat::North, 20_000),
+            seat(1, Seat::South, 38_000),
+            seat(-1, Seat::West, 22_000),
+            seat(GUEST_ID_BASE + 1, Seat::East, 20_000),
+        ];
+        let record = MatchRecord::new(1, Ruleset::Hanchan, None, 7, players, hands);
+        let placed: Vec<i32> = record.players.iter().map(|p| p.player_id).collect();
+        assert_eq!(placed, vec![1, -1, GUEST_ID_BASE + 1, 2]);
+
+        let ranking = RankingConfig::default();
+        let store = ResultStore::load(path, ranking).unwrap();
+        store.record(&record).await.unwrap();
+        let mut ranked = record.clone();
+        ranked.tier = Some(Tier::General);
+        store.record(&ranked).await.unwrap();
+
+        // Totals are rebuilt from the file after a restart.
+        let store = ResultStore::load(path, ranking).unwrap();
+        let winner = store.stats(1).await;
+        assert_eq!((winner.matches, winner.hands), (2, 4));
+        assert_eq!(winner.win_rate, Some(0.5));
+        assert_eq!(winner.riichi_rate, Some(0.5));
+        assert_eq!(winner.average_placement, 1.0);
+        assert_eq!(winner.average_win_value, Some(8_000.0));
+        let loser = store.stats(2).await;
+        assert_eq!(loser.deal_in_rate, Some(0.5));
+        assert_eq!(loser.call_rate, Some(0.5));
+        assert_eq!(loser.average_win_value, None);
+        assert_eq!(loser.average_placement, 4.0);
+        assert_eq!(store.stats(-1).await.matches, 0);
+        assert_eq!(store.stats(GUEST_ID_BASE + 1).await.matches, 0);
+
+        // Only the ranked match is placed, in the season it finished in.
+        let season = ranking.season_at(record.finished);
+        let placings = store.placings(season, None).await;
+        assert_eq!(placings.len(), 2);
+        assert_eq!(
+            placings[&2],
+            Placings {
+                games: 1,
+                placements: 4
+            }
+        );
+        assert!(
+            store
+                .placings(season, Some(Ruleset::Sanma))
+                .await
+                .is_empty()
+        );
+        assert!(store.placings(season + 1, None).await.is_empty());
+
+        let _ = std::fs::remove_dir_all(dir);
+    }
+}
@@ -1,54 +1,244 @@
-use crate::{network::client_manager::ClientManager, utils::errors::Error};
+use crate::{
+    network::{
+        client_manager::ClientManager,
+        tls::load_acceptor,
+        transport::{split_stream, split_websocket},
+    },
+    utils::{config::Config, errors::Error},
+};
 use lolg::Lolg;
-use std::{net::Ipv4Addr, sync::Arc};
-use tokio::{net::TcpListener, sync::RwLock};
+use std::{net::SocketAddr, sync::Arc};
+use tokio::{
+    net::{TcpListener, TcpStream},
+    sync::RwLock,
+};
+use tokio_rustls::TlsAcceptor;
 
 pub struct Server {
     port: u16,
     pub logger: Arc<Lolg>,
     pub socket: Arc<TcpListener>,
+    pub websocket: Option<Arc<TcpListener>>,
+    pub tls: Option<TlsAcceptor>,
     pub running: Arc<RwLock<bool>>,
     pub client_manager: Arc<ClientManager>,
 }
 
 impl Server {
-    pub async fn create_instance(port: u16) -> Result<Arc<Server>, Error> {
-        let host = Ipv4Addr::new(127, 0, 0, 1);
-        let listener = TcpListener::bind((host, port))
-            .await
-            .map_err(|_| Error::InitializationFailed(4))?;
-
-        let lolg = Lolg::init(port + 1, true)
+    pub async fn create_instance(config: Config) -> Result<Arc<Server>, Error> {
+        let lolg = Lolg::init(config.port + 1, true)
             .await
             .map_err(|_| Error::InitializationFailed(5))?;
 
         Arc::clone(&lolg).listen().await;
-        let cm = ClientManager::new(Arc::clone(&lolg)).await?;
+        return Self::bind(config, lolg).await;
+    }
+
+    // Loads the TLS acceptor and binds the listeners, logging to an already started logger.
+    async fn bind(config: Config, lolg: Arc<Lolg>) -> Result<Arc<Server>, Error> {
+        let port = config.port;
+        let tls = match &config.tls {
+            None => None,
+            Some(tls_config) => Some(load_acceptor(tls_config)?),
+        };
+
+        let listener = TcpListener::bind((config.host, port))
+            .await
+            .map_err(|_| Error::InitializationFailed(4))?;
+
+        let websocket = match config.websocket_port {
+            None => None,
+            Some(ws_port) => Some(Arc::new(
+                TcpListener::bind((config.host, ws_port))
+                    .await
+                    .map_err(|_| Error::InitializationFailed(4))?,
+            )),
+        };
+
+        let cm = ClientManager::new(Arc::clone(&lolg), &config).await?;
 
         let server = Server {
             running: Arc::clone(&lolg.running),
             client_manager: Arc::new(cm),
             socket: Arc::new(listener),
             logger: lolg,
+            websocket,
             port,
+            tls,
         };
 
         return Ok(Arc::new(server));
     }
 
     pub async fn start(self: Arc<Self>) {
-        let log_msg = &format!("Server initialized on port {}", &self.port);
+        if let Some(websocket) = self.websocket.clone() {
+            Arc::clone(&self).listen_websocket(websocket).await;
+        }
+
+        let encryption = if self.tls.is_some() {
+            "TLS"
+        } else {
+            "plaintext"
+        };
+        let log_msg = &format!("Server initialized on port {} ({encryption})", &self.port);
         self.logger.debug(&log_msg).await;
         while *self.running.read().await {
             match self.socket.accept().await {
                 Err(_) => continue,
                 Ok((stream, addr)) => {
                     self.logger.debug(&format!("New client {addr}")).await;
-                    Arc::clone(&self.client_manager).accept(stream, addr).await;
+                    let server = Arc::clone(&self);
+                    tokio::spawn(async move { server.accept_stream(stream, addr).await });
                     continue;
                 }
             }
         }
         self.logger.debug("Server was closed").await;
     }
+
+    // Spawns the WebSocket accept loop. Clients end up in the same ClientManager as TCP ones.
+    async fn listen_websocket(self: Arc<Self>, listener: Arc<TcpListener>) {
+        tokio::spawn(async move {
+            if let Ok(addr) = listener.local_addr() {
+                let log_msg = format!("WebSocket listener initialized on port {}", addr.port());
+                self.logger.debug(&log_msg).await;
+            }
+
+            while *self.running.read().await {
+                let Ok((stream, addr)) = listener.accept().await else {
+                    continue;
+                };
+
+                // The handshakes run in their own task so a slow client does not hold the listener.
+                let server = Arc::clone(&self);
+                tokio::spawn(async move { server.accept_websocket(stream, addr).await });
+            }
+        });
+    }
+
+    async fn accept_stream(self: Arc<Self>, stream: TcpStream, addr: SocketAddr) {
+        let (reader, writer) = match &self.tls {
+            None => split_stream(stream),
+            Some(acceptor) => match acceptor.accept(stream).await {
+                Ok(tls_stream) => split_stream(tls_stream),
+                Err(error) => {
+                    let log_msg = format!("{addr}: TLS handshake failed: {error}");
+                    self.logger.error(&log_msg).await;
+                    return;
+                }
+            },
+        };
+
+        Arc::clone(&self.client_manager)
+            .accept(reader, writer, addr)
+            .await;
+    }
+
+    async fn accept_websocket(self: Arc<Self>, stream: TcpStream, addr: SocketAddr) {
+        let upgraded = match &self.tls {
+            None => tokio_tungstenite::accept_async(stream)
+                .await
+                .map(split_websocket),
+            Some(acceptor) => match acceptor.accept(stream).await {
+                Ok(tls_stream) => tokio_tungstenite::accept_async(tls_stream)
+                    .await
+                    .map(split_websocket),
+                Err(error) => {
+                    let log_msg = format!("{addr}: TLS handshake failed: {error}");
+                    self.logger.error(&log_msg).await;
+                    return;
+                }
+            },
+        };
+
+        match upgraded {
+            Err(error) => {
+                let log_msg = format!("{addr}: WebSocket upgrade failed: {error}");
+                self.logger.error(&log_msg).await;
+            }
+            Ok((reader, writer)) => {
+                self.logger
+                    .debug(&format!("New WebSocket client {addr}"))
+                    .await;
+                Arc::clone(&self.client_manager)
+                    .accept(reader, writer, addr)
+                    .await;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        network::tls::tests::{remove_certificate, self_signed},
+        protocol::packet::{Packet, PacketKind},
+        utils::config::{AccountConfig, TournamentConfig},
+    };
+    use tokio_rustls::{
+        TlsConnector,
+        rustls::{ClientConfig, pki_types::ServerName},
+    };
+
+    // Both listeners come up with TLS; a Setup packet without an opcode is answered over each.
+    #[tokio::test]
+    async fn listeners_speak_tls() {
+        let (tls, roots) = self_signed("server");
+        let certificate = tls.clone();
+        let dir = std::env::temp_dir().join(format!("mahjong-server-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = |name: &str| dir.join(name).to_string_lossy().to_string();
+        let config = Config {
+            port: 0,
+            websocket_port: Some(0),
+            tls: Some(tls),
+            accounts: AccountConfig {
+                path: path("accounts.json"),
+                seasons_dir: path("seasons"),
+                ..AccountConfig::default()
+            },
+            tournaments: TournamentConfig {
+                path: path("tournaments.json"),
+                ..TournamentConfig::default()
+            },
+            paifu_dir: None,
+            results_path: path("results.jsonl"),
+            ..Config::default()
+        };
+        let logger = Lolg::init(0, false).await.unwrap();
+        let server = Server::bind(config, logger).await.unwrap();
+        remove_certificate(&certificate);
+        let tcp_addr = server.socket.local_addr().unwrap();
+        let ws_addr = server.websocket.as_ref().unwrap().local_addr().unwrap();
+        tokio::spawn(Arc::clone(&server).start());
+
+        let connector = TlsConnector::from(Arc::new(
+            ClientConfig::builder()
+                .with_root_certificates(roots)
+                .with_no_client_auth(),
+        ));
+        let domain = ServerName::try_from("localhost").unwrap();
+        let empty_setup = Packet::create(7, PacketKind::Setup, &[]);
+
+        let tcp = TcpStream::connect(tcp_addr).await.unwrap();
+        let tcp = connector.connect(domain.clone(), tcp).await.unwrap();
+        let (mut reader, mut writer) = split_stream(tcp);
+        writer.write_packet(&empty_setup).await.unwrap();
+        let response = reader.read_packet().await.unwrap().unwrap();
+        assert!(response.kind == PacketKind::Error && response.id == 7);
+
+        let ws = TcpStream::connect(ws_addr).await.unwrap();
+        let ws = connector.connect(domain, ws).await.unwrap();
+        let (ws, _) = tokio_tungstenite::client_async("wss://localhost/", ws)
+            .await
+            .unwrap();
+        let (mut reader, mut writer) = split_websocket(ws);
+        writer.write_packet(&empty_setup).await.unwrap();
+        let response = reader.read_packet().await.unwrap().unwrap();
+        assert!(response.kind == PacketKind::Error && response.id == 7);
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
 }
@@ -0,0 +1,138 @@
+use std::{collections::HashMap, sync::Arc, time::Duration};
+
+use tokio::{sync::RwLock, time::Instant};
+
+use crate::{protocol::session::SessionToken, utils::errors::Error};
+
+struct Session {
+    player_id: i32,
+    expires: Option<Instant>, // Set while the player is disconnected.
+}
+
+/// Session tokens of every seated player. A token is issued on connection, swapped for
+/// a new one on every reconnection and stops working once its player stayed
+/// disconnected for longer than the grace period.
+pub struct SessionStore {
+    grace: Duration,
+    sessions: Arc<RwLock<HashMap<SessionToken, Session>>>,
+}
+
+impl SessionStore {
+    pub fn new(grace: Duration) -> Self {
+        Self {
+            grace,
+            sessions: Arc::new(RwLock::new(HashMap::new())),
+        }
+    }
+
+    // Issues a fresh token for the player, revoking any token they held before.
+    pub async fn issue(&self, player_id: i32) -> Result<SessionToken, Error> {
+        let mut bytes = [0; SessionToken::SIZE];
+        getrandom::fill(&mut bytes).map_err(|_| Error::InternalError)?;
+        let token = SessionToken(bytes);
+
+        let mut sessions = self.sessions.write().await;
+        sessions.retain(|_, session| session.player_id != player_id);
+        sessions.insert(
+            token,
+            Session {
+                player_id,
+                expires: None,
+            },
+        );
+        return Ok(token);
+    }
+
+    // Player the token belongs to, without using it up. Unknown, expired and revoked tokens are
+    // refused alike.
+    pub async fn holder(&self, token: &SessionToken) -> Result<i32, Error> {
+        let mut sessions = self.sessions.write().await;
+        let now = Instant::now();
+        sessions.retain(|_, session| session.expires.is_none_or(|expires| expires > now));
+        let session = sessions.get(token).ok_or(Error::ReconnectionFailed(59))?;
+        return Ok(session.player_id);
+    }
+
+    // Trades a valid token for the player id and a new token, so a token is only usable once.
+    pub async fn rotate(&self, token: &SessionToken) -> Result<(i32, SessionToken), Error> {
+        let player_id = {
+            let mut sessions = self.sessions.write().await;
+            let now = Instant::now();
+            sessions.retain(|_, session| session.expires.is_none_or(|expires| expires > now));
+            let session = sessions
+                .remove(token)
+                .ok_or(Error::ReconnectionFailed(59))?;
+            session.player_id
+        };
+
+        let token = self.issue(player_id).await?;
+        return Ok((player_id, token));
+    }
+
+    // Starts the grace period of a player that lost their connection.
+    pub async fn suspend(&self, player_id: i32) {
+        let expires = Instant::now() + self.grace;
+        for session in self.sessions.write().await.values_mut() {
+            if session.player_id == player_id && session.expires.is_none() {
+                session.expires = Some(expires);
+            }
+        }
+    }
+
+    // Tokens still usable: their player is connected or within the grace period.
+    pub async fn live(&self) -> usize {
+        let mut sessions = self.sessions.write().await;
+        let now = Instant::now();
+        sessions.retain(|_, session| session.expires.is_none_or(|expires| expires > now));
+        return sessions.len();
+    }
+
+    // Invalidates the player's token. Returns false if they had none.
+    pub async fn revoke(&self, player_id: i32) -> bool {
+        let mut sessions = self.sessions.write().await;
+        let before = sessions.len();
+        sessions.retain(|_, session| session.player_id != player_id);
+        return sessions.len() != before;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn rotate_once() {
+        let store = SessionStore::new(Duration::from_secs(60));
+        let first = store.issue(7).await.unwrap();
+        assert_eq!(store.holder(&first).await.unwrap(), 7);
+        let (player_id, second) = store.rotate(&first).await.unwrap();
+        assert_eq!(player_id, 7);
+        assert!(first != second);
+        assert!(store.rotate(&first).await.is_err());
+        assert!(store.holder(&first).await.is_err());
+        assert!(
+            store
+                .rotate(&SessionToken([0; SessionToken::SIZE]))
+                .await
+                .is_err()
+        );
+    }
+
+    #[tokio::test]
+    async fn expire_and_revoke() {
+        let store = SessionStore::new(Duration::ZERO);
+        let token = store.issue(1).await.unwrap();
+        assert_eq!(store.live().await, 1);
+        store.suspend(1).await;
+        assert_eq!(store.live().await, 0);
+        assert!(matches!(
+            store.rotate(&token).await,
+            Err(Error::ReconnectionFailed(59))
+        ));
+
+        let token = store.issue(2).await.unwrap();
+        assert!(store.revoke(2).await);
+        assert!(!store.revoke(2).await);
+        assert!(store.rotate(&token).await.is_err());
+    }
+}
@@ -0,0 +1,191 @@
+use crate::network::transport::{BoxedReader, BoxedWriter};
+use crate::protocol::codec::Codec;
+use crate::protocol::event_log::SequencedEvent;
+use crate::protocol::heartbeat::Heartbeat;
+use crate::protocol::packet::{Packet, PacketKind};
+use crate::protocol::protocol::Protocol;
+use crate::protocol::version::Handshake;
+use crate::protocol::view::Viewer;
+use crate::utils::config::{HeartbeatConfig, OutboundConfig, SpectatorConfig};
+use crate::utils::errors::Error;
+use crate::utils::models::SpectatorMode;
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::RwLock;
+use tokio::sync::broadcast::{self, error::RecvError};
+use tokio::sync::mpsc;
+use tokio::task::JoinHandle;
+use tokio::time::{sleep_until, timeout};
+
+/// Connection watching the match without a seat. Spectators are never in the player pool,
+/// so they do not count towards the seats a match needs to start.
+pub struct Spectator {
+    pub id: i32,
+    pub mode: SpectatorMode,
+    pub addr: SocketAddr,
+    pub handshake: Handshake,
+    pub protocol: Arc<Protocol>,
+    pub heartbeat: HeartbeatConfig,
+    pub outbound: OutboundConfig,
+    pub delay: Duration, // How far behind the table events are sent, zero for the public mode.
+    tasks: Arc<RwLock<Vec<JoinHandle<()>>>>, // Reader, writer and event tasks.
+}
+
+impl Spectator {
+    pub fn new(
+        id: i32,
+        addr: SocketAddr,
+        mode: SpectatorMode,
+        handshake: Handshake,
+        protocol: Arc<Protocol>,
+        heartbeat: HeartbeatConfig,
+        outbound: OutboundConfig,
+        config: SpectatorConfig,
+    ) -> Arc<Self> {
+        // Only the public view is safe to show live.
+        let delay = match mode {
+            SpectatorMode::Public => Duration::ZERO,
+            SpectatorMode::Full | SpectatorMode::Player(_) => config.delay(),
+        };
+
+        Arc::new(Self {
+            id,
+            mode,
+            addr,
+            handshake,
+            protocol,
+            heartbeat,
+            outbound,
+            delay,
+            tasks: Arc::new(RwLock::new(Vec::new())),
+        })
+    }
+
+    pub fn viewer(&self) -> Viewer {
+        match self.mode {
+            SpectatorMode::Public => Viewer::Spectator,
+            SpectatorMode::Full => Viewer::Full,
+            SpectatorMode::Player(id) => Viewer::Player(id),
+        }
+    }
+
+    pub fn codec(&self) -> Codec {
+        Codec::from_capabilities(self.handshake.capabilities)
+    }
+
+    // Starts streaming the match from its first event. `bcrx` must be subscribed before
+    // the log is read so no event falls between the replay and the live stream.
+    pub async fn connect(
+        self: Arc<Self>,
+        read: BoxedReader,
+        write: BoxedWriter,
+        bcrx: broadcast::Receiver<Arc<SequencedEvent>>,
+    ) {
+        let (queue, outbound) = mpsc::channel(self.outbound.capacity.max(1));
+        let writer = tokio::spawn(Arc::clone(&self).write_queued(write, outbound));
+        let events = tokio::spawn(Arc::clone(&self).stream_events(bcrx, queue.clone()));
+        let reader = tokio::spawn(Arc::clone(&self).read_packets(read, queue));
+        self.tasks.write().await.extend([writer, events, reader]);
+    }
+
+    async fn write_queued(
+        self: Arc<Self>,
+        mut write: BoxedWriter,
+        mut outbound: mpsc::Receiver<Packet>,
+    ) {
+        while let Some(packet) = outbound.recv().await {
+            let written = timeout(self.heartbeat.timeout(), write.write_packet(&packet)).await;
+            if !matches!(written, Ok(Ok(()))) {
+                break;
+            }
+        }
+
+        self.leave().await;
+    }
+
+    // Sends the log so far, then the live events, each once and no earlier than the delay allows.
+    // Waiting on the delay lags the channel; whatever it drops is read back from the log.
+    async fn stream_events(
+        self: Arc<Self>,
+        mut bcrx: broadcast::Receiver<Arc<SequencedEvent>>,
+        queue: mpsc::Sender<Packet>,
+    ) {
+        let mut last_seq = 0;
+        let mut pending = self.protocol.events.after(last_seq).await;
+        loop {
+            for event in pending.drain(..) {
+                if event.seq <= last_seq {
+                    continue;
+                }
+
+                sleep_until(event.at + self.delay).await;
+                let packet = event.packet(self.viewer(), self.codec());
+                let queued = timeout(self.heartbeat.timeout(), queue.send(packet)).await;
+                if !matches!(queued, Ok(Ok(()))) {
+                    self.leave().await;
+                    return;
+                }
+                last_seq = event.seq;
+            }
+
+            match bcrx.recv().await {
+                Ok(event) => pending.push(event),
+                Err(RecvError::Closed) => return,
+                Err(RecvError::Lagged(_)) => pending = self.protocol.events.after(last_seq).await,
+            }
+        }
+    }
+
+    // Spectators only ping; anything else is refused without closing the connection.
+    async fn read_packets(self: Arc<Self>, mut read: BoxedReader, queue: mpsc::Sender<Packet>) {
+        loop {
+            let response = match read.read_packet().await {
+                Ok(None) => break,
+                Err(error) => {
+                    let framing_lost = matches!(error, Error::PacketSizeInvalid(_));
+                    let _ = queue.try_send(Packet::error(0, error));
+                    if framing_lost {
+                        break;
+                    }
+                    continue;
+                }
+                Ok(Some(packet)) if packet.kind == PacketKind::Heartbeat => {
+                    match Heartbeat::parse(&packet.body) {
+                        Err(error) => Packet::error(packet.id, error),
+                        Ok(heartbeat) => match heartbeat.reply() {
+                            Some(pong) => pong.packet(packet.id),
+                            None => continue,
+                        },
+                    }
+                }
+                Ok(Some(packet)) => Packet::error(packet.id, Error::OperationFailed(57)),
+            };
+
+            let _ = queue.try_send(response);
+        }
+
+        self.leave().await;
+    }
+
+    // Forgets the spectator and stops its tasks.
+    // Aborting may hit the calling task, so nothing is awaited after the tasks are taken.
+    pub async fn leave(&self) {
+        if self
+            .protocol
+            .spectators
+            .write()
+            .await
+            .remove(&self.id)
+            .is_some()
+        {
+            let log_msg = format!("{}: stopped spectating", self.addr);
+            self.protocol.logger.info(&log_msg).await;
+        }
+
+        let tasks = std::mem::take(&mut *self.tasks.write().await);
+        for task in tasks {
+            task.abort();
+        }
+    }
+}
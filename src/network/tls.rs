@@ -0,0 +1,118 @@
+use std::{fs::File, io::BufReader, sync::Arc};
+
+use tokio_rustls::{TlsAcceptor, rustls::ServerConfig};
+
+use crate::utils::{config::TlsConfig, errors::Error};
+
+// Builds the acceptor shared by the TCP and WebSocket listeners from PEM files.
+pub fn load_acceptor(config: &TlsConfig) -> Result<TlsAcceptor, Error> {
+    let cert_file = File::open(&config.cert).map_err(|_| Error::InitializationFailed(7))?;
+    let certs = rustls_pemfile::certs(&mut BufReader::new(cert_file))
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|_| Error::InitializationFailed(7))?;
+
+    let key_file = File::open(&config.key).map_err(|_| Error::InitializationFailed(7))?;
+    let key = rustls_pemfile::private_key(&mut BufReader::new(key_file))
+        .map_err(|_| Error::InitializationFailed(7))?
+        .ok_or(Error::InitializationFailed(7))?;
+
+    let server_config = ServerConfig::builder()
+        .with_no_client_auth()
+        .with_single_cert(certs, key)
+        .map_err(|_| Error::InitializationFailed(7))?;
+
+    return Ok(TlsAcceptor::from(Arc::new(server_config)));
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use crate::{
+        network::transport::{split_stream, split_websocket},
+        protocol::packet::{Packet, PacketKind},
+    };
+    use tokio::net::{TcpListener, TcpStream};
+    use tokio_rustls::{
+        TlsConnector,
+        rustls::{ClientConfig, RootCertStore, pki_types::ServerName},
+    };
+
+    // Writes a freshly generated self-signed certificate for localhost and returns its config.
+    pub(crate) fn self_signed(name: &str) -> (TlsConfig, RootCertStore) {
+        let generated = rcgen::generate_simple_self_signed(vec!["localhost".to_string()]).unwrap();
+        let dir = std::env::temp_dir().join(format!("mahjong-tls-{name}-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+
+        let cert = dir.join("cert.pem");
+        let key = dir.join("key.pem");
+        std::fs::write(&cert, generated.cert.pem()).unwrap();
+        std::fs::write(&key, generated.signing_key.serialize_pem()).unwrap();
+
+        let mut roots = RootCertStore::empty();
+        roots.add(generated.cert.der().clone()).unwrap();
+        let config = TlsConfig {
+            cert: cert.to_string_lossy().to_string(),
+            key: key.to_string_lossy().to_string(),
+        };
+        return (config, roots);
+    }
+
+    // The certificate is only read while the acceptor is built.
+    pub(crate) fn remove_certificate(config: &TlsConfig) {
+        if let Some(dir) = std::path::Path::new(&config.cert).parent() {
+            let _ = std::fs::remove_dir_all(dir);
+        }
+    }
+
+    async fn tls_pair(name: &str) -> (TcpStream, TcpStream, TlsAcceptor, TlsConnector) {
+        let (config, roots) = self_signed(name);
+        let acceptor = load_acceptor(&config).unwrap();
+        remove_certificate(&config);
+        let connector = TlsConnector::from(Arc::new(
+            ClientConfig::builder()
+                .with_root_certificates(roots)
+                .with_no_client_auth(),
+        ));
+
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let client = TcpStream::connect(listener.local_addr().unwrap())
+            .await
+            .unwrap();
+        let (server, _) = listener.accept().await.unwrap();
+        return (server, client, acceptor, connector);
+    }
+
+    #[tokio::test]
+    async fn tcp_over_tls() {
+        let (server, client, acceptor, connector) = tls_pair("tcp").await;
+        let domain = ServerName::try_from("localhost").unwrap();
+        let accepting = tokio::spawn(async move { acceptor.accept(server).await.unwrap() });
+        let client = connector.connect(domain, client).await.unwrap();
+
+        let (mut reader, _) = split_stream(accepting.await.unwrap());
+        let (_, mut writer) = split_stream(client);
+        let packet = Packet::create(3, PacketKind::Setup, &[0x01, 0x00, 0x00, 0x00]);
+        writer.write_packet(&packet).await.unwrap();
+        assert_eq!(reader.read_packet().await.unwrap().unwrap().id, 3);
+    }
+
+    #[tokio::test]
+    async fn websocket_over_tls() {
+        let (server, client, acceptor, connector) = tls_pair("ws").await;
+        let domain = ServerName::try_from("localhost").unwrap();
+        let accepting = tokio::spawn(async move {
+            let stream = acceptor.accept(server).await.unwrap();
+            tokio_tungstenite::accept_async(stream).await.unwrap()
+        });
+        let client = connector.connect(domain, client).await.unwrap();
+        let (client, _) = tokio_tungstenite::client_async("wss://localhost/", client)
+            .await
+            .unwrap();
+
+        let (mut reader, _) = split_websocket(accepting.await.unwrap());
+        let (_, mut writer) = split_websocket(client);
+        let packet = Packet::create(4, PacketKind::Setup, &[0x01, 0x00, 0x00, 0x00]);
+        writer.write_packet(&packet).await.unwrap();
+        assert_eq!(reader.read_packet().await.unwrap().unwrap().id, 4);
+    }
+}
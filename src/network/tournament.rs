@@ -0,0 +1,722 @@
+use crate::game::match_manager::MatchStatus;
+use crate::game::player::{Player, STARTING_SCORE};
+use crate::network::accounts::Account;
+use crate::network::match_registry::MatchRegistry;
+use crate::network::results::SeatResult;
+use crate::protocol::lobby::Ruleset;
+use crate::protocol::protocol::Protocol;
+use crate::protocol::session::SessionToken;
+use crate::protocol::tournament::{
+    Stage, TournamentInfo, TournamentStanding, TournamentTable, TournamentView,
+};
+use crate::utils::config::TournamentConfig;
+use crate::utils::errors::Error;
+use crate::utils::models::JoinRequest;
+use lolg::Lolg;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::path::PathBuf;
+use std::sync::Arc;
+use tokio::sync::{RwLock, mpsc};
+
+/// A registered account and what it scored so far.
+#[derive(Clone, Serialize, Deserialize)]
+struct Participant {
+    player_id: i32,
+    username: String,
+    points: f64,
+    placements: Vec<u8>,
+}
+
+/// A table of a round: the participants drawn to it and, once its match is over, how every seat
+/// (bots included) ended.
+#[derive(Clone, Serialize, Deserialize)]
+struct RoundTable {
+    players: Vec<i32>,
+    results: Option<Vec<SeatResult>>,
+    #[serde(skip)]
+    table_id: Option<i32>, // Server table, reopened after a restart.
+    #[serde(skip)]
+    seated: Vec<i32>,
+}
+
+/// A Swiss tournament: every round is drawn from the standings so far and played under one ruleset.
+#[derive(Clone, Serialize, Deserialize)]
+struct Tournament {
+    id: u32,
+    name: String,
+    ruleset: Ruleset,
+    rounds: u8,
+    stage: Stage,
+    participants: Vec<Participant>, // In registration order.
+    played: Vec<Vec<RoundTable>>,   // Every round drawn so far, the last one being played.
+}
+
+impl Tournament {
+    fn info(&self) -> TournamentInfo {
+        return TournamentInfo {
+            id: self.id,
+            name: self.name.clone(),
+            ruleset: self.ruleset,
+            rounds: self.rounds,
+            round: self.played.len() as u8,
+            stage: self.stage,
+            participants: self.participants.len(),
+        };
+    }
+
+    // Participants by points, ties to the earlier registration.
+    fn standings(&self) -> Vec<&Participant> {
+        let mut standings: Vec<&Participant> = self.participants.iter().collect();
+        standings.sort_by(|a, b| b.points.total_cmp(&a.points));
+        return standings;
+    }
+
+    fn view(&self) -> TournamentView {
+        let standings = self
+            .standings()
+            .into_iter()
+            .enumerate()
+            .map(|(index, participant)| TournamentStanding {
+                position: index as u32 + 1,
+                player_id: participant.player_id,
+                username: participant.username.clone(),
+                points: participant.points,
+                placements: participant.placements.clone(),
+            })
+            .collect();
+        let tables = self
+            .played
+            .last()
+            .map_or(&[][..], |round| &round[..])
+            .iter()
+            .enumerate()
+            .map(|(index, table)| TournamentTable {
+                number: index as u32 + 1,
+                table_id: table.table_id,
+                players: table.players.clone(),
+                seated: table.seated.clone(),
+                finished: table.results.is_some(),
+            })
+            .collect();
+        return TournamentView {
+            info: self.info(),
+            standings,
+            tables,
+        };
+    }
+
+    // Draws the next round: the first one at random, the others by standings.
+    fn draw(&mut self) -> Result<(), Error> {
+        let mut order: Vec<i32> = self
+            .standings()
+            .iter()
+            .map(|participant| participant.player_id)
+            .collect();
+        if self.played.is_empty() {
+            let mut keys = vec![0; order.len() * 8];
+            getrandom::fill(&mut keys).map_err(|_| Error::InternalError)?;
+            let mut keyed: Vec<(&[u8], i32)> = keys.chunks(8).zip(order).collect();
+            keyed.sort();
+            order = keyed.into_iter().map(|(_, id)| id).collect();
+        }
+
+        let met = opponents(self.played.iter().flatten().map(|table| &table.players));
+        let round = pair(&order, &met, self.ruleset.seats().len())
+            .into_iter()
+            .map(|players| RoundTable {
+                players,
+                results: None,
+                table_id: None,
+                seated: Vec::new(),
+            })
+            .collect();
+        self.played.push(round);
+        return Ok(());
+    }
+}
+
+// What the tournaments file holds.
+#[derive(Serialize, Deserialize)]
+struct TournamentFile {
+    next_id: u32,
+    tournaments: Vec<Tournament>,
+}
+
+// A table of a round whose match ended: tournament id, round and table index, and its seats.
+type Finished = (u32, usize, usize, Vec<SeatResult>);
+
+/// Tournaments kept in a JSON file and written back on every change. Admins create and start
+/// them; the tables of each round are opened by the tournament, which waits for all of them to
+/// finish (or be interrupted) before drawing the next round. Admins forfeit the seats of
+/// participants who do not show up. Tables of an unfinished round are reopened on startup.
+pub struct Tournaments {
+    logger: Arc<Lolg>,
+    registry: Arc<MatchRegistry>,
+    config: TournamentConfig,
+    path: PathBuf,
+    file: Arc<RwLock<TournamentFile>>,
+    finished: mpsc::UnboundedSender<Finished>, // Watchers of the open tables report here.
+}
+
+impl Tournaments {
+    // A missing file means no tournaments; a file that exists but cannot be read or parsed is an error.
+    pub async fn load(
+        logger: Arc<Lolg>,
+        registry: Arc<MatchRegistry>,
+        config: &TournamentConfig,
+    ) -> Result<Arc<Self>, Error> {
+        let file = match std::fs::read_to_string(&config.path) {
+            Err(error) if error.kind() == std::io::ErrorKind::NotFound => TournamentFile {
+                next_id: 1,
+                tournaments: Vec::new(),
+            },
+            Err(_) => return Err(Error::InitializationFailed(16)),
+            Ok(content) => {
+                serde_json::from_str(&content).map_err(|_| Error::InitializationFailed(16))?
+            }
+        };
+
+        let (finished, reports) = mpsc::unbounded_channel();
+        let tournaments = Arc::new(Self {
+            logger,
+            registry,
+            config: config.clone(),
+            path: PathBuf::from(&config.path),
+            file: Arc::new(RwLock::new(file)),
+            finished,
+        });
+        tokio::spawn(Arc::clone(&tournaments).run(reports));
+        let mut file = tournaments.file.write().await;
+        for tournament in file.tournaments.iter_mut() {
+            if tournament.stage == Stage::Playing {
+                tournaments.open_round(tournament).await;
+            }
+        }
+        drop(file);
+        return Ok(tournaments);
+    }
+
+    pub fn is_admin(&self, account: &Account) -> bool {
+        return self.config.is_admin(&account.username);
+    }
+
+    pub async fn list(&self) -> Vec<TournamentInfo> {
+        let file = self.file.read().await;
+        return file.tournaments.iter().map(Tournament::info).collect();
+    }
+
+    pub async fn show(&self, id: u32) -> Result<TournamentView, Error> {
+        let file = self.file.read().await;
+        let tournament = file.tournaments.iter().find(|t| t.id == id);
+        return tournament
+            .map(Tournament::view)
+            .ok_or(Error::OperationFailed(80));
+    }
+
+    pub async fn create(
+        &self,
+        ruleset: Ruleset,
+        rounds: u8,
+        name: &str,
+    ) -> Result<TournamentView, Error> {
+        let mut file = self.file.write().await;
+        let tournament = Tournament {
+            id: file.next_id,
+            name: name.to_string(),
+            ruleset,
+            rounds,
+            stage: Stage::Registering,
+            participants: Vec::new(),
+            played: Vec::new(),
+        };
+        let view = tournament.view();
+        file.next_id += 1;
+        file.tournaments.push(tournament);
+        self.save(&file)?;
+        return Ok(view);
+    }
+
+    // Registering twice changes nothing.
+    pub async fn register(&self, id: u32, account: &Account) -> Result<TournamentView, Error> {
+        let mut file = self.file.write().await;
+        let tournament = find(&mut file, id)?;
+        if tournament.stage != Stage::Registering {
+            return Err(Error::OperationFailed(81));
+        }
+        if !tournament
+            .participants
+            .iter()
+            .any(|p| p.player_id == account.id)
+        {
+            tournament.participants.push(Participant {
+                player_id: account.id,
+                username: account.username.clone(),
+                points: 0.0,
+                placements: Vec::new(),
+            });
+        }
+        let view = tournament.view();
+        self.save(&file)?;
+        return Ok(view);
+    }
+
+    pub async fn withdraw(&self, id: u32, player_id: i32) -> Result<TournamentView, Error> {
+        let mut file = self.file.write().await;
+        let tournament = find(&mut file, id)?;
+        if tournament.stage != Stage::Registering {
+            return Err(Error::OperationFailed(81));
+        }
+        let before = tournament.participants.len();
+        tournament.participants.retain(|p| p.player_id != player_id);
+        if tournament.participants.len() == before {
+            return Err(Error::OperationFailed(82));
+        }
+        let view = tournament.view();
+        self.save(&file)?;
+        return Ok(view);
+    }
+
+    // Closes the registration and opens the tables of the first round.
+    pub async fn start(self: &Arc<Self>, id: u32) -> Result<TournamentView, Error> {
+        let mut file = self.file.write().await;
+        let tournament = find(&mut file, id)?;
+        if tournament.stage != Stage::Registering {
+            return Err(Error::OperationFailed(81));
+        }
+        if tournament.participants.len() < 2 {
+            return Err(Error::OperationFailed(86));
+        }
+
+        tournament.draw()?;
+        tournament.stage = Stage::Playing;
+        self.open_round(tournament).await;
+        let view = tournament.view();
+        self.save(&file)?;
+        let log_msg = format!(
+            "tournament {id}: started with {} players",
+            view.info.participants
+        );
+        self.logger.info(&log_msg).await;
+        return Ok(view);
+    }
+
+    // Seats the participant at their table of the current round. A table that could not be
+    // opened with the round is opened now.
+    pub async fn play(
+        self: &Arc<Self>,
+        id: u32,
+        req: &JoinRequest,
+    ) -> Result<(Arc<Protocol>, Arc<Player>, SessionToken), Error> {
+        let mut file = self.file.write().await;
+        let tournament = find(&mut file, id)?;
+        if tournament.stage != Stage::Playing {
+            return Err(Error::OperationFailed(81));
+        }
+        let round = tournament.played.len() - 1;
+        let index = tournament.played[round]
+            .iter()
+            .position(|table| table.players.contains(&req.id))
+            .ok_or(Error::OperationFailed(82))?;
+        if tournament.played[round][index].results.is_some() {
+            return Err(Error::OperationFailed(81));
+        }
+
+        let open = match tournament.played[round][index].table_id {
+            Some(table_id) => self.registry.tables.read().await.get(&table_id).cloned(),
+            None => None,
+        };
+        let table = match open {
+            Some(table) => table,
+            None => self.open_table(tournament, round, index).await?,
+        };
+        let (player, token) = table.seat(req).await?;
+        tournament.played[round][index].seated.push(req.id);
+        return Ok((table, player, token));
+    }
+
+    // Swaps the seats of two participants of the current round, as long as neither sat down yet.
+    pub async fn swap(&self, id: u32, first: i32, second: i32) -> Result<TournamentView, Error> {
+        let mut file = self.file.write().await;
+        let tournament = find(&mut file, id)?;
+        if tournament.stage != Stage::Playing {
+            return Err(Error::OperationFailed(81));
+        }
+        let round = tournament
+            .played
+            .last_mut()
+            .ok_or(Error::OperationFailed(81))?;
+        let locate = |round: &[RoundTable], player: i32| {
+            let index = round
+                .iter()
+                .position(|table| table.players.contains(&player))
+                .ok_or(Error::OperationFailed(85))?;
+            let table = &round[index];
+            if table.results.is_some() || table.seated.contains(&player) {
+                return Err(Error::OperationFailed(85));
+            }
+            let seat = table.players.iter().position(|p| *p == player).unwrap();
+            return Ok((index, seat));
+        };
+        let (first_table, first_seat) = locate(round, first)?;
+        let (second_table, second_seat) = locate(round, second)?;
+        round[first_table].players[first_seat] = second;
+        round[second_table].players[second_seat] = first;
+
+        let view = tournament.view();
+        self.save(&file)?;
+        let log_msg = format!("tournament {id}: players {first} and {second} swapped seats");
+        self.logger.info(&log_msg).await;
+        return Ok(view);
+    }
+
+    // Gives a bot the seat of a participant who never sat down at their table of the current
+    // round, so the table can be played without them. They score nothing for the round.
+    pub async fn forfeit(self: &Arc<Self>, id: u32, player: i32) -> Result<TournamentView, Error> {
+        let mut file = self.file.write().await;
+        let tournament = find(&mut file, id)?;
+        if tournament.stage != Stage::Playing {
+            return Err(Error::OperationFailed(81));
+        }
+        let round = tournament.played.len() - 1;
+        let index = tournament.played[round]
+            .iter()
+            .position(|table| table.players.contains(&player))
+            .ok_or(Error::OperationFailed(85))?;
+        let entry = &tournament.played[round][index];
+        if entry.results.is_some() || entry.seated.contains(&player) {
+            return Err(Error::OperationFailed(85));
+        }
+
+        let open = match entry.table_id {
+            Some(table_id) => self.registry.tables.read().await.get(&table_id).cloned(),
+            None => None,
+        };
+        let table = match open {
+            Some(table) => table,
+            None => self.open_table(tournament, round, index).await?,
+        };
+        let bots = {
+            let players = table.match_manager.state.player_pool.read().await;
+            players.values().filter(|player| player.is_bot()).count()
+        };
+        table.seat_bot(bots as i32 + 1).await?;
+        tournament.played[round][index]
+            .players
+            .retain(|p| *p != player);
+
+        let view = tournament.view();
+        self.save(&file)?;
+        let log_msg = format!("tournament {id}: player {player} forfeited their seat");
+        self.logger.info(&log_msg).await;
+        return Ok(view);
+    }
+
+    // Opens every table of the current round that is not finished or open yet.
+    async fn open_round(self: &Arc<Self>, tournament: &mut Tournament) {
+        let round = tournament.played.len() - 1;
+        for index in 0..tournament.played[round].len() {
+            let table = &tournament.played[round][index];
+            if table.results.is_some() || table.table_id.is_some() {
+                continue;
+            }
+            if let Err(error) = self.open_table(tournament, round, index).await {
+                let log_msg = format!("tournament {}: {error}", tournament.id);
+                self.logger.error(&log_msg).await;
+            }
+        }
+    }
+
+    // Opens a table of the round, gives the seats no participant was drawn to to bots and waits
+    // for its match to end.
+    async fn open_table(
+        self: &Arc<Self>,
+        tournament: &mut Tournament,
+        round: usize,
+        index: usize,
+    ) -> Result<Arc<Protocol>, Error> {
+        let table = self
+            .registry
+            .open_tournament(tournament.ruleset, tournament.id)
+            .await?;
+        let entry = &mut tournament.played[round][index];
+        let bots = tournament.ruleset.seats().len() - entry.players.len();
+        for number in 1..=bots as i32 {
+            if let Err(error) = table.seat_bot(number).await {
+                self.logger.error(&error.to_string()).await;
+            }
+        }
+        entry.table_id = Some(table.table_id);
+        entry.seated.clear();
+
+        let finished = self.finished.clone();
+        let key = (tournament.id, round, index);
+        tokio::spawn(watch(finished, key, Arc::clone(&table)));
+        return Ok(table);
+    }
+
+    // Counts the tables as their matches end, one at a time.
+    async fn run(self: Arc<Self>, mut reports: mpsc::UnboundedReceiver<Finished>) {
+        while let Some((id, round, index, results)) = reports.recv().await {
+            if let Err(error) = self.report(id, round, index, results).await {
+                self.logger.error(&error.to_string()).await;
+            }
+        }
+    }
+
+    // Adds a finished table to the standings. Once every table of the round is finished the next
+    // round is drawn and opened, or the tournament ends after its last round.
+    async fn report(
+        self: &Arc<Self>,
+        id: u32,
+        round: usize,
+        index: usize,
+        results: Vec<SeatResult>,
+    ) -> Result<(), Error> {
+        let mut file = self.file.write().await;
+        let tournament = find(&mut file, id)?;
+        let Some(table) = tournament
+            .played
+            .get_mut(round)
+            .and_then(|r| r.get_mut(index))
+        else {
+            return Ok(());
+        };
+        if table.results.is_some() {
+            return Ok(());
+        }
+
+        for result in &results {
+            let participant = tournament
+                .participants
+                .iter_mut()
+                .find(|p| p.player_id == result.player_id);
+            if let Some(participant) = participant {
+                let points = points(&self.config, tournament.ruleset, result);
+                participant.points += points;
+                participant.placements.push(result.placement);
+            }
+        }
+        tournament.played[round][index].results = Some(results);
+
+        if tournament.played[round].iter().all(|t| t.results.is_some()) {
+            let log_msg = match tournament.played.len() >= tournament.rounds as usize {
+                true => {
+                    tournament.stage = Stage::Finished;
+                    format!("tournament {id}: finished")
+                }
+                false => {
+                    tournament.draw()?;
+                    self.open_round(tournament).await;
+                    format!("tournament {id}: round {} drawn", tournament.played.len())
+                }
+            };
+            self.logger.info(&log_msg).await;
+        }
+        self.save(&file)?;
+        return Ok(());
+    }
+
+    // Written to a temporary file first so a crash never leaves a half written store.
+    fn save(&self, file: &TournamentFile) -> Result<(), Error> {
+        let document =
+            serde_json::to_string_pretty(file).map_err(|_| Error::SerializationFailed(16))?;
+        if let Some(dir) = self.path.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+            std::fs::create_dir_all(dir).map_err(|_| Error::SerializationFailed(16))?;
+        }
+        let temporary = self.path.with_extension("tmp");
+        std::fs::write(&temporary, document).map_err(|_| Error::SerializationFailed(16))?;
+        std::fs::rename(&temporary, &self.path).map_err(|_| Error::SerializationFailed(16))?;
+        return Ok(());
+    }
+}
+
+// Waits for the table's match to end and sends how its seats ended. Interrupted matches count as
+// they stood.
+async fn watch(
+    finished: mpsc::UnboundedSender<Finished>,
+    (id, round, index): (u32, usize, usize),
+    table: Arc<Protocol>,
+) {
+    let mut status = table.status();
+    loop {
+        let current = *status.borrow_and_update();
+        if matches!(current, MatchStatus::Finished | MatchStatus::Interrupted) {
+            break;
+        }
+        // The table was dropped without an ending, nothing to report.
+        if status.changed().await.is_err() {
+            return;
+        }
+    }
+
+    let record = table.match_record().await;
+    let _ = finished.send((id, round, index, record.players));
+}
+
+fn find(file: &mut TournamentFile, id: u32) -> Result<&mut Tournament, Error> {
+    return file
+        .tournaments
+        .iter_mut()
+        .find(|tournament| tournament.id == id)
+        .ok_or(Error::OperationFailed(80));
+}
+
+// Tournament points of a seat: the final score against the return score in thousands, the uma of
+// the placement and, for first place, the oka (what every seat paid to get to the return score).
+fn points(config: &TournamentConfig, ruleset: Ruleset, result: &SeatResult) -> f64 {
+    let seats = ruleset.seats().len();
+    let place = (result.placement as usize).clamp(1, seats) - 1;
+    let uma = match seats {
+        3 => config.sanma_uma[place],
+        _ => config.uma[place],
+    };
+    let oka = match result.placement {
+        1 => (config.return_score - STARTING_SCORE) as f64 * seats as f64 / 1000.0,
+        _ => 0.0,
+    };
+    return (result.score - config.return_score) as f64 / 1000.0 + uma + oka;
+}
+
+// Everyone each player already shared a table with.
+fn opponents<'a>(tables: impl Iterator<Item = &'a Vec<i32>>) -> HashMap<i32, HashSet<i32>> {
+    let mut met: HashMap<i32, HashSet<i32>> = HashMap::new();
+    for table in tables {
+        for player in table {
+            let others = table.iter().filter(|other| *other != player);
+            met.entry(*player).or_default().extend(others);
+        }
+    }
+    return met;
+}
+
+// Swiss pairing: players are spread over as few tables as fit them, as evenly as possible (bots
+// take the seats left over). Each table is started by the best placed player left and filled, in
+// standings order, with players who met none of its players yet; when nobody is left who did not,
+// with whoever met them least often.
+fn pair(order: &[i32], met: &HashMap<i32, HashSet<i32>>, seats: usize) -> Vec<Vec<i32>> {
+    let count = order.len().div_ceil(seats.max(1));
+    let mut left: Vec<i32> = order.to_vec();
+    let mut tables = Vec::new();
+    for table in 0..count {
+        let size = order.len() / count + (table < order.len() % count) as usize;
+        let mut players = vec![left.remove(0)];
+        while players.len() < size {
+            let repeats = |candidate: &i32| {
+                let seen = met.get(candidate);
+                return players
+                    .iter()
+                    .filter(|player| seen.is_some_and(|seen| seen.contains(player)))
+                    .count();
+            };
+            let (index, _) = left
+                .iter()
+                .enumerate()
+                .min_by_key(|(index, candidate)| (repeats(candidate), *index))
+                .unwrap();
+            players.push(left.remove(index));
+        }
+        tables.push(players);
+    }
+    return tables;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::enums::Seat;
+    use crate::network::match_registry::tests::TestRegistry;
+    use crate::protocol::account::Credentials;
+    use std::time::Duration;
+
+    #[test]
+    fn swiss_pairing_and_points() {
+        // Nine players at four seats: three tables of three, each with a bot.
+        let order: Vec<i32> = (1..=9).collect();
+        let first = pair(&order, &HashMap::new(), 4);
+        assert_eq!(first, vec![vec![1, 2, 3], vec![4, 5, 6], vec![7, 8, 9]]);
+
+        // Nobody meets a player they already played.
+        let second = pair(&order, &opponents(first.iter()), 4);
+        assert_eq!(second, vec![vec![1, 4, 7], vec![2, 5, 8], vec![3, 6, 9]]);
+
+        // Two tables of eight players cannot avoid repeats: each table takes the fewest.
+        let order: Vec<i32> = (1..=8).collect();
+        let first = vec![vec![1, 2, 3, 4], vec![5, 6, 7, 8]];
+        let tables = pair(&order, &opponents(first.iter()), 4);
+        assert_eq!(tables, vec![vec![1, 5, 2, 6], vec![3, 7, 4, 8]]);
+
+        // 25,000 starts, 30,000 returns: first gets the 20 point oka, the four seats sum to zero.
+        let config = TournamentConfig::default();
+        let seat = |score, placement| SeatResult {
+            player_id: 1,
+            alias: String::new(),
+            seat: Seat::East,
+            score,
+            placement,
+        };
+        let results = [
+            seat(45_000, 1),
+            seat(30_000, 2),
+            seat(20_000, 3),
+            seat(5_000, 4),
+        ];
+        let scored: Vec<f64> = results
+            .iter()
+            .map(|result| points(&config, Ruleset::Hanchan, result))
+            .collect();
+        assert_eq!(scored, vec![50.0, 5.0, -15.0, -40.0]);
+        assert_eq!(scored.iter().sum::<f64>(), 0.0);
+    }
+
+    #[tokio::test]
+    async fn forfeited_round_is_played_out() {
+        let test = TestRegistry::open("tournament").await;
+        let registry = Arc::clone(&test.registry);
+        let config = TournamentConfig {
+            path: test.path("tournaments.json"),
+            ..TournamentConfig::default()
+        };
+        let tournaments =
+            Tournaments::load(Arc::clone(&test.logger), Arc::clone(&registry), &config)
+                .await
+                .unwrap();
+
+        let id = tournaments
+            .create(Ruleset::Sanma, 1, "Cup")
+            .await
+            .unwrap()
+            .info
+            .id;
+        let mut players = Vec::new();
+        for username in ["first", "second"] {
+            let credentials = Credentials {
+                username: username.to_string(),
+                password: "hunter22".to_string(),
+            };
+            let account = registry.accounts.register(&credentials).await.unwrap();
+            tournaments.register(id, &account).await.unwrap();
+            players.push(account.id);
+        }
+        tournaments.start(id).await.unwrap();
+        assert!(matches!(
+            tournaments.forfeit(id, 99).await,
+            Err(Error::OperationFailed(85))
+        ));
+
+        // Neither showed up: bots take both seats and play the only table, which ends the round.
+        for player in players {
+            tournaments.forfeit(id, player).await.unwrap();
+        }
+        let mut view = tournaments.show(id).await.unwrap();
+        for _ in 0..100 {
+            if view.info.stage == Stage::Finished {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+            view = tournaments.show(id).await.unwrap();
+        }
+        assert!(view.info.stage == Stage::Finished);
+        assert!(view.tables[0].finished && view.tables[0].players.is_empty());
+    }
+}
@@ -0,0 +1,150 @@
+use futures::{
+    SinkExt, StreamExt,
+    stream::{SplitSink, SplitStream},
+};
+use tokio::io::{AsyncRead, AsyncWrite, ReadHalf, WriteHalf};
+use tokio_tungstenite::{WebSocketStream, tungstenite::Message};
+
+use crate::{
+    protocol::{
+        decoder::PacketDecoder,
+        packet::{Packet, ToBytes, WriteBytesExt},
+    },
+    utils::errors::Error,
+};
+
+/// Receiving side of a client connection, independent of how packets travel.
+#[async_trait::async_trait]
+pub trait PacketReader: Send + Sync {
+    /// Next packet from the peer, or None once the connection is closed.
+    async fn read_packet(&mut self) -> Result<Option<Packet>, Error>;
+}
+
+/// Sending side of a client connection, independent of how packets travel.
+#[async_trait::async_trait]
+pub trait PacketWriter: Send + Sync {
+    async fn write_packet(&mut self, packet: &Packet) -> tokio::io::Result<()>;
+}
+
+pub type BoxedReader = Box<dyn PacketReader>;
+pub type BoxedWriter = Box<dyn PacketWriter>;
+
+/// Raw byte stream (plain TCP) carrying length-prefixed packets.
+pub struct StreamReader<S> {
+    half: ReadHalf<S>,
+    decoder: PacketDecoder,
+}
+
+pub struct StreamWriter<S> {
+    half: WriteHalf<S>,
+}
+
+pub fn split_stream<S>(stream: S) -> (BoxedReader, BoxedWriter)
+where
+    S: AsyncRead + AsyncWrite + Send + Sync + Unpin + 'static,
+{
+    let (read, write) = tokio::io::split(stream);
+    let reader = StreamReader {
+        half: read,
+        decoder: PacketDecoder::new(),
+    };
+    return (Box::new(reader), Box::new(StreamWriter { half: write }));
+}
+
+#[async_trait::async_trait]
+impl<S: AsyncRead + AsyncWrite + Send + Sync + Unpin> PacketReader for StreamReader<S> {
+    async fn read_packet(&mut self) -> Result<Option<Packet>, Error> {
+        self.decoder.read_packet(&mut self.half).await
+    }
+}
+
+#[async_trait::async_trait]
+impl<S: AsyncRead + AsyncWrite + Send + Sync + Unpin> PacketWriter for StreamWriter<S> {
+    async fn write_packet(&mut self, packet: &Packet) -> tokio::io::Result<()> {
+        self.half.send_packet(packet).await
+    }
+}
+
+/// WebSocket connection carrying one packet per binary message.
+/// Bytes still go through the decoder, so the size field is checked the same way as on TCP.
+pub struct WebSocketReader<S> {
+    stream: SplitStream<WebSocketStream<S>>,
+    decoder: PacketDecoder,
+}
+
+pub struct WebSocketWriter<S> {
+    sink: SplitSink<WebSocketStream<S>, Message>,
+}
+
+pub fn split_websocket<S>(stream: WebSocketStream<S>) -> (BoxedReader, BoxedWriter)
+where
+    S: AsyncRead + AsyncWrite + Send + Sync + Unpin + 'static,
+{
+    let (sink, stream) = stream.split();
+    let reader = WebSocketReader {
+        stream,
+        decoder: PacketDecoder::new(),
+    };
+    return (Box::new(reader), Box::new(WebSocketWriter { sink }));
+}
+
+#[async_trait::async_trait]
+impl<S: AsyncRead + AsyncWrite + Send + Sync + Unpin> PacketReader for WebSocketReader<S> {
+    async fn read_packet(&mut self) -> Result<Option<Packet>, Error> {
+        loop {
+            if let Some(packet) = self.decoder.next_packet()? {
+                return Ok(Some(packet));
+            }
+
+            match self.stream.next().await {
+                None | Some(Err(_)) | Some(Ok(Message::Close(_))) => return Ok(None),
+                Some(Ok(Message::Binary(bytes))) => self.decoder.extend(&bytes),
+                // Text frames are not part of the protocol and control frames are answered by tungstenite.
+                Some(Ok(_)) => continue,
+            }
+        }
+    }
+}
+
+#[async_trait::async_trait]
+impl<S: AsyncRead + AsyncWrite + Send + Sync + Unpin> PacketWriter for WebSocketWriter<S> {
+    async fn write_packet(&mut self, packet: &Packet) -> tokio::io::Result<()> {
+        let message = Message::Binary(packet.to_bytes().into());
+        self.sink
+            .send(message)
+            .await
+            .map_err(|error| tokio::io::Error::new(tokio::io::ErrorKind::Other, error))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::protocol::packet::PacketKind;
+
+    #[tokio::test]
+    async fn websocket_and_stream_carry_the_same_packets() {
+        let (server_io, client_io) = tokio::io::duplex(4096);
+        let upgrade = tokio::spawn(tokio_tungstenite::accept_async(server_io));
+        let (client_ws, _) = tokio_tungstenite::client_async("ws://localhost/", client_io)
+            .await
+            .unwrap();
+        let (mut server_reader, _) = split_websocket(upgrade.await.unwrap().unwrap());
+        let (_, mut client_writer) = split_websocket(client_ws);
+
+        let packet = Packet::create(7, PacketKind::Action, &[0x01; 300]);
+        client_writer.write_packet(&packet).await.unwrap();
+        let received = server_reader.read_packet().await.unwrap().unwrap();
+        assert_eq!(received.id, 7);
+        assert_eq!(received.body, packet.body);
+
+        let (a, b) = tokio::io::duplex(4096);
+        let (mut reader, _) = split_stream(a);
+        let (_, mut writer) = split_stream(b);
+        writer.write_packet(&packet).await.unwrap();
+        assert_eq!(
+            reader.read_packet().await.unwrap().unwrap().body,
+            packet.body
+        );
+    }
+}
@@ -0,0 +1,205 @@
+use std::sync::Arc;
+
+use serde::Serialize;
+
+use tokio::{
+    sync::{RwLock, broadcast},
+    time::Instant,
+};
+
+use crate::{
+    game::enums::{Seat, Tile},
+    protocol::{
+        chat::Chat,
+        codec::Codec,
+        packet::{Packet, PacketKind},
+        view::Viewer,
+    },
+    utils::models::{Deal, Discard, Draw, HandResult},
+};
+
+/// Something every client at the table is told about, in the shape of the drawer's view.
+/// Cut it down with `MatchEvent::view` before it leaves the server.
+#[derive(Clone, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum MatchEvent {
+    /// The hand is None once hidden from the viewer.
+    Dealt {
+        player_id: i32,
+        seat: Seat,
+        hand: Option<Vec<Tile>>,
+    },
+    /// The tile is None once hidden from the viewer.
+    Drew {
+        player_id: i32,
+        tile: Option<Tile>,
+    },
+    Discarded {
+        player_id: i32,
+        tile: Tile,
+    },
+    /// Table chat: messages and reactions, never mutes.
+    Chat {
+        player_id: i32,
+        chat: Chat,
+    },
+    /// A hand was won or drawn. Results are public, so every viewer gets them whole.
+    HandEnded {
+        result: HandResult,
+    },
+    /// The server could not go on with the match.
+    Failure {
+        message: String,
+    },
+}
+
+/// A logged event. The sequence number doubles as the id of the packet it is sent in.
+#[derive(Serialize)]
+pub struct SequencedEvent {
+    pub seq: i32,
+    pub event: MatchEvent,
+    #[serde(skip)]
+    pub at: Instant, // When it was published, delayed spectators are sent it relative to this.
+}
+
+impl SequencedEvent {
+    // Events are kept unencoded so every client gets them in its own codec and view.
+    pub fn packet(&self, viewer: Viewer, codec: Codec) -> Packet {
+        match self.event.view(viewer) {
+            MatchEvent::Dealt {
+                player_id,
+                seat,
+                hand,
+            } => {
+                let deal = Deal {
+                    player_id,
+                    seat,
+                    hand,
+                };
+                Deal::broadcast(self.seq, &deal, codec)
+            }
+            MatchEvent::Drew { player_id, tile } => {
+                Draw::broadcast(self.seq, player_id, tile, codec)
+            }
+            MatchEvent::Discarded { player_id, tile } => {
+                Discard::broadcast(self.seq, player_id, tile, codec)
+            }
+            MatchEvent::Chat { player_id, chat } => chat.relay(self.seq, player_id),
+            MatchEvent::HandEnded { result } => HandResult::broadcast(self.seq, &result, codec),
+            MatchEvent::Failure { message } => {
+                Packet::create(self.seq, PacketKind::Error, message.as_bytes())
+            }
+        }
+    }
+}
+
+/// Every event of the match in order, numbered from 1 without gaps.
+/// Clients that lagged behind the broadcast channel or reconnected are
+/// replayed from here instead of losing events.
+pub struct EventLog {
+    events: RwLock<Vec<Arc<SequencedEvent>>>,
+    bctx: broadcast::Sender<Arc<SequencedEvent>>,
+}
+
+impl EventLog {
+    pub fn new(capacity: usize) -> Self {
+        let (bctx, _rx) = broadcast::channel(capacity);
+        Self {
+            bctx,
+            events: RwLock::new(Vec::new()),
+        }
+    }
+
+    pub fn subscribe(&self) -> broadcast::Receiver<Arc<SequencedEvent>> {
+        return self.bctx.subscribe();
+    }
+
+    // Numbers, stores and broadcasts the event. The log stays locked while sending
+    // so the channel sees events in sequence order.
+    pub async fn publish(&self, event: MatchEvent) -> i32 {
+        let mut events = self.events.write().await;
+        let seq = events.len() as i32 + 1;
+        let event = Arc::new(SequencedEvent {
+            seq,
+            event,
+            at: Instant::now(),
+        });
+        events.push(Arc::clone(&event));
+        let _ = self.bctx.send(event);
+        return seq;
+    }
+
+    // Events with a sequence number greater than `seq`, oldest first.
+    pub async fn after(&self, seq: i32) -> Vec<Arc<SequencedEvent>> {
+        let events = self.events.read().await;
+        let start = (seq.max(0) as usize).min(events.len());
+        return events[start..].to_vec();
+    }
+
+    pub async fn last_sequence(&self) -> i32 {
+        return self.events.read().await.len() as i32;
+    }
+
+    // The whole match as written to replay files, hidden tiles included.
+    // Competitive exports leave the chat out.
+    pub async fn export(&self, include_chat: bool) -> serde_json::Value {
+        let events = self.events.read().await;
+        let exported: Vec<&SequencedEvent> = events
+            .iter()
+            .map(Arc::as_ref)
+            .filter(|event| include_chat || !matches!(event.event, MatchEvent::Chat { .. }))
+            .collect();
+        return serde_json::json!({ "events": exported });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn replay_after_lag() {
+        let log = EventLog::new(2);
+        let mut rx = log.subscribe();
+        for _ in 0..5 {
+            let failure = MatchEvent::Failure {
+                message: "boom".to_string(),
+            };
+            log.publish(failure).await;
+        }
+
+        assert!(matches!(
+            rx.recv().await,
+            Err(broadcast::error::RecvError::Lagged(3))
+        ));
+        assert_eq!(log.last_sequence().await, 5);
+
+        let missed: Vec<i32> = log.after(2).await.iter().map(|event| event.seq).collect();
+        assert_eq!(missed, vec![3, 4, 5]);
+        assert_eq!(log.after(-1).await.len(), 5);
+        assert!(log.after(9).await.is_empty());
+    }
+
+    #[tokio::test]
+    async fn export_can_leave_out_chat() {
+        let log = EventLog::new(8);
+        log.publish(MatchEvent::Chat {
+            player_id: 1,
+            chat: Chat::Message("gl".to_string()),
+        })
+        .await;
+        log.publish(MatchEvent::Failure {
+            message: "boom".to_string(),
+        })
+        .await;
+
+        let full = log.export(true).await;
+        assert_eq!(full["events"].as_array().unwrap().len(), 2);
+        assert_eq!(full["events"][0]["event"]["type"], "chat");
+
+        let without_chat = log.export(false).await;
+        let events = without_chat["events"].as_array().unwrap();
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0]["seq"], 2);
+    }
+}
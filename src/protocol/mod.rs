@@ -1,3 +1,8 @@
-pub mod packet;
+pub mod event_log;
 pub mod protocol;
-pub mod broadcast;
+pub mod view;
+
+pub use mahjong_protocol::{
+    account, chat, codec, decoder, heartbeat, lobby, packet, schema, session, stats, tournament,
+    version,
+};
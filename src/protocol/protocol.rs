@@ -1,43 +1,92 @@
 use lolg::Lolg;
-use std::{collections::hash_map::IntoKeys, sync::Arc};
-use tokio::sync::{Mutex, broadcast, watch};
+use std::{
+    collections::{HashMap, hash_map::IntoKeys},
+    sync::Arc,
+};
+use tokio::sync::{Mutex, RwLock, watch};
 
 use crate::{
     game::{
-        enums::Action,
+        enums::{Action, Tile},
         game_action::GameAction,
         match_manager::{MatchManager, MatchStatus},
         player::Player,
     },
-    network::{client::Client, setup::Setup},
-    protocol::packet::{Packet, PacketKind},
+    network::{
+        accounts::AccountStore,
+        client::Client,
+        leaderboard,
+        match_registry::Room,
+        ranking,
+        results::{MatchRecord, ResultStore, SeatResult},
+        session::SessionStore,
+        setup::Setup,
+        spectator::Spectator,
+    },
+    protocol::{
+        chat::Chat,
+        event_log::{EventLog, MatchEvent},
+        lobby::RoomInfo,
+        packet::{Packet, PacketKind},
+        session::SessionToken,
+        version::{Handshake, SESSION_TOKEN_VERSION},
+    },
     utils::{
+        config::{ChatConfig, Config, RankingConfig},
         errors::Error,
-        models::{Discard, JoinRequest},
-        types::ClientPool,
+        models::{HandResult, JoinRequest, SpectatorMode},
+        types::{ClientPool, SpectatorPool},
     },
 };
 
+/// One table: a match with its own game state, Lua state and event log, the clients seated
+/// at it and its spectators. The server hosts many of them through `MatchRegistry`.
 pub struct Protocol {
-    logger: Arc<Lolg>,
-    client_pool: ClientPool, // Connected client pool (Also held by ClientManager).
-    global_id: Arc<Mutex<i32>>, // Id tracker for packets sent by server
+    pub table_id: i32,
+    pub room: Room, // Code, rules and password the table was opened with.
+    pub logger: Arc<Lolg>,
+    pub client_pool: ClientPool, // Clients seated at this table, by player id.
+    global_id: Arc<Mutex<i32>>,  // Id tracker for packets sent by server
     mmrx: watch::Receiver<MatchStatus>, // Watches the MatchStatus that is sent from MatchManager.
-    pub bctx: broadcast::Sender<Packet>, // Global packet broadcaster to all clients.
+    pub events: EventLog,        // Sequenced match events, broadcast to all clients.
+    pub sessions: SessionStore,  // Reconnection tokens of the seated players.
+    pub spectators: SpectatorPool, // Connections watching the match without a seat.
+    pub chat: ChatConfig,        // Chat rules of the table.
+    replay_dir: Option<String>,  // Where the match log is exported once the match is finished.
+    paifu_dir: Option<String>, // Where the paifu of the match is written once the table is closed.
+    results: Arc<ResultStore>, // Where the match is recorded once finished.
+    accounts: Arc<AccountStore>, // Accounts whose standing a ranked match changes.
+    ranking: RankingConfig,
     pub match_manager: Arc<MatchManager>,
 }
 
 // PUBLIC METHODS
 impl Protocol {
-    pub async fn new(log_manager: Arc<Lolg>, client_pool: ClientPool) -> Result<Arc<Self>, Error> {
-        let (bctx, _rx) = broadcast::channel::<Packet>(4);
+    pub async fn new(
+        table_id: i32,
+        room: Room,
+        log_manager: Arc<Lolg>,
+        config: &Config,
+        results: Arc<ResultStore>,
+        accounts: Arc<AccountStore>,
+    ) -> Result<Arc<Self>, Error> {
         let (mmtx, mmrx) = watch::channel(MatchStatus::Waiting);
-        let match_manager = MatchManager::new(log_manager.clone(), mmtx).await?;
+        let match_manager = MatchManager::new(log_manager.clone(), mmtx, room.ruleset).await?;
 
         let protocol = Arc::new(Self {
+            table_id,
+            room,
             mmrx,
-            bctx,
-            client_pool,
+            sessions: SessionStore::new(config.session.grace()),
+            events: EventLog::new(config.tables.event_capacity.max(1)),
+            client_pool: Arc::new(RwLock::new(HashMap::new())),
+            spectators: Arc::new(RwLock::new(HashMap::new())),
+            chat: config.chat,
+            replay_dir: config.replay_dir.clone(),
+            paifu_dir: config.paifu_dir.clone(),
+            results,
+            accounts,
+            ranking: config.ranking,
             logger: log_manager,
             global_id: Arc::new(Mutex::new(0)),
             match_manager: Arc::new(match_manager),
@@ -53,6 +102,19 @@ impl Protocol {
             match packet.kind {
                 PacketKind::Setup => self.handle_setup(client, &packet).await,
                 PacketKind::Action => self.handle_action(client.clone(), &packet).await,
+                PacketKind::Chat => self.handle_chat(client, &packet).await,
+                PacketKind::Stats => {
+                    let codec = client.codec().await;
+                    let response = self.results.answer(&packet, client.id, codec).await;
+                    client.send_packet(&response).await;
+                }
+                PacketKind::Leaderboard => {
+                    let codec = client.codec().await;
+                    let (accounts, results) = (&self.accounts, &self.results);
+                    let response =
+                        leaderboard::answer(&packet, codec, accounts, results, self.ranking).await;
+                    client.send_packet(&response).await;
+                }
                 _ => {
                     let error = Error::PacketParsingFailed(102);
                     let packet = Packet::error(packet.id, error);
@@ -62,27 +124,221 @@ impl Protocol {
         });
     }
 
-    // Handles packets of the Connection kind.
-    pub async fn handle_connect(&self, packet: &Packet) -> Result<Arc<Player>, Error> {
-        let req = JoinRequest::parse(&packet.body[5..])?;
+    // Seats a connected player at this table.
+    // The version is negotiated before a seat is taken so an incompatible client never occupies one.
+    // The seated player gets the session token they need to reconnect.
+    pub async fn seat(&self, req: &JoinRequest) -> Result<(Arc<Player>, SessionToken), Error> {
+        let player = self.match_manager.assign_player(req).await?;
+        let token = self.sessions.issue(player.id).await?;
+        self.publish_deal(&player).await;
+        return Ok((player, token));
+    }
+
+    // Gives a seat nobody took to a bot. Bots have negative ids, need no token and are ready
+    // (and connected) from the start.
+    pub async fn seat_bot(&self, number: i32) -> Result<Arc<Player>, Error> {
+        let req = JoinRequest {
+            id: -number,
+            alias: format!("Bot {number}"),
+        };
         let player = self.match_manager.assign_player(&req).await?;
+        *player.connected.write().await = true;
+        player.set_ready().await;
+        self.publish_deal(&player).await;
+        self.try_start().await;
         return Ok(player);
     }
 
+    // Starts the match once every seat is taken and ready, playing the bots' turns from there.
+    pub async fn try_start(&self) {
+        if self.match_manager.start().await {
+            let log_msg = format!("table {}: match started", self.table_id);
+            self.logger.info(&log_msg).await;
+            self.play_bots().await;
+        }
+    }
+
+    // Tells the table how a hand ended. The match record is built from these once it is finished.
+    pub async fn end_hand(&self, result: HandResult) {
+        self.match_manager.end_hand(&result).await;
+        self.events.publish(MatchEvent::HandEnded { result }).await;
+    }
+
+    // Ends the match: it is recorded, exported and its table closed after `tables.linger_ms`.
+    pub async fn finish(&self) {
+        self.match_manager.finish().await;
+    }
+
+    // Whether nobody is left to play: everyone who sat down stayed away longer than the session
+    // grace period, or nobody did. Tournament tables wait for their participants to sit down.
+    pub async fn abandoned(&self) -> bool {
+        if self.sessions.live().await > 0 {
+            return false;
+        }
+        let players = self.match_manager.state.player_pool.read().await;
+        let seated = players.values().any(|player| !player.is_bot());
+        return seated || self.room.tournament.is_none();
+    }
+
+    // Interrupts the match of an abandoned table, which the registry then closes.
+    pub async fn interrupt(&self) {
+        self.match_manager.interrupt().await;
+    }
+
+    // The table as listed in the lobby.
+    pub async fn room_info(&self) -> RoomInfo {
+        let (seated, seats) = self.match_manager.occupancy().await;
+        return RoomInfo {
+            code: self.room.code.clone(),
+            table_id: self.table_id,
+            ruleset: self.room.ruleset,
+            seated,
+            seats,
+            locked: self.room.locked(),
+        };
+    }
+
+    // Handles packets of the Spectate kind: | setup (4) | handshake (6) | mode (1) | player id (4)? |
+    // The table id and password after them are checked by the registry.
+    // A spectator following a player can only pick one that is seated.
+    pub async fn handle_spectate(
+        &self,
+        packet: &Packet,
+    ) -> Result<(Handshake, SpectatorMode), Error> {
+        let handshake_bytes = packet.body.get(4..).ok_or(Error::ConnectionFailed(54))?;
+        let handshake = Handshake::parse(handshake_bytes)?.negotiate()?;
+        let mode = SpectatorMode::parse(&handshake_bytes[Handshake::SIZE..])?;
+        if let SpectatorMode::Player(id) = mode {
+            let players = self.match_manager.state.player_pool.read().await;
+            if !players.values().any(|player| player.id == id) {
+                return Err(Error::ConnectionFailed(61));
+            }
+        }
+        return Ok((handshake, mode));
+    }
+
+    // Status of the match, for whoever needs to follow it (the registry closes finished tables).
+    pub fn status(&self) -> watch::Receiver<MatchStatus> {
+        return self.mmrx.clone();
+    }
+
+    // Disconnects everyone at the table and writes its paifu. Clients and spectators hold the
+    // table too, so dropping them from the pools is what lets it be freed.
+    pub async fn close(&self) {
+        self.write_paifu().await;
+        let clients: Vec<Arc<Client>> = self
+            .client_pool
+            .write()
+            .await
+            .drain()
+            .map(|(_, c)| c)
+            .collect();
+        for client in clients {
+            client.disconnect().await;
+            self.sessions.revoke(client.id).await;
+        }
+
+        let spectators: Vec<Arc<Spectator>> =
+            self.spectators.read().await.values().cloned().collect();
+        for spectator in spectators {
+            spectator.leave().await;
+        }
+    }
+
     pub async fn get_global_id(&self) -> i32 {
         let mut id = self.global_id.lock().await;
         *id += 1;
         return *id;
     }
 
-    // Handles packets of the Reconnection kind.
-    pub fn handle_reconnect(&self, packet: &Packet) -> Result<JoinRequest, Error> {
-        return JoinRequest::parse(&packet.body[5..]);
+    // Handles packets of the Reconnection kind: | setup (4) | handshake (6) | session token (32) |
+    // The token is swapped for a new one, so the seat's player id is never taken from the client.
+    // It is only swapped once the seat's client is found, so a refused token stays usable.
+    pub async fn handle_reconnect(
+        &self,
+        packet: &Packet,
+    ) -> Result<(Arc<Client>, SessionToken, Handshake), Error> {
+        let handshake_bytes = packet.body.get(4..).ok_or(Error::ConnectionFailed(54))?;
+        let handshake = Handshake::parse(handshake_bytes)?.negotiate()?;
+        if handshake.version < SESSION_TOKEN_VERSION {
+            return Err(Error::ReconnectionFailed(59));
+        }
+
+        let token = SessionToken::parse(&handshake_bytes[Handshake::SIZE..])?;
+        let player_id = self.sessions.holder(&token).await?;
+        let client = self
+            .client_pool
+            .read()
+            .await
+            .get(&player_id)
+            .cloned()
+            .ok_or(Error::ReconnectionFailed(55))?;
+        let (_, token) = self.sessions.rotate(&token).await?;
+        return Ok((client, token, handshake));
+    }
+
+    // Connection bodies: | setup (4) | handshake (6) | join request |
+    pub fn parse_join(packet: &Packet) -> Result<(Handshake, JoinRequest), Error> {
+        let handshake_bytes = packet.body.get(4..).ok_or(Error::ConnectionFailed(54))?;
+        let handshake = Handshake::parse(handshake_bytes)?.negotiate()?;
+        let req = JoinRequest::parse(&handshake_bytes[Handshake::SIZE..])?;
+        return Ok((handshake, req));
+    }
+
+    // Player-scoped snapshot of the table, sent after a reconnection or when the client asks for it.
+    pub async fn resync(&self, client: &Client, id: i32) -> Packet {
+        let last_sequence = self.events.last_sequence().await;
+        let snapshot = self
+            .match_manager
+            .snapshot(&client.player, last_sequence)
+            .await;
+        match client.codec().await.encode(&snapshot) {
+            Err(error) => {
+                let addr = client.addr.read().await;
+                self.logger.error(&format!("{addr}: {error}")).await;
+                Packet::error(id, error)
+            }
+            Ok(bytes) => {
+                let mut body = Setup::Resync.bytes().to_vec();
+                body.extend(bytes);
+                Packet::create(id, PacketKind::Setup, &body)
+            }
+        }
+    }
+
+    // Setup response confirming the operation, the negotiated handshake and the player's session token.
+    pub fn handshake_response(
+        id: i32,
+        operation: Setup,
+        handshake: &Handshake,
+        token: &SessionToken,
+    ) -> Packet {
+        let mut body = operation.bytes().to_vec();
+        body.extend(handshake.bytes());
+        body.extend(token.bytes());
+        return Packet::create(id, PacketKind::Setup, &body);
     }
 }
 
 impl Protocol {
+    async fn publish_deal(&self, player: &Player) {
+        let hand = player.hand.read().await.iter().map(|tile| **tile).collect();
+        let dealt = MatchEvent::Dealt {
+            player_id: player.id,
+            seat: *player.seat.read().await,
+            hand: Some(hand),
+        };
+        self.events.publish(dealt).await;
+    }
+
+    // Actions are only taken while the match is being played.
     async fn handle_action(&self, client: Arc<Client>, p: &Packet) {
+        if *self.mmrx.borrow() != MatchStatus::Ongoing {
+            let error = Error::ActionFailed(166);
+            client.send_packet(&Packet::error(p.id, error)).await;
+            return;
+        }
+
         match GameAction::parse(&p.body) {
             Err(error) => {
                 let addr = client.addr.read().await;
@@ -99,41 +355,37 @@ impl Protocol {
                                 self.logger.error(&error.to_string()).await;
                                 Packet::error(p.id, error)
                             }
-                            Ok(tile) => match serde_cbor::to_vec(&tile) {
-                                Err(_) => {
-                                    let error = Error::InternalError;
-                                    self.logger.error(&error.to_string()).await;
-                                    Packet::error(p.id, error)
-                                }
-                                Ok(bytes) => {
-                                    let mut body = Vec::new();
-                                    body.extend_from_slice(&Action::DRAW.bytes());
-                                    body.extend_from_slice(&bytes);
-                                    Packet::create(
-                                        p.id,
-                                        PacketKind::Action,
-                                        &body.into_boxed_slice(),
-                                    )
+                            Ok(tile) => {
+                                let drew = MatchEvent::Drew {
+                                    player_id: client.id,
+                                    tile: Some(*tile),
+                                };
+                                self.events.publish(drew).await;
+                                match client.codec().await.encode(&tile) {
+                                    Err(error) => {
+                                        self.logger.error(&error.to_string()).await;
+                                        Packet::error(p.id, error)
+                                    }
+                                    Ok(bytes) => {
+                                        let mut body = Vec::new();
+                                        body.extend_from_slice(&Action::DRAW.bytes());
+                                        body.extend_from_slice(&bytes);
+                                        Packet::create(
+                                            p.id,
+                                            PacketKind::Action,
+                                            &body.into_boxed_slice(),
+                                        )
+                                    }
                                 }
-                            },
+                            }
                         };
 
                         client.send_packet(&response).await;
                     }
-                    Action::KAN => {
-                        todo!()
-                    }
-                    Action::CHI => {
-                        todo!()
-                    }
-                    Action::PON => {
-                        todo!()
-                    }
-                    Action::RON => {
-                        todo!()
-                    }
-                    Action::TSUMO => {
-                        todo!()
+                    // Calls are offered on discards but cannot be played out yet.
+                    Action::KAN | Action::CHI | Action::PON | Action::RON | Action::TSUMO => {
+                        let response = Packet::error(p.id, Error::ActionFailed(167));
+                        client.send_packet(&response).await;
                     }
                     Action::DISCARD => {
                         match self.match_manager.discard(player, action).await {
@@ -142,26 +394,8 @@ impl Protocol {
                                 client.send_packet(&response).await;
                             }
                             Ok(tile) => {
-                                let Ok(melds) = self.match_manager.check_calls(tile).await else {
-                                    let id = self.get_global_id().await;
-                                    let error = Error::InternalError;
-                                    let response = Packet::error(id, error);
-                                    let _ = self.bctx.send(response);
-                                    return;
-                                };
-
-                                let client_pool = self.client_pool.read().await;
-                                for key in melds.keys() {
-                                    let client = client_pool.get(key).unwrap();
-                                    let meld = melds.get(key).unwrap();
-
-                                    let id = self.get_global_id().await;
-                                    let mut body = Vec::new();
-                                    body.extend_from_slice(&Action::DISCARD.bytes());
-                                    body.extend_from_slice(&meld);
-                                    let response = Packet::create(id, PacketKind::Action, &body);
-                                    client.send_packet(&response).await;
-                                }
+                                self.after_discard(client.id, tile).await;
+                                self.play_bots().await;
                             }
                         };
                     }
@@ -170,8 +404,91 @@ impl Protocol {
         };
     }
 
+    // Tells the table about the discard and offers the calls on it. The hand ends in an
+    // exhaustive draw once the wall is empty, and with it the match; otherwise the turn passes on.
+    async fn after_discard(&self, player_id: i32, tile: Tile) {
+        let discarded = MatchEvent::Discarded { player_id, tile };
+        self.events.publish(discarded).await;
+        self.offer_calls(tile).await;
+
+        if self.match_manager.state.wall.read().await.is_empty() {
+            self.end_hand(HandResult::default()).await;
+            self.finish().await;
+            return;
+        }
+        if let Err(error) = self.match_manager.next_turn().await {
+            self.logger.error(&error.to_string()).await;
+        }
+    }
+
+    // Sends every seated client the calls it may make on the discard.
+    async fn offer_calls(&self, tile: Tile) {
+        let Ok(melds) = self.match_manager.check_calls(tile).await else {
+            let error = Error::InternalError.to_string();
+            let failure = MatchEvent::Failure { message: error };
+            self.events.publish(failure).await;
+            return;
+        };
+
+        let client_pool = self.client_pool.read().await;
+        for (key, meld) in melds.iter() {
+            // Bots have no client to be offered calls.
+            let Some(client) = client_pool.get(key) else {
+                continue;
+            };
+            let Ok(meld) = client.codec().await.encode(meld) else {
+                continue;
+            };
+
+            let id = self.get_global_id().await;
+            let mut body = Vec::new();
+            body.extend_from_slice(&Action::DISCARD.bytes());
+            body.extend_from_slice(&meld);
+            let response = Packet::create(id, PacketKind::Action, &body);
+            client.send_packet(&response).await;
+        }
+    }
+
+    // Bots draw and let go of the tile they drew until it is a person's turn or the match is over.
+    async fn play_bots(&self) {
+        while *self.mmrx.borrow() == MatchStatus::Ongoing {
+            let Some(player) = self.match_manager.current_player().await else {
+                return;
+            };
+            if !player.is_bot() {
+                return;
+            }
+
+            let player_id = player.id;
+            let tile = match self.match_manager.draw(Arc::clone(&player)).await {
+                Err(error) => {
+                    self.logger.error(&error.to_string()).await;
+                    return;
+                }
+                Ok(tile) => *tile,
+            };
+            let drew = MatchEvent::Drew {
+                player_id,
+                tile: Some(tile),
+            };
+            self.events.publish(drew).await;
+
+            let action = GameAction {
+                action: Action::DISCARD,
+                target: Some(tile),
+            };
+            match self.match_manager.discard(player, action).await {
+                Err(error) => {
+                    self.logger.error(&error.to_string()).await;
+                    return;
+                }
+                Ok(tile) => self.after_discard(player_id, tile).await,
+            }
+        }
+    }
+
     async fn handle_setup(&self, client: Arc<Client>, packet: &Packet) {
-        let Some(operation) = Setup::from(&packet.body[..4]) else {
+        let Some(operation) = packet.body.get(..4).and_then(Setup::from) else {
             let error = Error::ConnectionNeeded;
             let addr = client.addr.read().await;
             self.logger.error(&format!("{addr}: {error}")).await;
@@ -180,25 +497,52 @@ impl Protocol {
         };
 
         let response = match operation {
-            Setup::Initialization => match client.player.get_initial_view().await {
-                Ok(view_bytes) => {
-                    let setup = Setup::Initialization.bytes();
-                    let mut body_bytes = setup.to_vec();
-                    body_bytes.extend(view_bytes);
-                    Packet::create(packet.id, PacketKind::Setup, &body_bytes)
-                }
-                Err(error) => {
-                    let addr = client.addr.read().await;
-                    self.logger.error(&format!("{addr}: {error}")).await;
-                    Packet::error(packet.id, error)
+            Setup::Initialization => {
+                match client.player.get_initial_view(client.codec().await).await {
+                    Ok(view_bytes) => {
+                        let setup = Setup::Initialization.bytes();
+                        let mut body_bytes = setup.to_vec();
+                        body_bytes.extend(view_bytes);
+                        Packet::create(packet.id, PacketKind::Setup, &body_bytes)
+                    }
+                    Err(error) => {
+                        let addr = client.addr.read().await;
+                        self.logger.error(&format!("{addr}: {error}")).await;
+                        Packet::error(packet.id, error)
+                    }
                 }
-            },
+            }
             Setup::Ready => {
                 client.player.set_ready().await;
                 let addr = client.addr.read().await;
                 self.logger.info(&format!("{addr}: is ready.")).await;
                 Packet::create(packet.id, PacketKind::Setup, &[0x00])
             }
+            Setup::Resync => self.resync(&client, packet.id).await,
+            Setup::Replay => {
+                // | setup (4) | last seen sequence (i32) |
+                let Some(seen) = packet.body.get(4..8) else {
+                    let error = Error::OperationFailed(60);
+                    client.send_packet(&Packet::error(packet.id, error)).await;
+                    return;
+                };
+
+                // Events the broadcast task already sent are skipped, so nothing arrives twice.
+                let seen = i32::from_le_bytes([seen[0], seen[1], seen[2], seen[3]]);
+                for event in self.events.after(seen).await {
+                    client.send_event(&event).await;
+                }
+
+                // Ends the replay with the sequence number it reached.
+                let reached = {
+                    let mut last_seq = client.last_seq.write().await;
+                    *last_seq = seen.max(*last_seq);
+                    *last_seq
+                };
+                let mut body = Setup::Replay.bytes().to_vec();
+                body.extend(reached.to_le_bytes());
+                Packet::create(packet.id, PacketKind::Setup, &body)
+            }
             _ => {
                 let error = Error::OperationFailed(57);
                 let addr = client.addr.read().await;
@@ -208,11 +552,148 @@ impl Protocol {
         };
 
         client.send_packet(&response).await;
+        // The last player to be ready starts the match.
+        if matches!(operation, Setup::Ready) {
+            self.try_start().await;
+        }
+    }
+
+    // Relays chat to the table through the match log, so spectators and replays get it too.
+    // Mutes are kept on the client and never relayed.
+    async fn handle_chat(&self, client: Arc<Client>, packet: &Packet) {
+        let result = match Chat::parse(&packet.body) {
+            Err(error) => Err(error),
+            Ok(Chat::Mute(id)) => {
+                client.muted.write().await.insert(id);
+                Ok(())
+            }
+            Ok(Chat::Unmute(id)) => {
+                client.muted.write().await.remove(&id);
+                Ok(())
+            }
+            Ok(chat) => match self.check_chat(&client, &chat).await {
+                Err(error) => Err(error),
+                Ok(()) => {
+                    let player_id = client.id;
+                    self.events
+                        .publish(MatchEvent::Chat { player_id, chat })
+                        .await;
+                    Ok(())
+                }
+            },
+        };
+
+        if let Err(error) = result {
+            let addr = client.addr.read().await;
+            self.logger.error(&format!("{addr}: {error}")).await;
+            client.send_packet(&Packet::error(packet.id, error)).await;
+        }
+    }
+
+    // Free text may be off (ranked rooms), and has to fit the length limit.
+    // Reactions only count against the rate limit.
+    async fn check_chat(&self, client: &Client, chat: &Chat) -> Result<(), Error> {
+        if let Chat::Message(text) = chat {
+            if !self.chat.free_text {
+                return Err(Error::OperationFailed(64));
+            }
+            if text.trim().is_empty() || text.chars().count() > self.chat.max_length {
+                return Err(Error::OperationFailed(62));
+            }
+        }
+
+        let window = self.chat.rate_window();
+        if !client.take_chat_slot(self.chat.rate_limit, window).await {
+            return Err(Error::OperationFailed(63));
+        }
+        return Ok(());
+    }
+
+    // Writes the match log to `replay_dir`, without the chat unless `chat.in_replays` is on.
+    async fn export_replay(&self) -> Result<(), Error> {
+        let Some(dir) = &self.replay_dir else {
+            return Ok(());
+        };
+
+        let replay = self.events.export(self.chat.in_replays).await;
+        let seconds = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map_or(0, |elapsed| elapsed.as_secs());
+        let path = std::path::Path::new(dir).join(format!("{seconds}.json"));
+        let document =
+            serde_json::to_string_pretty(&replay).map_err(|_| Error::SerializationFailed(13))?;
+        std::fs::create_dir_all(dir).map_err(|_| Error::SerializationFailed(13))?;
+        std::fs::write(path, document).map_err(|_| Error::SerializationFailed(13))?;
+        return Ok(());
+    }
+
+    // Every closed table is written, an interrupted match's paifu ends where play stopped.
+    async fn write_paifu(&self) {
+        let Some(dir) = &self.paifu_dir else {
+            return;
+        };
+        if let Err(error) = self.match_manager.paifu.write(dir, self.table_id).await {
+            self.logger.error(&error.to_string()).await;
+        }
+    }
+
+    // Final scores and placements, with every hand reported through `end_hand`.
+    pub async fn match_record(&self) -> MatchRecord {
+        let mut players = Vec::new();
+        for (seat, player) in self.match_manager.state.player_pool.read().await.iter() {
+            players.push(SeatResult {
+                player_id: player.id,
+                alias: player.alias.read().await.clone(),
+                seat: *seat,
+                score: *player.score.read().await,
+                placement: 0,
+            });
+        }
+        let hands = self
+            .events
+            .after(0)
+            .await
+            .iter()
+            .filter_map(|event| match &event.event {
+                MatchEvent::HandEnded { result } => Some(result.clone()),
+                _ => None,
+            })
+            .collect();
+        let seed = self.match_manager.state.seed;
+        let (ruleset, tier) = (self.room.ruleset, self.room.tier);
+        return MatchRecord::new(self.table_id, ruleset, tier, seed, players, hands);
+    }
+
+    // Moves the ratings and rank points of the accounts at a ranked table. Guests and bots
+    // count at the starting standing and are not updated. A match finished in a new season is
+    // rated from the soft reset standings.
+    async fn rate(&self, record: &MatchRecord) -> Result<(), Error> {
+        let Some(tier) = record.tier else {
+            return Ok(());
+        };
+        let season = self.ranking.season_at(record.finished);
+        self.accounts
+            .roll_season(season, self.ranking.soft_reset)
+            .await?;
+
+        let mut seats = Vec::new();
+        for player in &record.players {
+            let standing = self.accounts.standing(player.player_id).await;
+            seats.push((player.placement, standing.unwrap_or_default()));
+        }
+        let k_factor = self.ranking.k_factor;
+        let rated = ranking::rate(record.ruleset, tier, k_factor, &seats);
+        let standings: Vec<_> = record
+            .players
+            .iter()
+            .map(|player| player.player_id)
+            .zip(rated)
+            .collect();
+        return self.accounts.update_standings(&standings).await;
     }
 
     // Spawns a task to watch the changes from the match status and deal with each respective status.
     async fn watch_match_status(self: Arc<Self>) {
-        let bctx = self.bctx.clone();
         let mut mmrx = self.mmrx.clone();
         tokio::spawn({
             async move {
@@ -220,14 +701,260 @@ impl Protocol {
                     let status = *mmrx.borrow();
                     match &status {
                         MatchStatus::Waiting => {}
-                        MatchStatus::Finished => {}
+                        MatchStatus::Finished => {
+                            let record = self.match_record().await;
+                            if let Err(error) = self.results.record(&record).await {
+                                self.logger.error(&error.to_string()).await;
+                            }
+                            if let Err(error) = self.rate(&record).await {
+                                self.logger.error(&error.to_string()).await;
+                            }
+                            if let Err(error) = self.export_replay().await {
+                                self.logger.error(&error.to_string()).await;
+                            }
+                            return;
+                        }
                         MatchStatus::Ongoing => {}
-                        MatchStatus::Interrupted => {}
+                        // The table is closed by the registry, nothing left to watch.
+                        MatchStatus::Interrupted => return,
                     }
 
-                    mmrx.changed().await.unwrap();
+                    if mmrx.changed().await.is_err() {
+                        return;
+                    }
                 }
             }
         });
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::{
+        enums::Seat,
+        paifu::{PaifuDocument, PaifuEvent},
+    };
+    use crate::protocol::{
+        account::Credentials,
+        lobby::{Ruleset, Tier},
+    };
+    use std::{
+        collections::HashSet,
+        path::{Path, PathBuf},
+        time::Duration,
+    };
+
+    // A table whose results and accounts are kept in a temporary directory of its own.
+    async fn open(name: &str, room: Room) -> (Arc<Protocol>, PathBuf) {
+        let dir = std::env::temp_dir().join(format!("mahjong-{name}-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        let path = |file: &str| dir.join(file).to_string_lossy().into_owned();
+        let config = Config {
+            paifu_dir: Some(path("paifu")),
+            results_path: path("results.jsonl"),
+            ..Config::default()
+        };
+        let results = Arc::new(ResultStore::load(&config.results_path, config.ranking).unwrap());
+        let accounts = AccountStore::load(&path("accounts.json"), &path("seasons")).unwrap();
+        let logger = Lolg::init(0, false).await.unwrap();
+        let table = Protocol::new(1, room, logger, &config, results, Arc::new(accounts))
+            .await
+            .unwrap();
+        return (table, dir);
+    }
+
+    // The match record is written by the task watching the match status.
+    async fn written_record(dir: &Path) -> MatchRecord {
+        let mut line = String::new();
+        for _ in 0..100 {
+            line = std::fs::read_to_string(dir.join("results.jsonl")).unwrap_or_default();
+            if !line.is_empty() {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        return serde_json::from_str(line.trim_end()).unwrap();
+    }
+
+    #[tokio::test]
+    async fn bots_play_a_match_to_its_record() {
+        let room = Room::new("ABCDEF".to_string(), Ruleset::Sanma, None);
+        let (table, dir) = open("bots", room).await;
+
+        // The last bot to sit down starts the match, and bots play it out to an exhaustive draw.
+        for number in 1..=3 {
+            table.seat_bot(number).await.unwrap();
+        }
+        assert!(*table.status().borrow() == MatchStatus::Finished);
+        assert!(table.match_manager.state.wall.read().await.is_empty());
+
+        let record = written_record(&dir).await;
+        assert_eq!(record.table_id, 1);
+        assert_eq!(record.players.len(), 3);
+        assert!(record.hands == vec![HandResult::default()]);
+
+        // The paifu is written on close, with every turn of the hand and how it ended.
+        table.close().await;
+        let file = std::fs::read_dir(dir.join("paifu"))
+            .unwrap()
+            .next()
+            .unwrap();
+        let paifu = std::fs::read_to_string(file.unwrap().path()).unwrap();
+        let paifu: PaifuDocument = serde_json::from_str(&paifu).unwrap();
+        let count = |kind: fn(&PaifuEvent) -> bool| {
+            return paifu
+                .entries
+                .iter()
+                .filter(|entry| kind(&entry.event))
+                .count();
+        };
+        // One tile of the wall is the dora indicator and never drawn.
+        assert_eq!(
+            count(|event| matches!(event, PaifuEvent::DoraRevealed { .. })),
+            1
+        );
+        let drawn = 108 - 3 * 13 - 1;
+        assert_eq!(
+            count(|event| matches!(event, PaifuEvent::Turn { .. })),
+            drawn
+        );
+        assert_eq!(
+            count(|event| matches!(event, PaifuEvent::Drew { .. })),
+            drawn
+        );
+        assert_eq!(
+            count(|event| matches!(event, PaifuEvent::Discarded { .. })),
+            drawn
+        );
+        let last = &paifu.entries.last().unwrap().event;
+        assert!(matches!(last, PaifuEvent::HandEnded { .. }));
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[tokio::test]
+    async fn ranked_match_updates_standings() {
+        let room = Room::matchmade("ABCDEF".to_string(), Ruleset::Sanma, Tier::General);
+        let (table, dir) = open("ranked", room).await;
+        let credentials = Credentials {
+            username: "tester".to_string(),
+            password: "hunter22".to_string(),
+        };
+        let account = table.accounts.register(&credentials).await.unwrap();
+        let req = JoinRequest {
+            id: account.id,
+            alias: account.username.clone(),
+        };
+        let (player, _) = table.seat(&req).await.unwrap();
+        for number in 1..=2 {
+            table.seat_bot(number).await.unwrap();
+        }
+
+        // East is the account's seat; it lets go of every tile it draws, the bots play the rest.
+        player.set_ready().await;
+        table.try_start().await;
+        while *table.status().borrow() == MatchStatus::Ongoing {
+            let tile = *table.match_manager.draw(Arc::clone(&player)).await.unwrap();
+            let action = GameAction {
+                action: Action::DISCARD,
+                target: Some(tile),
+            };
+            let tile = table
+                .match_manager
+                .discard(Arc::clone(&player), action)
+                .await;
+            table.after_discard(player.id, tile.unwrap()).await;
+            table.play_bots().await;
+        }
+
+        let record = written_record(&dir).await;
+        assert!(record.tier == Some(Tier::General));
+        assert_eq!(record.players[0].player_id, account.id);
+        let mut standing = None;
+        for _ in 0..100 {
+            standing = table.accounts.standing(account.id).await;
+            if standing.is_some_and(|standing| standing.games == 1) {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        let standing = standing.unwrap();
+        assert_eq!(standing.games, 1);
+        // First place wins against both bots, and its rank points take a novice up a rank.
+        assert!(standing.rating() > 1500 && standing.rank == 1);
+
+        // The hand was drawn, so there is nothing to build the win or call rates from.
+        let stats = table.results.stats(account.id).await;
+        assert_eq!((stats.matches, stats.hands), (1, 1));
+        assert!(stats.win_rate.is_none() && stats.call_rate.is_none());
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    // Joins racing for the same table each get a seat of their own, and an account only one of them.
+    #[tokio::test]
+    async fn concurrent_joins_take_distinct_seats() {
+        let room = Room::new("ABCDEF".to_string(), Ruleset::Hanchan, None);
+        let (table, dir) = open("concurrent", room).await;
+        // Holding the wall stops every join at its deal, after it picked a seat.
+        let wall = table.match_manager.state.wall.write().await;
+        let joins: Vec<_> = (0..8)
+            .map(|n| {
+                let table = Arc::clone(&table);
+                let req = JoinRequest {
+                    id: n % 4 + 1,
+                    alias: format!("Player {n}"),
+                };
+                return tokio::spawn(async move {
+                    return table.seat(&req).await.map(|(player, _)| player);
+                });
+            })
+            .collect();
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        drop(wall);
+
+        let mut seated = Vec::new();
+        for join in joins {
+            if let Ok(player) = join.await.unwrap() {
+                seated.push((player.id, *player.seat.read().await));
+            }
+        }
+        seated.sort_by_key(|(id, _)| *id);
+        let ids: Vec<i32> = seated.iter().map(|(id, _)| *id).collect();
+        assert_eq!(ids, [1, 2, 3, 4]);
+        let seats: HashSet<Seat> = seated.iter().map(|(_, seat)| *seat).collect();
+        assert_eq!(seats.len(), 4);
+
+        let dealt = table.match_manager.paifu.entries().await;
+        let dealt = dealt
+            .iter()
+            .filter(|entry| matches!(entry.event, PaifuEvent::Dealt { .. }))
+            .count();
+        assert_eq!(dealt, 4);
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[tokio::test]
+    async fn tables_everybody_left_are_interrupted() {
+        let room = Room::new("ABCDEF".to_string(), Ruleset::Sanma, None);
+        let (table, dir) = open("abandoned", room).await;
+        assert!(table.abandoned().await);
+        let req = JoinRequest {
+            id: 7,
+            alias: "Bunny".to_string(),
+        };
+        table.seat(&req).await.unwrap();
+        assert!(!table.abandoned().await);
+
+        // Their session is gone, as it is once the grace period after a disconnection ran out.
+        table.sessions.revoke(7).await;
+        assert!(table.abandoned().await);
+        table.interrupt().await;
+        assert!(*table.status().borrow() == MatchStatus::Interrupted);
+
+        // Nobody sat down at the tournament table yet, it waits for its participants.
+        let room = Room::tournament("ABCDEF".to_string(), Ruleset::Sanma, 1);
+        let (tournament, _) = open("abandoned", room).await;
+        assert!(!tournament.abandoned().await);
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
@@ -0,0 +1,187 @@
+use crate::protocol::event_log::MatchEvent;
+
+/// Who a packet is built for. Every match event goes through `MatchEvent::view`
+/// first, so hidden tiles are cut per recipient rather than per call site.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Viewer {
+    /// Seated player, by player id.
+    Player(i32),
+    /// Watching the table without a seat: public information only.
+    Spectator,
+    /// Spectator shown every hand, kept behind the table by a delay.
+    Full,
+}
+
+impl Viewer {
+    // Whether this viewer may see the given player's hidden tiles.
+    pub fn sees(&self, player_id: i32) -> bool {
+        match self {
+            Viewer::Player(id) => *id == player_id,
+            Viewer::Spectator => false,
+            Viewer::Full => true,
+        }
+    }
+}
+
+impl MatchEvent {
+    // The event as `viewer` may see it. Dealt hands and drawn tiles are only shown to their
+    // owner (and full-view spectators); discards, chat and failures are public.
+    pub fn view(&self, viewer: Viewer) -> MatchEvent {
+        match self {
+            MatchEvent::Dealt {
+                player_id,
+                seat,
+                hand,
+            } => MatchEvent::Dealt {
+                player_id: *player_id,
+                seat: *seat,
+                hand: hand.clone().filter(|_| viewer.sees(*player_id)),
+            },
+            MatchEvent::Drew { player_id, tile } => MatchEvent::Drew {
+                player_id: *player_id,
+                tile: tile.filter(|_| viewer.sees(*player_id)),
+            },
+            MatchEvent::Discarded { .. }
+            | MatchEvent::Chat { .. }
+            | MatchEvent::HandEnded { .. }
+            | MatchEvent::Failure { .. } => self.clone(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        game::enums::{Seat, Tile, TileKind},
+        protocol::{chat::Chat, codec::Codec, event_log::SequencedEvent, packet::PacketKind},
+        utils::models::{Deal, Discard, Draw},
+    };
+    use tokio::time::Instant;
+
+    const DRAWER: i32 = 7;
+    const TILE: Tile = Tile {
+        copy: 3,
+        kind: TileKind::Pinzu5,
+    };
+
+    // Every kind of recipient, and whether it may see the drawer's hidden tiles.
+    fn viewers() -> [(Viewer, bool); 5] {
+        return [
+            (Viewer::Player(DRAWER), true),
+            (Viewer::Player(DRAWER + 1), false),
+            (Viewer::Spectator, false),
+            (Viewer::Full, true),
+            // Spectator following another player.
+            (Viewer::Player(DRAWER + 2), false),
+        ];
+    }
+
+    fn sequenced(seq: i32, event: MatchEvent) -> SequencedEvent {
+        return SequencedEvent {
+            seq,
+            event,
+            at: Instant::now(),
+        };
+    }
+
+    #[test]
+    fn drawn_tile_only_reaches_drawer() {
+        let event = sequenced(
+            4,
+            MatchEvent::Drew {
+                player_id: DRAWER,
+                tile: Some(TILE),
+            },
+        );
+
+        for codec in [Codec::Cbor, Codec::Json] {
+            for (viewer, sees_tile) in viewers() {
+                let packet = event.packet(viewer, codec);
+                assert_eq!(packet.kind, PacketKind::Broadcast);
+                assert_eq!(packet.id, 4);
+
+                let draw: Draw = codec.decode(&packet.body[4..]).unwrap();
+                assert_eq!(draw.player_id, DRAWER);
+                assert_eq!(draw.tile.is_some(), sees_tile, "{viewer:?}");
+                if let Some(tile) = draw.tile {
+                    assert!(tile.kind == TILE.kind && tile.copy == TILE.copy);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn dealt_hand_only_reaches_owner() {
+        let event = sequenced(
+            1,
+            MatchEvent::Dealt {
+                player_id: DRAWER,
+                seat: Seat::South,
+                hand: Some(vec![TILE; 13]),
+            },
+        );
+
+        for (viewer, sees_hand) in viewers() {
+            let packet = event.packet(viewer, Codec::Cbor);
+            let deal: Deal = Codec::Cbor.decode(&packet.body[4..]).unwrap();
+            assert!(deal.seat == Seat::South);
+            assert_eq!(deal.hand.is_some(), sees_hand, "{viewer:?}");
+        }
+    }
+
+    // The other packets are byte for byte the same as a spectator's, so nothing
+    // beyond the public view can be hiding in their encoding.
+    #[test]
+    fn opponents_get_the_public_view() {
+        let events = [
+            MatchEvent::Dealt {
+                player_id: DRAWER,
+                seat: Seat::South,
+                hand: Some(vec![TILE; 13]),
+            },
+            MatchEvent::Drew {
+                player_id: DRAWER,
+                tile: Some(TILE),
+            },
+            MatchEvent::Discarded {
+                player_id: DRAWER,
+                tile: TILE,
+            },
+            MatchEvent::Chat {
+                player_id: DRAWER,
+                chat: Chat::Message("tenpai?".to_string()),
+            },
+            MatchEvent::Failure {
+                message: "boom".to_string(),
+            },
+        ];
+
+        for event in events {
+            let event = sequenced(1, event);
+            let public = event.packet(Viewer::Spectator, Codec::Json);
+            for (viewer, sees) in viewers() {
+                if !sees {
+                    assert_eq!(event.packet(viewer, Codec::Json).body, public.body);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn discards_are_public() {
+        let event = sequenced(
+            2,
+            MatchEvent::Discarded {
+                player_id: DRAWER,
+                tile: TILE,
+            },
+        );
+
+        for (viewer, _) in viewers() {
+            let packet = event.packet(viewer, Codec::Cbor);
+            let discard: Discard = Codec::Cbor.decode(&packet.body[4..]).unwrap();
+            assert!(discard.tile_kind == TILE.kind && discard.tile_copy == TILE.copy);
+        }
+    }
+}
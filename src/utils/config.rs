@@ -0,0 +1,369 @@
+use std::{net::Ipv4Addr, time::Duration};
+
+use serde::Deserialize;
+
+use crate::utils::errors::Error;
+
+pub const CONFIG_PATH: &str = "./config.json";
+
+/// Server settings read from `config.json`. Every field is optional and falls back to its default.
+#[derive(Deserialize, Clone)]
+#[serde(default)]
+pub struct Config {
+    pub host: Ipv4Addr,
+    /// Raw TCP listener. The log manager takes the port right after it.
+    pub port: u16,
+    /// WebSocket listener for clients that cannot open raw sockets (browsers).
+    pub websocket_port: Option<u16>,
+    /// Wraps both listeners in TLS when present.
+    pub tls: Option<TlsConfig>,
+    pub heartbeat: HeartbeatConfig,
+    pub session: SessionConfig,
+    pub outbound: OutboundConfig,
+    pub spectator: SpectatorConfig,
+    pub chat: ChatConfig,
+    pub tables: TableConfig,
+    pub matchmaking: MatchmakingConfig,
+    pub accounts: AccountConfig,
+    pub ranking: RankingConfig,
+    pub tournaments: TournamentConfig,
+    /// Directory finished matches are exported to as replays, none when missing.
+    pub replay_dir: Option<String>,
+    /// Directory the paifu of every match (each change to its game state) is written to, none when null.
+    pub paifu_dir: Option<String>,
+    /// JSON lines file every finished match is appended to; player statistics are built from it.
+    pub results_path: String,
+}
+
+/// PEM encoded certificate chain and private key.
+#[derive(Deserialize, Clone)]
+pub struct TlsConfig {
+    pub cert: String,
+    pub key: String,
+}
+
+/// How often connected clients are pinged, and how long they may stay silent before
+/// their seat is marked disconnected.
+#[derive(Deserialize, Clone, Copy)]
+#[serde(default)]
+pub struct HeartbeatConfig {
+    pub interval_ms: u64,
+    pub timeout_ms: u64,
+}
+
+impl HeartbeatConfig {
+    pub fn interval(&self) -> Duration {
+        Duration::from_millis(self.interval_ms)
+    }
+
+    pub fn timeout(&self) -> Duration {
+        Duration::from_millis(self.timeout_ms)
+    }
+}
+
+impl Default for HeartbeatConfig {
+    fn default() -> Self {
+        Self {
+            interval_ms: 5_000,
+            timeout_ms: 15_000,
+        }
+    }
+}
+
+/// How long a disconnected player's session token stays valid for reconnection.
+#[derive(Deserialize, Clone, Copy)]
+#[serde(default)]
+pub struct SessionConfig {
+    pub grace_ms: u64,
+}
+
+impl SessionConfig {
+    pub fn grace(&self) -> Duration {
+        Duration::from_millis(self.grace_ms)
+    }
+}
+
+impl Default for SessionConfig {
+    fn default() -> Self {
+        Self { grace_ms: 120_000 }
+    }
+}
+
+/// Packets each client may have waiting to be written. A client whose queue is full
+/// has fallen too far behind and is disconnected.
+#[derive(Deserialize, Clone, Copy)]
+#[serde(default)]
+pub struct OutboundConfig {
+    pub capacity: usize,
+}
+
+impl Default for OutboundConfig {
+    fn default() -> Self {
+        Self { capacity: 64 }
+    }
+}
+
+/// How far behind the table spectators that see hidden tiles are kept, and whether the tables
+/// left out of the lobby (queue and tournament ones) may be watched.
+#[derive(Deserialize, Clone, Copy)]
+#[serde(default)]
+pub struct SpectatorConfig {
+    pub delay_ms: u64,
+    pub unlisted: bool,
+}
+
+impl SpectatorConfig {
+    pub fn delay(&self) -> Duration {
+        Duration::from_millis(self.delay_ms)
+    }
+}
+
+impl Default for SpectatorConfig {
+    fn default() -> Self {
+        Self {
+            delay_ms: 30_000,
+            unlisted: false,
+        }
+    }
+}
+
+/// Table chat rules. Ranked rooms turn free text off and keep the preset reactions.
+#[derive(Deserialize, Clone, Copy)]
+#[serde(default)]
+pub struct ChatConfig {
+    pub max_length: usize,
+    /// Messages (reactions included) a player may send per window.
+    pub rate_limit: usize,
+    pub rate_window_ms: u64,
+    pub free_text: bool,
+    /// Whether chat is kept in exported replays. Off for competitive play.
+    pub in_replays: bool,
+}
+
+impl ChatConfig {
+    pub fn rate_window(&self) -> Duration {
+        Duration::from_millis(self.rate_window_ms)
+    }
+}
+
+impl Default for ChatConfig {
+    fn default() -> Self {
+        Self {
+            max_length: 200,
+            rate_limit: 5,
+            rate_window_ms: 10_000,
+            free_text: true,
+            in_replays: true,
+        }
+    }
+}
+
+/// How many matches the server hosts at once, and how long a finished one is kept open.
+#[derive(Deserialize, Clone, Copy)]
+#[serde(default)]
+pub struct TableConfig {
+    pub max: usize,
+    /// Time players of a finished match have to read the results before the table is closed.
+    pub linger_ms: u64,
+    /// Match events held for a client's broadcast task. One that falls further behind is sent
+    /// the rest from the event log.
+    pub event_capacity: usize,
+}
+
+impl TableConfig {
+    pub fn linger(&self) -> Duration {
+        Duration::from_millis(self.linger_ms)
+    }
+}
+
+impl Default for TableConfig {
+    fn default() -> Self {
+        Self {
+            max: 64,
+            linger_ms: 30_000,
+            event_capacity: 256,
+        }
+    }
+}
+
+/// How the matchmaking queue forms tables. Players are only seated together while their
+/// ratings are within the band, which starts at `band` and widens by `widen_per_s` every
+/// second spent waiting, up to `max_band`. Seats still free after `bots_after_ms` go to bots.
+#[derive(Deserialize, Clone, Copy)]
+#[serde(default)]
+pub struct MatchmakingConfig {
+    pub tick_ms: u64,
+    pub band: u32,
+    pub widen_per_s: u32,
+    pub max_band: u32,
+    pub bots_after_ms: u64,
+}
+
+impl MatchmakingConfig {
+    pub fn tick(&self) -> Duration {
+        Duration::from_millis(self.tick_ms)
+    }
+
+    pub fn bots_after(&self) -> Duration {
+        Duration::from_millis(self.bots_after_ms)
+    }
+
+    // Rating difference a player accepts after waiting this long.
+    pub fn band_after(&self, waited: Duration) -> u32 {
+        let widened = self.widen_per_s.saturating_mul(waited.as_secs() as u32);
+        return self.band.saturating_add(widened).min(self.max_band);
+    }
+}
+
+impl Default for MatchmakingConfig {
+    fn default() -> Self {
+        Self {
+            tick_ms: 1_000,
+            band: 100,
+            widen_per_s: 10,
+            max_band: 1_000,
+            bots_after_ms: 60_000,
+        }
+    }
+}
+
+/// Where player accounts are kept, whether players may connect without one, and where the
+/// standings of past seasons are archived.
+#[derive(Deserialize, Clone)]
+#[serde(default)]
+pub struct AccountConfig {
+    pub path: String,
+    pub guests: bool,
+    pub seasons_dir: String,
+}
+
+impl Default for AccountConfig {
+    fn default() -> Self {
+        Self {
+            path: "./accounts.json".to_string(),
+            guests: true,
+            seasons_dir: "./seasons".to_string(),
+        }
+    }
+}
+
+/// How far one ranked match moves a rating: `k_factor` is split over the pairs of players at the table.
+/// Seasons last `season_days`; at each rollover ratings keep `soft_reset` of their distance to the
+/// starting rating. Leaderboards are sent `page_size` players at a time, and only rank players with
+/// `placement_games` ranked games by average placement.
+#[derive(Deserialize, Clone, Copy)]
+#[serde(default)]
+pub struct RankingConfig {
+    pub k_factor: f64,
+    pub season_days: u64,
+    pub soft_reset: f64,
+    pub page_size: usize,
+    pub placement_games: u32,
+}
+
+impl RankingConfig {
+    // Seasons are numbered from 1, counted from the Unix epoch.
+    pub fn season_at(&self, seconds: u64) -> u32 {
+        return (seconds / (self.season_days.max(1) * 86_400)) as u32 + 1;
+    }
+
+    pub fn current_season(&self) -> u32 {
+        let now = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map_or(0, |elapsed| elapsed.as_secs());
+        return self.season_at(now);
+    }
+}
+
+impl Default for RankingConfig {
+    fn default() -> Self {
+        Self {
+            k_factor: 32.0,
+            season_days: 90,
+            soft_reset: 0.5,
+            page_size: 20,
+            placement_games: 5,
+        }
+    }
+}
+
+/// Where tournaments are kept, which accounts may run them (by username), and how a finished
+/// table scores: final scores are counted against `return_score` in thousands, plus the uma of the
+/// placement (`uma` for four players, `sanma_uma` for three) and the oka for first place.
+#[derive(Deserialize, Clone)]
+#[serde(default)]
+pub struct TournamentConfig {
+    pub path: String,
+    pub admins: Vec<String>,
+    pub return_score: i32,
+    pub uma: [f64; 4],
+    pub sanma_uma: [f64; 3],
+}
+
+impl TournamentConfig {
+    pub fn is_admin(&self, username: &str) -> bool {
+        return self
+            .admins
+            .iter()
+            .any(|admin| admin.eq_ignore_ascii_case(username));
+    }
+}
+
+impl Default for TournamentConfig {
+    fn default() -> Self {
+        Self {
+            path: "./tournaments.json".to_string(),
+            admins: Vec::new(),
+            return_score: 30_000,
+            uma: [15.0, 5.0, -5.0, -15.0],
+            sanma_uma: [15.0, 0.0, -15.0],
+        }
+    }
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            host: Ipv4Addr::new(127, 0, 0, 1),
+            port: 3000,
+            websocket_port: Some(3002),
+            tls: None,
+            heartbeat: HeartbeatConfig::default(),
+            session: SessionConfig::default(),
+            outbound: OutboundConfig::default(),
+            spectator: SpectatorConfig::default(),
+            chat: ChatConfig::default(),
+            tables: TableConfig::default(),
+            matchmaking: MatchmakingConfig::default(),
+            accounts: AccountConfig::default(),
+            ranking: RankingConfig::default(),
+            tournaments: TournamentConfig::default(),
+            replay_dir: None,
+            paifu_dir: Some("./paifu".to_string()),
+            results_path: "./results.jsonl".to_string(),
+        }
+    }
+}
+
+impl Config {
+    // A missing file means defaults; a file that exists but cannot be read or parsed is an error.
+    pub fn load(path: &str) -> Result<Self, Error> {
+        let config: Self = match std::fs::read_to_string(path) {
+            Err(error) if error.kind() == std::io::ErrorKind::NotFound => Self::default(),
+            Err(_) => return Err(Error::InitializationFailed(6)),
+            Ok(content) => {
+                serde_json::from_str(&content).map_err(|_| Error::InitializationFailed(6))?
+            }
+        };
+        config.validate()?;
+        return Ok(config);
+    }
+
+    // Periodic timers cannot run with a zero period.
+    fn validate(&self) -> Result<(), Error> {
+        if self.heartbeat.interval_ms == 0 || self.matchmaking.tick_ms == 0 {
+            return Err(Error::InitializationFailed(18));
+        }
+        return Ok(());
+    }
+}
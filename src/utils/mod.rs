@@ -1,3 +1,4 @@
-pub mod errors;
-pub mod models;
+pub mod config;
 pub mod types;
+
+pub use mahjong_protocol::{errors, models};
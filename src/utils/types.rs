@@ -1,5 +1,8 @@
-use crate::network::client::Client;
+use crate::network::{client::Client, spectator::Spectator};
+use crate::protocol::protocol::Protocol;
 use std::{collections::HashMap, sync::Arc};
 use tokio::sync::RwLock;
 
 pub type ClientPool = Arc<RwLock<HashMap<i32, Arc<Client>>>>;
+pub type SpectatorPool = Arc<RwLock<HashMap<i32, Arc<Spectator>>>>;
+pub type TablePool = Arc<RwLock<HashMap<i32, Arc<Protocol>>>>;